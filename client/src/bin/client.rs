@@ -1,32 +1,143 @@
-﻿use tokio::io::{stdin, AsyncReadExt, AsyncWriteExt};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+﻿use std::env;
+use std::process::ExitCode;
 
-use client::core::client::{ClientService, MyNonSecureClientService};
+use tokio::io::{stdin, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use client::core::client::{ClientService, MyClientService, MyNonSecureClientService};
 
 const CONNECTION_HOST: &str = "localhost";
 const CONNECTION_PORT: &str = "6973";
-const _CERT_FILE_PATH: &str =
-    "/Users/chantapat.t/CLionProjects/mini-redis-rs/client/src/config/ssl/client.crt";
-const _KEY_FILE_PATH: &str =
-    "/Users/chantapat.t/CLionProjects/mini-redis-rs/client/src/config/ssl/client.key";
+const CERT_FILE_PATH: &str = "certs/client.crt";
+const KEY_FILE_PATH: &str = "certs/client.key";
 const DEFAULT_BUFFER_SIZE: usize = 1024;
 
+const USAGE: &str = "\
+usage: client [--host <host>] [--port <port>] [--tls] [--cert <file>] [--key <file>]
+              [--cacert <file>] [--sni <name>] [--pin <file>] [--insecure]
+
+  --tls          connects over TLS, implied by the other TLS options
+  --cert --key   the client certificate and its key
+  --cacert       a CA bundle trusted besides the public CAs
+  --sni          the name the server certificate is verified against instead of the host
+  --pin          only accepts a server presenting one of the certificates of the file
+  --insecure     accepts any server certificate, for local development only";
+
+#[derive(Default)]
+struct Options {
+    host: Option<String>,
+    port: Option<String>,
+    tls: bool,
+    cert: Option<String>,
+    key: Option<String>,
+    cacert: Option<String>,
+    sni: Option<String>,
+    pin: Option<String>,
+    insecure: bool,
+}
+
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} expects a value", arg));
+            match arg.as_str() {
+                "--host" => options.host = Some(value()?),
+                "--port" => options.port = Some(value()?),
+                "--tls" => options.tls = true,
+                "--cert" => options.cert = Some(value()?),
+                "--key" => options.key = Some(value()?),
+                "--cacert" => options.cacert = Some(value()?),
+                "--sni" => options.sni = Some(value()?),
+                "--pin" => options.pin = Some(value()?),
+                "--insecure" => options.insecure = true,
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+        let tls_options = [&options.cert, &options.key, &options.cacert, &options.sni, &options.pin];
+        options.tls |= options.insecure || tls_options.iter().any(|option| option.is_some());
+        if options.insecure && options.pin.is_some() {
+            return Err("--insecure and --pin can't be used together".to_owned());
+        }
+        Ok(options)
+    }
+
+    fn tls_client(&self, host: &str, port: &str) -> MyClientService {
+        let mut client_service = MyClientService::new(
+            host,
+            port,
+            self.cert.as_deref().unwrap_or(CERT_FILE_PATH),
+            self.key.as_deref().unwrap_or(KEY_FILE_PATH),
+        );
+        if let Some(cacert) = &self.cacert {
+            client_service = client_service.with_ca_file_path(cacert);
+        }
+        if let Some(sni) = &self.sni {
+            client_service = client_service.with_server_name(sni);
+        }
+        if let Some(pin) = &self.pin {
+            client_service = client_service.with_pinned_cert(pin);
+        }
+        if self.insecure {
+            client_service = client_service.insecure();
+        }
+        client_service
+    }
+}
+
 #[tokio::main]
-async fn main() {
-    // let client_service = MyClientService::new(CONNECTION_ADDRESS, CERT_FILE_PATH, KEY_FILE_PATH);
-    let client_service = MyNonSecureClientService::new(CONNECTION_HOST, CONNECTION_PORT);
-    let socket = client_service.connect().await;
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let host = options.host.as_deref().unwrap_or(CONNECTION_HOST);
+    let port = options.port.as_deref().unwrap_or(CONNECTION_PORT);
+
+    let result = if options.tls {
+        run(options.tls_client(host, port)).await
+    } else {
+        run(MyNonSecureClientService::new(host, port)).await
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+async fn run<C: ClientService>(client_service: C) -> std::io::Result<()>
+where
+    C::Stream: 'static,
+{
+    let socket = client_service.connect().await?;
     println!("connected to server");
-    let (mut reader, mut writer) = socket.into_split();
+    let (mut reader, mut writer) = tokio::io::split(socket);
 
     tokio::spawn(async move { handle_message_from_server(&mut reader).await });
     handle_message_from_client(&mut writer).await;
+    Ok(())
 }
 
-async fn handle_message_from_server(reader: &mut OwnedReadHalf) {
+async fn handle_message_from_server(reader: &mut (impl AsyncRead + Unpin)) {
     loop {
         let mut buffer = [0u8; DEFAULT_BUFFER_SIZE];
-        let size = reader.read(&mut buffer).await.unwrap();
+        let size = match reader.read(&mut buffer).await {
+            Ok(size) => size,
+            Err(error) => {
+                eprintln!("error reading from server: {}", error);
+                break;
+            }
+        };
         if size == 0 {
             println!("socket is closed");
             break;
@@ -36,7 +147,7 @@ async fn handle_message_from_server(reader: &mut OwnedReadHalf) {
     }
 }
 
-async fn handle_message_from_client(writer: &mut OwnedWriteHalf) {
+async fn handle_message_from_client(writer: &mut (impl AsyncWrite + Unpin)) {
     loop {
         let mut buffer = [0u8; DEFAULT_BUFFER_SIZE];
         if let Err(error) = stdin().read(&mut buffer).await {
@@ -47,5 +158,6 @@ async fn handle_message_from_client(writer: &mut OwnedWriteHalf) {
             eprintln!("error writing to server: {}", error);
             let _ = writer.shutdown().await;
         }
+        let _ = writer.flush().await;
     }
 }
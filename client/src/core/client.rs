@@ -3,13 +3,27 @@ use std::path::Path;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
 use tokio_rustls::{rustls, TlsConnector};
 use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
 
+use crate::core::verifier::{InsecureVerifier, PinnedCertVerifier};
+
 #[async_trait]
 pub trait ClientService: Send + Sync {
-    async fn connect(&self) -> TcpStream;
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin;
+
+    async fn connect(&self) -> io::Result<Self::Stream>;
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum ServerVerification {
+    #[default]
+    Roots,
+    Pinned(String),
+    Insecure,
 }
 
 pub struct MyClientService {
@@ -17,6 +31,9 @@ pub struct MyClientService {
     connection_port: String,
     cert_file_path: String,
     key_file_path: String,
+    ca_file_path: Option<String>,
+    server_name: Option<String>,
+    server_verification: ServerVerification,
 }
 
 impl MyClientService {
@@ -31,18 +48,68 @@ impl MyClientService {
             connection_port: connection_port.to_owned(),
             cert_file_path: cert_file_path.to_owned(),
             key_file_path: key_file_path.to_owned(),
+            ca_file_path: None,
+            server_name: None,
+            server_verification: ServerVerification::Roots,
         }
     }
 
-    fn load_tls_config(cert_file_path: &str, key_file_path: &str) -> ClientConfig {
-        let root_cert_store = Self::get_default_root_ca();
-        let certs = utils::cert::load_cert(Path::new(cert_file_path)).unwrap();
-        let mut keys = utils::cert::load_key(Path::new(key_file_path)).unwrap();
-        ClientConfig::builder()
+    pub fn with_ca_file_path(mut self, ca_file_path: &str) -> Self {
+        self.ca_file_path = Some(ca_file_path.to_owned());
+        self
+    }
+
+    pub fn with_server_name(mut self, server_name: &str) -> Self {
+        self.server_name = Some(server_name.to_owned());
+        self
+    }
+
+    pub fn with_pinned_cert(mut self, pinned_cert_file_path: &str) -> Self {
+        self.server_verification = ServerVerification::Pinned(pinned_cert_file_path.to_owned());
+        self
+    }
+
+    /// Accepts any server certificate, the connection is encrypted but the server is not authenticated.
+    /// For local development only.
+    pub fn insecure(mut self) -> Self {
+        self.server_verification = ServerVerification::Insecure;
+        self
+    }
+
+    fn load_tls_config(&self) -> io::Result<ClientConfig> {
+        let invalid = |err: rustls::Error| io::Error::new(io::ErrorKind::InvalidInput, err);
+        let mut root_cert_store = Self::get_default_root_ca();
+        if let Some(ca_file_path) = &self.ca_file_path {
+            for cert in utils::cert::load_cert(Path::new(ca_file_path))? {
+                root_cert_store
+                    .add(&cert)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            }
+        }
+        let certs = utils::cert::load_cert(Path::new(&self.cert_file_path))?;
+        let key = utils::cert::load_key(Path::new(&self.key_file_path))?;
+        let mut config = ClientConfig::builder()
             .with_safe_defaults()
             .with_root_certificates(root_cert_store)
-            .with_client_auth_cert(certs, keys.remove(0))
-            .unwrap()
+            .with_client_auth_cert(certs, key)
+            .map_err(invalid)?;
+        match &self.server_verification {
+            ServerVerification::Roots => {}
+            ServerVerification::Pinned(pinned_cert_file_path) => {
+                let pinned_certs = utils::cert::load_cert(Path::new(pinned_cert_file_path))?;
+                config
+                    .dangerous()
+                    .set_certificate_verifier(Arc::new(PinnedCertVerifier::new(pinned_certs)));
+            }
+            ServerVerification::Insecure => {
+                eprintln!("***************************************************************************");
+                eprintln!("* WARNING: the certificate of the server is NOT verified, anyone can      *");
+                eprintln!("* impersonate it and read the traffic. Never use --insecure in production *");
+                eprintln!("***************************************************************************");
+                config.dangerous().set_certificate_verifier(Arc::new(InsecureVerifier));
+            }
+        }
+        Ok(config)
     }
 
     fn get_default_root_ca() -> RootCertStore {
@@ -74,30 +141,33 @@ impl MyNonSecureClientService {
 
 #[async_trait]
 impl ClientService for MyClientService {
-    async fn connect(&self) -> TcpStream {
-        let config = Self::load_tls_config(&self.cert_file_path, &self.key_file_path);
+    type Stream = TlsStream<TcpStream>;
+
+    async fn connect(&self) -> io::Result<TlsStream<TcpStream>> {
+        let config = self.load_tls_config()?;
         let connector = TlsConnector::from(Arc::new(config));
 
         let address = format!("{}:{}", self.connection_host, self.connection_port);
-        let socket = TcpStream::connect(address.clone())
-            .await
-            .unwrap_or_else(|_| panic!("unable to connect to: {}", address));
-
-        let domain = rustls::ServerName::try_from(self.connection_host.as_str())
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid dnsname"))
-            .unwrap();
-        let tls_socket = connector.connect(domain, socket).await.unwrap();
-        let (stream, _client_connection) = tls_socket.into_inner();
-        stream
+        let socket = TcpStream::connect(address.clone()).await.map_err(|err| {
+            io::Error::new(err.kind(), format!("unable to connect to {}: {}", address, err))
+        })?;
+
+        let server_name = self.server_name.as_deref().unwrap_or(&self.connection_host);
+        let domain = rustls::ServerName::try_from(server_name).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("invalid server name '{}'", server_name))
+        })?;
+        connector.connect(domain, socket).await
     }
 }
 
 #[async_trait]
 impl ClientService for MyNonSecureClientService {
-    async fn connect(&self) -> TcpStream {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<TcpStream> {
         let address = format!("{}:{}", self.connection_host, self.connection_port);
-        TcpStream::connect(address.clone())
-            .await
-            .unwrap_or_else(|_| panic!("unable to connect to: {}", address))
+        TcpStream::connect(address.clone()).await.map_err(|err| {
+            io::Error::new(err.kind(), format!("unable to connect to {}: {}", address, err))
+        })
     }
 }
@@ -0,0 +1,50 @@
+use std::time::SystemTime;
+
+use tokio_rustls::rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::{Certificate, Error, ServerName};
+
+/// Accepts the server presenting one of the pinned certificates, whoever signed it and whatever its names.
+/// The server still proves that it holds the key of the certificate during the handshake.
+pub struct PinnedCertVerifier {
+    pinned_certs: Vec<Certificate>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(pinned_certs: Vec<Certificate>) -> Self {
+        Self { pinned_certs }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if self.pinned_certs.contains(end_entity) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(Error::General("the server certificate is not the pinned one".to_owned()))
+        }
+    }
+}
+
+pub struct InsecureVerifier;
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
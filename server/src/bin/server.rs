@@ -1,35 +1,178 @@
-﻿use std::io;
+﻿use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
 use tokio::sync::oneshot;
 
+use server::core::auth::MyAuthService;
 use server::core::broker::MyBrokerService;
-use server::core::cache::reader::MyCacheReader;
-use server::core::cache::writer::MyCacheWriter;
+use server::core::cache::history::MyHistoryStore;
+use server::core::cache::none::NoCache;
+use server::core::cache::reader::{CacheReaderService, MyCacheReader};
+use server::core::cache::writer::{CacheWriterService, MyCacheWriter};
+use server::core::config::service::{ConfigListener, MyConfigService};
+use server::core::config::{Config, Persistence, USAGE};
 use server::core::handler::MyHandlerService;
+use server::core::logger;
 use server::core::redis::MyRedisService;
-use server::core::server::{MyNonSecureServerService, ServerService};
+use server::core::script::MyScriptService;
+use server::core::server::{ClientLimits, MyNonSecureServerService, MyServerService, ServerService};
+use server::core::tls::MyTlsService;
 
-const BINDING_HOST: &str = "localhost";
-const BINDING_PORT: &str = "6973";
-const _CERT_FILE_PATH: &str =
-    "/Users/chantapat.t/CLionProjects/mini-redis-rs/server/src/config/ssl/server.crt";
-const _KEY_FILE_PATH: &str =
-    "/Users/chantapat.t/CLionProjects/mini-redis-rs/server/src/config/ssl/server.key";
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "gen-certs") {
+        return gen_certs(&args[1..]);
+    }
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let (config, config_path) = match Config::from_args(args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("invalid configuration: {}", err);
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    logger::init(config.loglevel);
+
+    if let Err(err) = start(config, config_path).await {
+        log::error!("{}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
 
-const CACHE_FOLDER: &str = "/Users/chantapat.t/CLionProjects/mini-redis-rs/cache";
+fn gen_certs(args: &[String]) -> ExitCode {
+    let mut out: Option<PathBuf> = None;
+    let mut server_names: Vec<String> = Vec::new();
+    let mut client_names: Vec<String> = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match (arg.as_str(), args.next()) {
+            ("--help" | "-h", _) => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            (_, Some(value)) => value.clone(),
+            (_, None) => {
+                eprintln!("{} expects a value\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
+            }
+        };
+        match arg.as_str() {
+            "--out" => out = Some(PathBuf::from(value)),
+            "--san" => server_names.push(value),
+            "--client" => client_names.push(value),
+            _ => {
+                eprintln!("unexpected argument '{}'\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(out) = out else {
+        eprintln!("--out is required\n{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    if server_names.is_empty() {
+        server_names = vec!["localhost".to_owned(), "127.0.0.1".to_owned()];
+    }
+    if client_names.is_empty() {
+        client_names = vec!["client".to_owned()];
+    }
 
-#[tokio::main]
-async fn main() -> io::Result<()> {
-    let cache_reader_service = Arc::new(MyCacheReader::new(CACHE_FOLDER));
-    let cache_writer_service = Arc::new(MyCacheWriter::new(CACHE_FOLDER));
-    let redis_service = Arc::new(MyRedisService::new(
-        cache_reader_service,
-        cache_writer_service,
-    ));
-    let broker_service = Arc::new(MyBrokerService::new());
-    let handler_service = Arc::new(MyHandlerService::new(redis_service, broker_service));
-
-    let server_service = MyNonSecureServerService::new(BINDING_HOST, BINDING_PORT, handler_service);
+    if let Err(err) = utils::dev_certs::generate(&out, &server_names, &client_names) {
+        eprintln!("failed to generate the certificates: {}", err);
+        return ExitCode::FAILURE;
+    }
+    println!(
+        "generated in {}: ca.crt, server.crt for {}, {} with their keys",
+        out.display(),
+        server_names.join(", "),
+        client_names.iter().map(|name| format!("{}.crt", name)).collect::<Vec<_>>().join(", ")
+    );
+    ExitCode::SUCCESS
+}
+
+async fn start(config: Config, config_path: Option<PathBuf>) -> io::Result<()> {
+    let cache_folder = config.dir.display().to_string();
+    let mut broker_service =
+        MyBrokerService::new().with_output_buffer_limits(config.client_output_buffer_limit);
+    let mut cache_listener: Option<Arc<dyn ConfigListener>> = None;
+    let (cache_reader_service, cache_writer_service): (
+        Arc<dyn CacheReaderService>,
+        Arc<dyn CacheWriterService>,
+    ) = match config.persistence {
+        Persistence::Files => {
+            tokio::fs::create_dir_all(&config.dir).await?;
+            let history_store = Arc::new(MyHistoryStore::new(&cache_folder));
+            broker_service = broker_service.with_history_store(history_store);
+            let cache_writer = Arc::new(MyCacheWriter::new(&cache_folder).with_appendfsync(config.appendfsync));
+            cache_listener = Some(cache_writer.clone());
+            (Arc::new(MyCacheReader::new(&cache_folder)), cache_writer)
+        }
+        Persistence::None => (Arc::new(NoCache), Arc::new(NoCache)),
+    };
+    let broker_service = Arc::new(broker_service);
+    let redis_service = Arc::new(
+        MyRedisService::new(cache_reader_service, cache_writer_service)
+            .with_databases(config.databases)
+            .with_maxmemory(config.maxmemory())
+            .with_keyspace_notifications(broker_service.clone(), config.keyspace_events()),
+    );
+    let script_service = Arc::new(MyScriptService::new().with_time_limit(config.lua_time_limit));
+    let mut auth_service = MyAuthService::new().with_requirepass(&config.requirepass);
+    if let Some(aclfile) = &config.aclfile {
+        auth_service = auth_service.with_acl_file(aclfile.clone())?;
+    }
+    let auth_service = Arc::new(auth_service);
+    let client_limits = ClientLimits::default();
+    client_limits.apply_config(&config);
+
+    let mut config_service = MyConfigService::new(config.clone())
+        .with_listener(redis_service.clone())
+        .with_listener(broker_service.clone())
+        .with_listener(script_service.clone())
+        .with_listener(auth_service.clone())
+        .with_listener(Arc::new(client_limits.clone()))
+        .with_listener(Arc::new(|config: &Config| logger::set_level(config.loglevel)));
+    if let Some(cache_listener) = cache_listener {
+        config_service = config_service.with_listener(cache_listener);
+    }
+    if let Some(path) = config_path {
+        config_service = config_service.with_path(path);
+    }
+    let mut handler_service = MyHandlerService::new(redis_service, broker_service)
+        .with_script_service(script_service)
+        .with_config_service(Arc::new(config_service))
+        .with_auth_service(auth_service)
+        .with_cert_user(config.tls_auth_clients_user);
+
+    let port = config.port.to_string();
+    let server_service: Box<dyn ServerService> = match (&config.tls_cert_file, &config.tls_key_file) {
+        (Some(cert_file), Some(key_file)) if config.tls => {
+            let mut tls_service =
+                MyTlsService::new(&cert_file.display().to_string(), &key_file.display().to_string())?;
+            if let Some(ca_cert_file) = &config.tls_ca_cert_file {
+                tls_service = tls_service
+                    .with_client_auth(&ca_cert_file.display().to_string(), config.tls_auth_clients)?;
+            }
+            let tls_service = Arc::new(tls_service);
+            handler_service = handler_service.with_tls_service(tls_service.clone());
+            Box::new(
+                MyServerService::new(&config.bind, &port, tls_service, Arc::new(handler_service))
+                    .with_client_limits(client_limits),
+            )
+        }
+        _ => Box::new(
+            MyNonSecureServerService::new(&config.bind, &port, Arc::new(handler_service))
+                .with_client_limits(client_limits),
+        ),
+    };
 
     let (tx, _rx) = oneshot::channel::<u16>();
     server_service.start(tx).await
@@ -0,0 +1,515 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use crate::core::command::registry::{Command, CommandFlags};
+use crate::core::glob::glob_match;
+use crate::core::parser::parse_command;
+
+pub const ACL_LOG_MAX_LEN: usize = 128;
+
+const ACL_LOG_GROUPING_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum CommandSelector {
+    All,
+    Category(String),
+    Name(String),
+}
+
+impl CommandSelector {
+    fn matches(&self, command: &Command) -> bool {
+        match self {
+            CommandSelector::All => true,
+            CommandSelector::Category(category) => CommandFlags::from_name(category)
+                .is_some_and(|flags| command.flags().contains(flags)),
+            CommandSelector::Name(name) => {
+                let command = command.name();
+                command == name || command.strip_prefix(name.as_str()).is_some_and(|sub| sub.starts_with('|'))
+            }
+        }
+    }
+
+    fn describe(&self, allowed: bool) -> String {
+        let sign = if allowed { '+' } else { '-' };
+        match self {
+            CommandSelector::All => format!("{}@all", sign),
+            CommandSelector::Category(category) => format!("{}@{}", sign, category),
+            CommandSelector::Name(name) => format!("{}{}", sign, name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Denial {
+    Command,
+    Key(String),
+    Channel(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct User {
+    name: String,
+    enabled: bool,
+    nopass: bool,
+    passwords: Vec<String>,
+    /// the allowing and denying rules in order, the last one matching a command wins
+    commands: Vec<(bool, CommandSelector)>,
+    key_patterns: Vec<String>,
+    channel_patterns: Vec<String>,
+}
+
+impl User {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            enabled: false,
+            nopass: false,
+            passwords: Vec::new(),
+            commands: Vec::new(),
+            key_patterns: Vec::new(),
+            channel_patterns: Vec::new(),
+        }
+    }
+
+    pub fn new_default(name: &str) -> Self {
+        let mut user = Self::new(name);
+        for rule in ["on", "nopass", "allkeys", "allchannels", "allcommands"] {
+            // valid rules
+            let _ = user.apply_rule(rule);
+        }
+        user
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.enabled && self.nopass
+    }
+
+    /// Applies the rules in order, none of them is applied if one is invalid.
+    pub fn apply_rules(&mut self, rules: &[String]) -> Result<(), String> {
+        let mut user = self.clone();
+        for rule in rules.iter() {
+            user.apply_rule(rule)?;
+        }
+        *self = user;
+        Ok(())
+    }
+
+    fn apply_rule(&mut self, rule: &str) -> Result<(), String> {
+        let invalid = || format!("invalid rule '{}'", rule);
+        match rule.to_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.key_patterns = vec!["*".to_owned()],
+            "resetkeys" => self.key_patterns.clear(),
+            "allchannels" => self.channel_patterns = vec!["*".to_owned()],
+            "resetchannels" => self.channel_patterns.clear(),
+            "allcommands" | "+@all" => self.commands = vec![(true, CommandSelector::All)],
+            "nocommands" | "-@all" => self.commands.clear(),
+            "reset" => *self = Self::new(&self.name),
+            _ => {
+                let (prefix, value) = rule.split_at(rule.chars().next().map_or(0, char::len_utf8));
+                match prefix {
+                    ">" => {
+                        let digest = password_digest(value);
+                        if !self.passwords.contains(&digest) {
+                            self.passwords.push(digest);
+                        }
+                        self.nopass = false;
+                    }
+                    "<" => {
+                        let digest = password_digest(value);
+                        self.passwords.retain(|password| *password != digest);
+                    }
+                    "#" => {
+                        let digest = value.to_lowercase();
+                        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                            return Err(format!("invalid rule '{}', expected a SHA-256 digest in hexadecimal", rule));
+                        }
+                        if !self.passwords.contains(&digest) {
+                            self.passwords.push(digest);
+                        }
+                        self.nopass = false;
+                    }
+                    "!" => {
+                        let digest = value.to_lowercase();
+                        self.passwords.retain(|password| *password != digest);
+                    }
+                    "~" if !value.is_empty() => self.key_patterns.push(value.to_owned()),
+                    "&" if !value.is_empty() => self.channel_patterns.push(value.to_owned()),
+                    "+" | "-" if !value.is_empty() => {
+                        let selector = match value.strip_prefix('@') {
+                            Some(category) => {
+                                let category = category.to_lowercase();
+                                CommandFlags::from_name(&category).ok_or_else(invalid)?;
+                                CommandSelector::Category(category)
+                            }
+                            None => CommandSelector::Name(value.to_lowercase()),
+                        };
+                        self.commands.push((prefix == "+", selector));
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a password against the digests of the user, comparing all of them in constant time.
+    pub fn check_password(&self, password: &str) -> bool {
+        if self.nopass {
+            return true;
+        }
+        let digest = password_digest(password);
+        self.passwords
+            .iter()
+            .fold(false, |matched, password| matched | constant_time_eq(password.as_bytes(), digest.as_bytes()))
+    }
+
+    pub fn check(&self, command: &Command, args: &[String]) -> Result<(), Denial> {
+        let allowed = self
+            .commands
+            .iter()
+            .rev()
+            .find(|(_, selector)| selector.matches(command))
+            .is_some_and(|(allowed, _)| *allowed);
+        if !allowed {
+            return Err(Denial::Command);
+        }
+        let matches_any = |patterns: &[String], value: &str| {
+            patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), value.as_bytes()))
+        };
+        if let Some(key) = command.keys(args).into_iter().find(|key| !matches_any(&self.key_patterns, key)) {
+            return Err(Denial::Key(key.to_owned()));
+        }
+        if let Some(channel) = command
+            .channels(args)
+            .into_iter()
+            .find(|channel| !matches_any(&self.channel_patterns, channel))
+        {
+            return Err(Denial::Channel(channel.to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn rules(&self) -> Vec<String> {
+        let mut rules = vec![if self.enabled { "on" } else { "off" }.to_owned()];
+        if self.nopass {
+            rules.push("nopass".to_owned());
+        }
+        rules.extend(self.passwords.iter().map(|password| format!("#{}", password)));
+        rules.extend(self.key_patterns.iter().map(|pattern| format!("~{}", pattern)));
+        if self.channel_patterns.is_empty() {
+            rules.push("resetchannels".to_owned());
+        }
+        rules.extend(self.channel_patterns.iter().map(|pattern| format!("&{}", pattern)));
+        rules.extend(self.command_rules());
+        rules
+    }
+
+    pub fn command_rules(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        if self.commands.first() != Some(&(true, CommandSelector::All)) {
+            rules.push("-@all".to_owned());
+        }
+        rules.extend(self.commands.iter().map(|(allowed, selector)| selector.describe(*allowed)));
+        rules
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        let prefixed = |prefix: char, patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| format!("{}{}", prefix, pattern))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        vec![
+            format!("flags {}", flags.join(" ")),
+            format!("passwords {}", self.passwords.join(" ")),
+            format!("commands {}", self.command_rules().join(" ")),
+            format!("keys {}", prefixed('~', &self.key_patterns)),
+            format!("channels {}", prefixed('&', &self.channel_patterns)),
+        ]
+    }
+}
+
+pub fn parse_acl_file(contents: &str) -> Result<Vec<User>, String> {
+    let mut users: Vec<User> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |err: String| format!("line {}: {}", index + 1, err);
+        let Some(args) = parse_command(line.as_bytes()) else {
+            return Err(invalid("unbalanced quotes".to_owned()));
+        };
+        let [keyword, name, rules @ ..] = args.as_slice() else {
+            return Err(invalid("expected user <name> <rule> ...".to_owned()));
+        };
+        if !keyword.eq_ignore_ascii_case("user") {
+            return Err(invalid("expected user <name> <rule> ...".to_owned()));
+        }
+        if users.iter().any(|user| user.name == *name) {
+            return Err(invalid(format!("duplicate user '{}'", name)));
+        }
+        let mut user = User::new(name);
+        user.apply_rules(rules).map_err(invalid)?;
+        users.push(user);
+    }
+    Ok(users)
+}
+
+pub fn format_acl_file<'a>(users: impl IntoIterator<Item = &'a User>) -> String {
+    users
+        .into_iter()
+        .map(|user| format!("user {} {}\n", user.name, user.rules().join(" ")))
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct AclLogEntry {
+    pub count: u64,
+    pub reason: &'static str,
+    pub object: String,
+    pub username: String,
+    pub client: String,
+    pub created: Instant,
+    pub updated: Instant,
+}
+
+impl AclLogEntry {
+    pub fn describe(&self) -> String {
+        format!(
+            "count {} reason {} object {} username {} age-seconds {:.3} client {}",
+            self.count,
+            self.reason,
+            self.object,
+            self.username,
+            self.created.elapsed().as_secs_f64(),
+            self.client
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AclLog {
+    entries: VecDeque<AclLogEntry>,
+}
+
+impl AclLog {
+    pub fn add(&mut self, reason: &'static str, object: String, username: String, client: String) {
+        let now = Instant::now();
+        let same = self.entries.iter().position(|entry| {
+            entry.reason == reason
+                && entry.object == object
+                && entry.username == username
+                && entry.client == client
+                && now.duration_since(entry.updated) < ACL_LOG_GROUPING_DELAY
+        });
+        let entry = match same.and_then(|index| self.entries.remove(index)) {
+            Some(mut entry) => {
+                entry.count += 1;
+                entry.updated = now;
+                entry
+            }
+            None => AclLogEntry {
+                count: 1,
+                reason,
+                object,
+                username,
+                client,
+                created: now,
+                updated: now,
+            },
+        };
+        self.entries.push_front(entry);
+        self.entries.truncate(ACL_LOG_MAX_LEN);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &AclLogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub fn password_digest(password: &str) -> String {
+    Sha256::digest(password.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Compares every byte whatever the first difference, the time taken doesn't tell how close an attempt is.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b.iter()).fold(0u8, |difference, (a, b)| difference | (a ^ b));
+    std::hint::black_box(difference) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::acl::{
+        constant_time_eq, format_acl_file, parse_acl_file, password_digest, AclLog, Denial, User,
+    };
+    use crate::core::command::registry::{Command, CommandFlags, CommandFuture};
+    use crate::core::command::context::{args, CommandContext};
+
+    fn noop(_context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+        Box::pin(async move {})
+    }
+
+    fn new_user(rules: &str) -> User {
+        let mut user = User::new("alice");
+        user.apply_rules(&args(rules)).unwrap();
+        user
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"s3cret", b"s3cret"));
+        assert!(!constant_time_eq(b"s3cret", b"s3creT"));
+        assert!(!constant_time_eq(b"s3cret", b"s3cret "));
+    }
+
+    #[test]
+    fn test_passwords() {
+        let mut user = new_user("on >s3cret >other");
+        assert!(user.check_password("s3cret"));
+        assert!(user.check_password("other"));
+        assert!(!user.check_password("S3CRET"));
+
+        user.apply_rules(&args("<other")).unwrap();
+        assert!(!user.check_password("other"));
+        assert_eq!(password_digest("abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        user.apply_rules(&[format!("#{}", password_digest("hashed"))]).unwrap();
+        assert!(user.check_password("hashed"));
+        // a SHA-1 digest
+        assert!(user.apply_rules(&args("#a9993e364706816aba3e25717850c26c9cd0d89d")).is_err());
+        user.apply_rules(&args("nopass")).unwrap();
+        assert!(user.check_password("anything"));
+        user.apply_rules(&args("resetpass")).unwrap();
+        assert!(!user.check_password("anything"));
+    }
+
+    #[test]
+    fn test_check() {
+        let get = Command::new("get", 2, CommandFlags::READONLY, noop).with_keys(1, 1, 1);
+        let set = Command::new("set", -3, CommandFlags::WRITE, noop).with_keys(1, 1, 1);
+        let config_get = Command::new("config|get", -3, CommandFlags::ADMIN, noop);
+        let config_set = Command::new("config|set", -4, CommandFlags::ADMIN, noop);
+        let subscribe = Command::new("subscribe", -2, CommandFlags::PUBSUB, noop).with_channels(1, 1, 1);
+        let user = new_user("on ~cache* &events.* +@readonly +config -config|set +subscribe");
+
+        assert_eq!(user.check(&get, &args("get cache1")), Ok(()));
+        assert_eq!(user.check(&get, &args("get other")), Err(Denial::Key("other".to_owned())));
+        assert_eq!(user.check(&set, &args("set cache1 1")), Err(Denial::Command));
+        assert_eq!(user.check(&config_get, &args("config get port")), Ok(()));
+        assert_eq!(user.check(&config_set, &args("config set port 1")), Err(Denial::Command));
+        assert_eq!(user.check(&subscribe, &args("subscribe events.a")), Ok(()));
+        assert_eq!(
+            user.check(&subscribe, &args("subscribe news")),
+            Err(Denial::Channel("news".to_owned()))
+        );
+
+        let user = new_user("on allkeys allchannels +@all -set");
+        assert_eq!(user.check(&get, &args("get other")), Ok(()));
+        assert_eq!(user.check(&set, &args("set other 1")), Err(Denial::Command));
+        assert_eq!(User::new("bob").check(&get, &args("get a")), Err(Denial::Command));
+    }
+
+    #[test]
+    fn test_apply_rules_errors() {
+        let mut user = new_user("on +get");
+
+        assert_eq!(user.apply_rules(&args("+set xyz")), Err("invalid rule 'xyz'".to_owned()));
+        assert_eq!(user.apply_rules(&args("+@unknown")), Err("invalid rule '+@unknown'".to_owned()));
+        assert!(user.apply_rules(&args("#abc")).is_err());
+        // nothing is applied
+        assert_eq!(user, new_user("on +get"));
+    }
+
+    #[test]
+    fn test_rules() {
+        let user = new_user("on >s3cret ~cache* &events.* +get +config -config|set");
+        assert_eq!(
+            user.rules().join(" "),
+            format!("on #{} ~cache* &events.* -@all +get +config -config|set", password_digest("s3cret"))
+        );
+        assert_eq!(
+            User::new_default("default").rules().join(" "),
+            "on nopass ~* &* +@all"
+        );
+        assert_eq!(User::new("bob").rules().join(" "), "off resetchannels -@all");
+    }
+
+    #[test]
+    fn test_acl_file() {
+        let users = vec![
+            User::new_default("default"),
+            new_user("on >s3cret ~cache* -@all +@readonly"),
+        ];
+
+        let contents = format_acl_file(&users);
+        assert_eq!(parse_acl_file(&contents), Ok(users));
+
+        assert_eq!(
+            parse_acl_file("# users\nuser alice on\nuser alice off"),
+            Err("line 3: duplicate user 'alice'".to_owned())
+        );
+        assert_eq!(
+            parse_acl_file("user alice xyz"),
+            Err("line 1: invalid rule 'xyz'".to_owned())
+        );
+        assert_eq!(
+            parse_acl_file("alice on"),
+            Err("line 1: expected user <name> <rule> ...".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_acl_log() {
+        let mut log = AclLog::default();
+        log.add("command", "get".to_owned(), "alice".to_owned(), "127.0.0.1:1".to_owned());
+        log.add("key", "a".to_owned(), "alice".to_owned(), "127.0.0.1:1".to_owned());
+        log.add("command", "get".to_owned(), "alice".to_owned(), "127.0.0.1:1".to_owned());
+
+        let entries: Vec<(u64, &str)> = log.entries().map(|entry| (entry.count, entry.reason)).collect();
+        assert_eq!(entries, vec![(2, "command"), (1, "key")]);
+        assert!(log
+            .entries()
+            .next()
+            .unwrap()
+            .describe()
+            .starts_with("count 2 reason command object get username alice age-seconds "));
+
+        log.clear();
+        assert_eq!(log.entries().count(), 0);
+    }
+}
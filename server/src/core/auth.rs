@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::automock;
+use tokio::io;
+
+use crate::core::acl::{format_acl_file, parse_acl_file, AclLog, AclLogEntry, Denial, User};
+use crate::core::command::registry::Command;
+use crate::core::config::service::ConfigListener;
+use crate::core::config::Config;
+use crate::core::file::replace_file;
+use crate::core::session::DEFAULT_USER;
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait AuthService: Send + Sync {
+    fn requires_auth(&self) -> bool;
+
+    fn authenticate(&self, client: SocketAddr, user: &str, password: &str) -> bool;
+
+    fn failed_attempts(&self) -> u64;
+
+    fn check(&self, client: SocketAddr, user: &str, command: &Command, args: &[String]) -> Result<(), String>;
+
+    fn set_user(&self, name: &str, rules: &[String]) -> Result<(), String>;
+
+    fn user(&self, name: &str) -> Option<User>;
+
+    fn users(&self) -> Vec<User>;
+
+    fn delete_users(&self, names: &[String]) -> Result<usize, String>;
+
+    fn log(&self) -> Vec<AclLogEntry>;
+
+    fn reset_log(&self);
+
+    async fn save(&self) -> io::Result<()>;
+}
+
+pub struct MyAuthService {
+    users: RwLock<BTreeMap<String, User>>,
+    acl_file: Option<PathBuf>,
+    /// the last `requirepass` applied to the default user, it is applied again only when it changes
+    requirepass: Mutex<String>,
+    log: Mutex<AclLog>,
+    failed_attempts: AtomicU64,
+}
+
+impl MyAuthService {
+    pub fn new() -> Self {
+        let default_user = User::new_default(DEFAULT_USER);
+        Self {
+            users: RwLock::new(BTreeMap::from([(DEFAULT_USER.to_owned(), default_user)])),
+            acl_file: None,
+            requirepass: Mutex::new(String::new()),
+            log: Mutex::new(AclLog::default()),
+            failed_attempts: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_requirepass(self, password: &str) -> Self {
+        self.set_requirepass(password);
+        self
+    }
+
+    pub fn with_acl_file(mut self, path: PathBuf) -> io::Result<Self> {
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let users = parse_acl_file(&contents).map_err(|err| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", path.display(), err))
+                })?;
+                let mut current_users = self.users.write().unwrap();
+                for user in users {
+                    current_users.insert(user.name().to_owned(), user);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("can't read '{}': {}", path.display(), err),
+                ))
+            }
+        }
+        self.acl_file = Some(path);
+        Ok(self)
+    }
+
+    fn set_requirepass(&self, password: &str) {
+        let mut requirepass = self.requirepass.lock().unwrap();
+        if *requirepass == password {
+            return;
+        }
+        *requirepass = password.to_owned();
+        let rules = if password.is_empty() {
+            vec!["nopass".to_owned()]
+        } else {
+            vec!["resetpass".to_owned(), format!(">{}", password)]
+        };
+        let mut users = self.users.write().unwrap();
+        if let Some(default_user) = users.get_mut(DEFAULT_USER) {
+            // valid rules
+            let _ = default_user.apply_rules(&rules);
+        }
+    }
+}
+
+impl Default for MyAuthService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigListener for MyAuthService {
+    fn apply_config(&self, config: &Config) {
+        self.set_requirepass(&config.requirepass);
+    }
+
+    fn reset_stats(&self) {
+        self.failed_attempts.store(0, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl AuthService for MyAuthService {
+    fn requires_auth(&self) -> bool {
+        let users = self.users.read().unwrap();
+        !users.get(DEFAULT_USER).is_some_and(User::is_open)
+    }
+
+    fn authenticate(&self, client: SocketAddr, user: &str, password: &str) -> bool {
+        let authenticated = self
+            .users
+            .read()
+            .unwrap()
+            .get(user)
+            .is_some_and(|found| found.check_password(password) && found.is_enabled());
+        if !authenticated {
+            self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+            let mut log = self.log.lock().unwrap();
+            log.add("auth", "AUTH".to_owned(), user.to_owned(), client.to_string());
+        }
+        authenticated
+    }
+
+    fn failed_attempts(&self) -> u64 {
+        self.failed_attempts.load(Ordering::Relaxed)
+    }
+
+    fn check(&self, client: SocketAddr, user: &str, command: &Command, args: &[String]) -> Result<(), String> {
+        let denial = match self.users.read().unwrap().get(user) {
+            Some(found) => found.check(command, args),
+            // deleted while the client is connected
+            None => Err(Denial::Command),
+        };
+        let Err(denial) = denial else { return Ok(()); };
+        let (reason, object, reply) = match denial {
+            Denial::Command => (
+                "command",
+                command.name().to_owned(),
+                format!(
+                    "err NOPERM user '{}' has no permissions to run the '{}' command\n",
+                    user,
+                    command.name()
+                ),
+            ),
+            Denial::Key(key) => ("key", key, "err NOPERM no permissions to access a key\n".to_owned()),
+            Denial::Channel(channel) => (
+                "channel",
+                channel,
+                "err NOPERM no permissions to access a channel\n".to_owned(),
+            ),
+        };
+        log::debug!("[{}] '{}' denied to '{}' on {} '{}'", client, command.name(), user, reason, object);
+        let mut log = self.log.lock().unwrap();
+        log.add(reason, object, user.to_owned(), client.to_string());
+        Err(reply)
+    }
+
+    fn set_user(&self, name: &str, rules: &[String]) -> Result<(), String> {
+        let mut users = self.users.write().unwrap();
+        let mut user = users.get(name).cloned().unwrap_or_else(|| User::new(name));
+        user.apply_rules(rules)?;
+        users.insert(name.to_owned(), user);
+        Ok(())
+    }
+
+    fn user(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    fn users(&self) -> Vec<User> {
+        self.users.read().unwrap().values().cloned().collect()
+    }
+
+    fn delete_users(&self, names: &[String]) -> Result<usize, String> {
+        if names.iter().any(|name| name == DEFAULT_USER) {
+            return Err(format!("the '{}' user can't be deleted", DEFAULT_USER));
+        }
+        let mut users = self.users.write().unwrap();
+        Ok(names.iter().filter(|name| users.remove(name.as_str()).is_some()).count())
+    }
+
+    fn log(&self) -> Vec<AclLogEntry> {
+        self.log.lock().unwrap().entries().cloned().collect()
+    }
+
+    fn reset_log(&self) {
+        self.log.lock().unwrap().clear();
+    }
+
+    async fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.acl_file else { return Ok(()); };
+        let contents = format_acl_file(self.users.read().unwrap().values());
+        replace_file(path, contents.as_bytes(), false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use tempdir::TempDir;
+
+    use crate::core::auth::{AuthService, MyAuthService};
+    use crate::core::command::context::args;
+    use crate::core::command::registry::CommandRegistry;
+    use crate::core::config::service::ConfigListener;
+    use crate::core::config::Config;
+
+    fn client() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111)
+    }
+
+    #[test]
+    fn authenticate_should_check_password() {
+        let service = MyAuthService::new().with_requirepass("s3cret");
+        service.set_user("alice", &args("on >wonderland")).unwrap();
+        service.set_user("bob", &args("off >builder")).unwrap();
+
+        assert!(service.requires_auth());
+        assert!(service.authenticate(client(), "default", "s3cret"));
+        assert!(service.authenticate(client(), "alice", "wonderland"));
+        assert!(!service.authenticate(client(), "default", "S3CRET"));
+        assert!(!service.authenticate(client(), "alice", "s3cret"));
+        assert!(!service.authenticate(client(), "bob", "builder"));
+        assert!(!service.authenticate(client(), "carol", "s3cret"));
+        assert_eq!(service.failed_attempts(), 4);
+        assert_eq!(service.log()[0].reason, "auth");
+        assert_eq!(service.log()[0].username, "carol");
+
+        service.reset_stats();
+        assert_eq!(service.failed_attempts(), 0);
+    }
+
+    #[test]
+    fn requirepass_should_follow_config() {
+        let service = MyAuthService::new();
+        assert!(!service.requires_auth());
+        assert!(service.authenticate(client(), "default", "anything"));
+
+        let config = Config {
+            requirepass: "s3cret".to_owned(),
+            ..Config::default()
+        };
+        service.apply_config(&config);
+        assert!(service.requires_auth());
+        assert!(!service.authenticate(client(), "default", "anything"));
+
+        // the password set by ACL SETUSER is kept while requirepass doesn't change
+        service.set_user("default", &args("resetpass >other")).unwrap();
+        service.apply_config(&config);
+        assert!(service.authenticate(client(), "default", "other"));
+
+        service.apply_config(&Config::default());
+        assert!(!service.requires_auth());
+    }
+
+    #[test]
+    fn check_should_log_denials() {
+        let registry = CommandRegistry::new().with_builtin_commands();
+        let service = MyAuthService::new();
+        service.set_user("alice", &args("on nopass ~cache* +get")).unwrap();
+        let command = |args: &[String]| registry.command(args).unwrap();
+
+        let get = args("get cache1");
+        assert_eq!(service.check(client(), "alice", &command(&get), &get), Ok(()));
+        assert_eq!(service.check(client(), "default", &command(&get), &get), Ok(()));
+        let set = args("set cache1 1");
+        assert_eq!(
+            service.check(client(), "alice", &command(&set), &set),
+            Err("err NOPERM user 'alice' has no permissions to run the 'set' command\n".to_owned())
+        );
+        let get = args("get other");
+        assert_eq!(
+            service.check(client(), "alice", &command(&get), &get),
+            Err("err NOPERM no permissions to access a key\n".to_owned())
+        );
+        assert!(service.check(client(), "carol", &command(&get), &get).is_err());
+
+        let log: Vec<(&str, String)> = service
+            .log()
+            .into_iter()
+            .map(|entry| (entry.reason, entry.object))
+            .collect();
+        assert_eq!(
+            log,
+            vec![
+                ("command", "get".to_owned()),
+                ("key", "other".to_owned()),
+                ("command", "set".to_owned())
+            ]
+        );
+        service.reset_log();
+        assert!(service.log().is_empty());
+    }
+
+    #[test]
+    fn delete_users_should_keep_default_user() {
+        let service = MyAuthService::new();
+        service.set_user("alice", &args("on")).unwrap();
+
+        assert!(service.delete_users(&args("alice default")).is_err());
+        assert_eq!(service.delete_users(&args("alice bob")), Ok(1));
+        let names: Vec<String> = service.users().iter().map(|user| user.name().to_owned()).collect();
+        assert_eq!(names, vec!["default"]);
+    }
+
+    #[tokio::test]
+    async fn acl_file_should_be_saved_and_loaded() {
+        let temp_dir = TempDir::new("auth-tests").unwrap();
+        let path = temp_dir.path().join("users.acl");
+        let service = MyAuthService::new().with_acl_file(path.clone()).unwrap();
+        service.set_user("alice", &args("on >wonderland ~cache* &events.* +get")).unwrap();
+        service.set_user("default", &args("off")).unwrap();
+        service.save().await.unwrap();
+
+        let loaded = MyAuthService::new().with_acl_file(path.clone()).unwrap();
+        assert_eq!(loaded.users(), service.users());
+        assert!(loaded.requires_auth());
+        assert!(loaded.authenticate(client(), "alice", "wonderland"));
+
+        std::fs::write(&path, "user alice xyz\n").unwrap();
+        let err = MyAuthService::new().with_acl_file(path).err().unwrap();
+        assert!(err.to_string().ends_with("users.acl: line 1: invalid rule 'xyz'"));
+    }
+}
@@ -1,22 +1,38 @@
 use std::collections::HashMap;
+use std::io;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use async_trait::async_trait;
 #[cfg(test)]
 use mockall::automock;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
+
+use crate::core::buffer::{OutputBufferLimits, OutputBufferSender};
+use crate::core::cache::history::HistoryStoreService;
+use crate::core::config::service::ConfigListener;
+use crate::core::config::Config;
+use crate::core::glob::glob_match;
+use crate::core::history::{HistoryWrite, RetentionPolicy, TopicHistory};
 
 #[derive(Debug)]
 struct Subscriber {
     addr: SocketAddr,
-    sender: UnboundedSender<Vec<u8>>,
+    sender: OutputBufferSender,
+    soft_limit_reached_at: Option<Instant>,
+    with_offsets: bool,
 }
 
 impl Subscriber {
-    pub fn new(addr: SocketAddr, sender: UnboundedSender<Vec<u8>>) -> Self {
-        Self { addr, sender }
+    pub fn new(addr: SocketAddr, sender: OutputBufferSender, with_offsets: bool) -> Self {
+        Self {
+            addr,
+            sender,
+            soft_limit_reached_at: None,
+            with_offsets,
+        }
     }
 }
 
@@ -26,23 +42,49 @@ impl PartialEq for Subscriber {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BrokerMetrics {
+    pub dropped_messages: u64,
+    pub disconnected_subscribers: u64,
+}
+
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait BrokerService: Send + Sync {
     async fn is_subscription_connection(&self, socket_addr: SocketAddr) -> bool;
-    async fn subscribe(
+    async fn subscribe(&self, socket_addr: SocketAddr, sender: OutputBufferSender, topic: String);
+    /// Subscribes to a topic after replaying its retained messages starting from the given offset. Every message
+    /// of a durable topic is then preceded by its offset, `<offset> <message>`, to resume from the next one.
+    async fn subscribe_from(
         &self,
         socket_addr: SocketAddr,
-        sender: UnboundedSender<Vec<u8>>,
+        sender: OutputBufferSender,
         topic: String,
+        offset: u64,
     );
     async fn unsubscribe(&self, socket_addr: SocketAddr);
     async fn publish(&self, publisher_addr: SocketAddr, message: Vec<u8>);
+    async fn publish_to(&self, topic: String, message: Vec<u8>);
+    async fn metrics(&self) -> BrokerMetrics;
+    async fn channels(&self, pattern: Option<String>) -> Vec<String>;
+    async fn num_subscribers(&self, channels: Vec<String>) -> Vec<(String, usize)>;
+    async fn num_patterns(&self) -> usize;
+    async fn make_durable(&self, topic: String, policy: RetentionPolicy) -> io::Result<()>;
+    async fn read_history(&self) -> io::Result<()>;
 }
 
+/// The history of a durable topic, its lock is held until the history is written so that the writes of a topic
+/// happen in the order of its messages.
+type SharedHistory = Arc<Mutex<TopicHistory>>;
+
 pub struct MyBrokerService {
     clients: Arc<RwLock<HashMap<SocketAddr, String>>>,
     subscribers: Arc<RwLock<HashMap<String, Vec<Subscriber>>>>,
+    histories: Arc<RwLock<HashMap<String, SharedHistory>>>,
+    history_store: Option<Arc<dyn HistoryStoreService>>,
+    output_buffer_limits: std::sync::RwLock<OutputBufferLimits>,
+    dropped_messages: AtomicU64,
+    disconnected_subscribers: AtomicU64,
 }
 
 impl MyBrokerService {
@@ -50,7 +92,164 @@ impl MyBrokerService {
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
             subscribers: Arc::new(RwLock::new(HashMap::new())),
+            histories: Arc::new(RwLock::new(HashMap::new())),
+            history_store: None,
+            output_buffer_limits: std::sync::RwLock::new(OutputBufferLimits::default()),
+            dropped_messages: AtomicU64::new(0),
+            disconnected_subscribers: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_output_buffer_limits(mut self, output_buffer_limits: OutputBufferLimits) -> Self {
+        self.output_buffer_limits = std::sync::RwLock::new(output_buffer_limits);
+        self
+    }
+
+    pub fn with_history_store(mut self, history_store: Arc<dyn HistoryStoreService>) -> Self {
+        self.history_store = Some(history_store);
+        self
+    }
+
+    async fn add_subscriber(
+        &self,
+        socket_addr: SocketAddr,
+        sender: OutputBufferSender,
+        topic: String,
+        offset: Option<u64>,
+    ) {
+        let history = match offset {
+            Some(_) => self.lock_history(&topic).await,
+            None => None,
+        };
+        let mut subscribers = self.subscribers.write().await;
+        if let (Some(offset), Some(mut history)) = (offset, history) {
+            // replayed while holding the subscribers lock so that no message is missed or duplicated
+            for (offset, message) in history.since(offset) {
+                let _ = sender.send(with_offset(offset, &message));
+            }
+        }
+        let subscriber = Subscriber::new(socket_addr, sender, offset.is_some());
+        subscribers.entry(topic.clone()).or_default().push(subscriber);
+        drop(subscribers);
+
+        self.clients.write().await.insert(socket_addr, topic);
+    }
+
+    /// Locks the history of a topic, None if the topic isn't durable.
+    /// It is locked before the subscribers so that a publication waiting for a previous write of the topic
+    /// doesn't hold them.
+    async fn lock_history(&self, topic: &str) -> Option<OwnedMutexGuard<TopicHistory>> {
+        let history = self.histories.read().await.get(topic).cloned()?;
+        Some(history.lock_owned().await)
+    }
+
+    async fn write_history(&self, topic: String, history: &mut TopicHistory, write: HistoryWrite) -> io::Result<()> {
+        let Some(history_store) = &self.history_store else { return Ok(()); };
+        match write {
+            HistoryWrite::Append(record) => {
+                if history_store.append(topic.clone(), record).await.is_ok() {
+                    return Ok(());
+                }
+                // e.g. a previous write failed, the whole history is written again
+                history_store.write(topic, history.compact()).await
+            }
+            HistoryWrite::Replace(bytes) => history_store.write(topic, bytes).await,
+        }
+    }
+
+    async fn deliver(&self, topic: String, publisher_addr: Option<SocketAddr>, message: Vec<u8>) {
+        let mut slow_subscribers = Vec::new();
+        let mut dropped_messages = 0u64;
+        let mut history = self.lock_history(&topic).await;
+        let mut subscribers = self.subscribers.write().await;
+        let (offset, history_write) = match history.as_mut() {
+            Some(history) => {
+                let (offset, history_write) = history.append(message.clone());
+                (Some(offset), Some(history_write))
+            }
+            None => (None, None),
+        };
+        let message_with_offset = offset.map(|offset| with_offset(offset, &message));
+        if let Some(topic_subscribers) = subscribers.get_mut(&topic) {
+            for sub in topic_subscribers.iter_mut() {
+                if Some(sub.addr) == publisher_addr {
+                    // skip publishing to the sender
+                    continue;
+                }
+                let message = match &message_with_offset {
+                    Some(message_with_offset) if sub.with_offsets => message_with_offset,
+                    _ => &message,
+                };
+                if !self.is_within_output_buffer_limits(sub, message.len()) {
+                    sub.sender.close();
+                    // the queued messages are discarded once the buffer is closed
+                    dropped_messages += 1 + sub.sender.queued_messages() as u64;
+                    slow_subscribers.push(sub.addr);
+                    continue;
+                }
+                let _ = sub.sender.send(message.clone());
+            }
+            topic_subscribers.retain(|s| !slow_subscribers.contains(&s.addr));
+            if topic_subscribers.is_empty() {
+                subscribers.remove(&topic);
+            }
         }
+        drop(subscribers);
+
+        if let (Some(history), Some(history_write)) = (history.as_mut(), history_write) {
+            if let Err(err) = self.write_history(topic, history, history_write).await {
+                log::error!("error during writing topic history: {}", err);
+            }
+        }
+
+        if slow_subscribers.is_empty() {
+            return;
+        }
+        let mut clients = self.clients.write().await;
+        for addr in slow_subscribers.iter() {
+            log::warn!("[{}] output buffer limits exceeded, disconnecting", addr);
+            clients.remove(addr);
+        }
+        self.dropped_messages.fetch_add(dropped_messages, Ordering::Relaxed);
+        self.disconnected_subscribers
+            .fetch_add(slow_subscribers.len() as u64, Ordering::Relaxed);
+    }
+
+    fn is_within_output_buffer_limits(&self, subscriber: &mut Subscriber, size: usize) -> bool {
+        let limits = *self.output_buffer_limits.read().unwrap();
+        let queued_bytes = subscriber.sender.queued_bytes() + size;
+        if limits.hard_limit > 0 && queued_bytes > limits.hard_limit {
+            return false;
+        }
+        if limits.soft_limit > 0 && queued_bytes > limits.soft_limit {
+            let reached_at = *subscriber
+                .soft_limit_reached_at
+                .get_or_insert_with(Instant::now);
+            return reached_at.elapsed() < limits.soft_limit_duration;
+        }
+        subscriber.soft_limit_reached_at = None;
+        true
+    }
+}
+
+fn with_offset(offset: u64, message: &[u8]) -> Vec<u8> {
+    [format!("{} ", offset).as_bytes(), message].concat()
+}
+
+impl ConfigListener for MyBrokerService {
+    fn apply_config(&self, config: &Config) {
+        *self.output_buffer_limits.write().unwrap() = config.client_output_buffer_limit;
+    }
+
+    fn reset_stats(&self) {
+        self.dropped_messages.store(0, Ordering::Relaxed);
+        self.disconnected_subscribers.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for MyBrokerService {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -60,52 +259,125 @@ impl BrokerService for MyBrokerService {
         self.clients.read().await.contains_key(&socket_addr)
     }
 
-    async fn subscribe(
+    async fn subscribe(&self, socket_addr: SocketAddr, sender: OutputBufferSender, topic: String) {
+        self.add_subscriber(socket_addr, sender, topic, None).await;
+    }
+
+    async fn subscribe_from(
         &self,
         socket_addr: SocketAddr,
-        sender: UnboundedSender<Vec<u8>>,
+        sender: OutputBufferSender,
         topic: String,
+        offset: u64,
     ) {
-        let subscriber = Subscriber::new(socket_addr, sender);
-        self.subscribers
-            .write()
-            .await
-            .entry(topic.clone())
-            .or_default()
-            .push(subscriber);
-
-        self.clients.write().await.insert(socket_addr, topic);
+        self.add_subscriber(socket_addr, sender, topic, Some(offset))
+            .await;
     }
 
     async fn unsubscribe(&self, socket_addr: SocketAddr) {
         if let Some(topic) = self.clients.write().await.remove(&socket_addr) {
-            if let Some(subscribers) = self.subscribers.write().await.get_mut(&topic) {
+            let mut subscribers = self.subscribers.write().await;
+            if let Some(topic_subscribers) = subscribers.get_mut(&topic) {
                 // remove a subscriber from a topic
-                subscribers.retain(|s| s.addr != socket_addr);
+                topic_subscribers.retain(|s| s.addr != socket_addr);
+                if topic_subscribers.is_empty() {
+                    subscribers.remove(&topic);
+                }
             }
         }
     }
 
     async fn publish(&self, publisher_addr: SocketAddr, message: Vec<u8>) {
-        if let Some(topic) = self.clients.read().await.get(&publisher_addr) {
-            if let Some(subscribers) = self.subscribers.write().await.get(topic) {
-                for sub in subscribers.iter() {
-                    if sub.addr == publisher_addr {
-                        // skip publishing to the sender
-                        continue;
-                    }
-                    let _ = sub.sender.send(message.clone());
-                }
-            }
+        let Some(topic) = self.clients.read().await.get(&publisher_addr).cloned() else { return; };
+        self.deliver(topic, Some(publisher_addr), message).await;
+    }
+
+    async fn publish_to(&self, topic: String, message: Vec<u8>) {
+        self.deliver(topic, None, message).await;
+    }
+
+    async fn metrics(&self) -> BrokerMetrics {
+        BrokerMetrics {
+            dropped_messages: self.dropped_messages.load(Ordering::Relaxed),
+            disconnected_subscribers: self.disconnected_subscribers.load(Ordering::Relaxed),
         }
     }
+
+    async fn channels(&self, pattern: Option<String>) -> Vec<String> {
+        let mut channels: Vec<String> = self
+            .subscribers
+            .read()
+            .await
+            .iter()
+            .filter(|(_, subscribers)| !subscribers.is_empty())
+            .map(|(topic, _)| topic)
+            .filter(|topic| {
+                pattern
+                    .as_ref()
+                    .is_none_or(|pattern| glob_match(pattern.as_bytes(), topic.as_bytes()))
+            })
+            .cloned()
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    async fn num_subscribers(&self, channels: Vec<String>) -> Vec<(String, usize)> {
+        let subscribers = self.subscribers.read().await;
+        channels
+            .into_iter()
+            .map(|channel| {
+                let count = subscribers.get(&channel).map_or(0, Vec::len);
+                (channel, count)
+            })
+            .collect()
+    }
+
+    async fn num_patterns(&self) -> usize {
+        0
+    }
+
+    async fn make_durable(&self, topic: String, policy: RetentionPolicy) -> io::Result<()> {
+        let history = self
+            .histories
+            .write()
+            .await
+            .entry(topic.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(TopicHistory::new(policy))))
+            .clone();
+        let mut history = history.lock().await;
+        history.set_policy(policy);
+        let bytes = history.compact();
+        self.write_history(topic, &mut history, HistoryWrite::Replace(bytes)).await
+    }
+
+    async fn read_history(&self) -> io::Result<()> {
+        let Some(history_store) = &self.history_store else { return Ok(()); };
+        let stored_histories = history_store.read().await?;
+        let mut histories = self.histories.write().await;
+        for (topic, bytes) in stored_histories.into_iter() {
+            let Some(mut history) = TopicHistory::from_bytes(&bytes) else {
+                log::warn!("invalid topic history: {}", topic);
+                continue;
+            };
+            // the records appended since the last compaction are dropped from the file
+            history_store.write(topic.clone(), history.compact()).await?;
+            histories.insert(topic, Arc::new(Mutex::new(history)));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::net::{IpAddr, Ipv4Addr};
 
-    use tokio::sync::mpsc::unbounded_channel;
+    use std::time::Duration;
+
+    use mockall::predicate::eq;
+
+    use crate::core::buffer::output_buffer;
+    use crate::core::cache::history::MockHistoryStoreService;
 
     use super::*;
 
@@ -121,7 +393,7 @@ mod tests {
         let service = MyBrokerService::new();
 
         let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
-        let (tx, _) = unbounded_channel::<Vec<u8>>();
+        let (tx, _rx) = output_buffer();
         let topic = "t1";
         service
             .subscribe(socket_addr, tx.clone(), topic.to_owned())
@@ -149,7 +421,7 @@ mod tests {
         let service = MyBrokerService::new();
 
         let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
-        let (tx, _) = unbounded_channel::<Vec<u8>>();
+        let (tx, _rx) = output_buffer();
         let topic = "t1";
         service
             .subscribe(socket_addr, tx.clone(), topic.to_owned())
@@ -157,12 +429,8 @@ mod tests {
 
         service.unsubscribe(socket_addr).await;
 
-        {
-            let guard = service.subscribers.read().await;
-            let subscribers = guard.get(topic);
-            assert!(subscribers.is_some());
-            assert!(subscribers.unwrap().is_empty());
-        }
+        // a channel without subscribers is removed
+        assert!(service.subscribers.read().await.get(topic).is_none());
         assert!(service.clients.read().await.is_empty());
     }
 
@@ -172,8 +440,8 @@ mod tests {
 
         let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
         let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
-        let (tx1, _) = unbounded_channel::<Vec<u8>>();
-        let (tx2, mut rx2) = unbounded_channel::<Vec<u8>>();
+        let (tx1, _rx1) = output_buffer();
+        let (tx2, mut rx2) = output_buffer();
 
         let topic = "t1";
         service
@@ -188,4 +456,286 @@ mod tests {
         let result = rx2.recv().await;
         assert_eq!(result, Some(vec![100u8, 110u8]));
     }
+
+    #[tokio::test]
+    async fn publish_should_disconnect_subscriber_over_hard_limit() {
+        let service = MyBrokerService::new().with_output_buffer_limits(OutputBufferLimits {
+            hard_limit: 10,
+            soft_limit: 0,
+            soft_limit_duration: Duration::from_secs(60),
+        });
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let (tx1, _rx1) = output_buffer();
+        let (tx2, mut rx2) = output_buffer();
+
+        let topic = "t1";
+        service
+            .subscribe(socket_addr1, tx1.clone(), topic.to_owned())
+            .await;
+        service
+            .subscribe(socket_addr2, tx2.clone(), topic.to_owned())
+            .await;
+
+        // client2 never reads, the second message goes over the hard limit
+        service.publish(socket_addr1, vec![1u8; 6]).await;
+        service.publish(socket_addr1, vec![2u8; 6]).await;
+
+        assert!(tx2.is_closed());
+        assert_eq!(rx2.recv().await, None);
+        assert!(!service.is_subscription_connection(socket_addr2).await);
+        assert!(service.is_subscription_connection(socket_addr1).await);
+        assert_eq!(
+            service.metrics().await,
+            BrokerMetrics {
+                // the refused message and the queued one
+                dropped_messages: 2,
+                disconnected_subscribers: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_should_disconnect_subscriber_over_soft_limit_for_too_long() {
+        let service = MyBrokerService::new().with_output_buffer_limits(OutputBufferLimits {
+            hard_limit: 0,
+            soft_limit: 4,
+            soft_limit_duration: Duration::ZERO,
+        });
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let (tx1, _rx1) = output_buffer();
+        let (tx2, _rx2) = output_buffer();
+
+        let topic = "t1";
+        service
+            .subscribe(socket_addr1, tx1.clone(), topic.to_owned())
+            .await;
+        service
+            .subscribe(socket_addr2, tx2.clone(), topic.to_owned())
+            .await;
+
+        service.publish(socket_addr1, vec![1u8; 6]).await;
+
+        assert!(tx2.is_closed());
+        assert!(!service.is_subscription_connection(socket_addr2).await);
+        assert_eq!(service.metrics().await.disconnected_subscribers, 1);
+    }
+
+    #[tokio::test]
+    async fn publish_should_keep_subscriber_under_soft_limit_duration() {
+        let service = MyBrokerService::new().with_output_buffer_limits(OutputBufferLimits {
+            hard_limit: 0,
+            soft_limit: 4,
+            soft_limit_duration: Duration::from_secs(60),
+        });
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let (tx1, _rx1) = output_buffer();
+        let (tx2, mut rx2) = output_buffer();
+
+        let topic = "t1";
+        service
+            .subscribe(socket_addr1, tx1.clone(), topic.to_owned())
+            .await;
+        service
+            .subscribe(socket_addr2, tx2.clone(), topic.to_owned())
+            .await;
+
+        service.publish(socket_addr1, vec![1u8; 6]).await;
+
+        assert!(!tx2.is_closed());
+        assert_eq!(rx2.recv().await, Some(vec![1u8; 6]));
+        assert_eq!(service.metrics().await, BrokerMetrics::default());
+    }
+
+    #[tokio::test]
+    async fn channels_should_be_returned() {
+        let service = MyBrokerService::new();
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let socket_addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1113);
+        let (tx, _rx) = output_buffer();
+        service
+            .subscribe(socket_addr1, tx.clone(), "news".to_owned())
+            .await;
+        service
+            .subscribe(socket_addr2, tx.clone(), "newsletter".to_owned())
+            .await;
+        service
+            .subscribe(socket_addr3, tx.clone(), "sports".to_owned())
+            .await;
+
+        assert_eq!(
+            service.channels(None).await,
+            vec!["news".to_owned(), "newsletter".to_owned(), "sports".to_owned()]
+        );
+        assert_eq!(
+            service.channels(Some("news*".to_owned())).await,
+            vec!["news".to_owned(), "newsletter".to_owned()]
+        );
+
+        service.unsubscribe(socket_addr3).await;
+        assert_eq!(
+            service.channels(Some("sports".to_owned())).await,
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn num_subscribers_should_be_returned() {
+        let service = MyBrokerService::new();
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let (tx, _rx) = output_buffer();
+        service
+            .subscribe(socket_addr1, tx.clone(), "t1".to_owned())
+            .await;
+        service
+            .subscribe(socket_addr2, tx.clone(), "t1".to_owned())
+            .await;
+
+        let result = service
+            .num_subscribers(vec!["t1".to_owned(), "t2".to_owned()])
+            .await;
+
+        assert_eq!(result, vec![("t1".to_owned(), 2), ("t2".to_owned(), 0)]);
+        assert_eq!(service.num_patterns().await, 0);
+    }
+
+    #[tokio::test]
+    async fn subscribe_from_should_replay_durable_topic() {
+        let service = MyBrokerService::new();
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let (tx1, _rx1) = output_buffer();
+        let (tx2, mut rx2) = output_buffer();
+
+        let topic = "t1";
+        service
+            .make_durable(topic.to_owned(), RetentionPolicy::MaxLen(2))
+            .await
+            .unwrap();
+        service
+            .subscribe(socket_addr1, tx1.clone(), topic.to_owned())
+            .await;
+        service.publish(socket_addr1, vec![1u8]).await;
+        service.publish(socket_addr1, vec![2u8]).await;
+        service.publish(socket_addr1, vec![3u8]).await;
+
+        // offset 0 has been dropped by the retention policy
+        service
+            .subscribe_from(socket_addr2, tx2.clone(), topic.to_owned(), 0)
+            .await;
+        service.publish(socket_addr1, vec![4u8]).await;
+
+        assert_eq!(rx2.recv().await, Some(b"1 \x02".to_vec()));
+        assert_eq!(rx2.recv().await, Some(b"2 \x03".to_vec()));
+        assert_eq!(rx2.recv().await, Some(b"3 \x04".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn subscribe_from_should_not_replay_non_durable_topic() {
+        let service = MyBrokerService::new();
+
+        let socket_addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let socket_addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1112);
+        let (tx1, _rx1) = output_buffer();
+        let (tx2, _rx2) = output_buffer();
+
+        let topic = "t1";
+        service
+            .subscribe(socket_addr1, tx1.clone(), topic.to_owned())
+            .await;
+        service.publish(socket_addr1, vec![1u8]).await;
+        service
+            .subscribe_from(socket_addr2, tx2.clone(), topic.to_owned(), 0)
+            .await;
+
+        assert_eq!(tx2.queued_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn durable_topic_should_be_persisted() {
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let mut expected = TopicHistory::new(RetentionPolicy::MaxLen(5));
+        let empty_history = expected.to_bytes();
+        let (_, HistoryWrite::Append(record)) = expected.append(vec![7u8]) else { panic!("not appended") };
+
+        let mut history_store = MockHistoryStoreService::new();
+        history_store
+            .expect_write()
+            .with(eq("t1".to_owned()), eq(empty_history))
+            .once()
+            .returning(|_, _| Ok(()));
+        // only the new message is written
+        history_store
+            .expect_append()
+            .withf(move |topic, appended| {
+                // the timestamps may differ
+                topic == "t1" && appended[..8] == record[..8] && appended[16..] == record[16..]
+            })
+            .once()
+            .returning(|_, _| Ok(()));
+        let service = MyBrokerService::new().with_history_store(Arc::new(history_store));
+
+        let (tx, _rx) = output_buffer();
+        service
+            .make_durable("t1".to_owned(), RetentionPolicy::MaxLen(5))
+            .await
+            .unwrap();
+        service.subscribe(socket_addr, tx, "t1".to_owned()).await;
+        service.publish(socket_addr, vec![7u8]).await;
+    }
+
+    #[tokio::test]
+    async fn read_history_should_recover_durable_topics() {
+        let mut history = TopicHistory::new(RetentionPolicy::MaxLen(5));
+        history.append(vec![9u8]);
+        let bytes = history.to_bytes();
+
+        let mut history_store = MockHistoryStoreService::new();
+        history_store
+            .expect_read()
+            .once()
+            .returning(move || Ok(HashMap::from([("t1".to_owned(), bytes.clone())])));
+        history_store
+            .expect_write()
+            .with(eq("t1".to_owned()), eq(history.to_bytes()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let service = MyBrokerService::new().with_history_store(Arc::new(history_store));
+
+        let result = service.read_history().await;
+
+        assert!(result.is_ok());
+        let histories = service.histories.read().await;
+        assert_eq!(*histories.get("t1").unwrap().lock().await, history);
+    }
+
+    #[tokio::test]
+    async fn publish_to_should_be_ok() {
+        let service = MyBrokerService::new();
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let (tx, mut rx) = output_buffer();
+        service
+            .subscribe(socket_addr, tx, "__keyevent@0__:set".to_owned())
+            .await;
+
+        service
+            .publish_to("__keyevent@0__:set".to_owned(), vec![107u8])
+            .await;
+        service
+            .publish_to("__keyevent@0__:del".to_owned(), vec![108u8])
+            .await;
+
+        assert_eq!(rx.recv().await, Some(vec![107u8]));
+    }
 }
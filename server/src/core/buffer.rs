@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+
+/// Limits applied to the messages queued for a subscriber but not yet written to its socket.
+/// A limit of `0` disables the corresponding check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OutputBufferLimits {
+    pub hard_limit: usize,
+    pub soft_limit: usize,
+    pub soft_limit_duration: Duration,
+}
+
+impl Default for OutputBufferLimits {
+    fn default() -> Self {
+        Self {
+            hard_limit: 32 * 1024 * 1024,
+            soft_limit: 8 * 1024 * 1024,
+            soft_limit_duration: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct OutputBufferState {
+    queued_bytes: AtomicUsize,
+    queued_messages: AtomicUsize,
+    closed: AtomicBool,
+    closed_notify: Notify,
+}
+
+impl OutputBufferState {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    async fn closed(&self) {
+        loop {
+            let notified = self.closed_notify.notified();
+            if self.is_closed() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OutputBufferSender {
+    sender: UnboundedSender<Vec<u8>>,
+    state: Arc<OutputBufferState>,
+}
+
+impl OutputBufferSender {
+    pub fn send(&self, message: Vec<u8>) -> bool {
+        if self.state.is_closed() {
+            return false;
+        }
+        let size = message.len();
+        self.state.queued_bytes.fetch_add(size, Ordering::AcqRel);
+        self.state.queued_messages.fetch_add(1, Ordering::AcqRel);
+        if self.sender.send(message).is_err() {
+            self.state.queued_bytes.fetch_sub(size, Ordering::AcqRel);
+            self.state.queued_messages.fetch_sub(1, Ordering::AcqRel);
+            return false;
+        }
+        true
+    }
+
+    pub fn queued_bytes(&self) -> usize {
+        self.state.queued_bytes.load(Ordering::Acquire)
+    }
+
+    pub fn queued_messages(&self) -> usize {
+        self.state.queued_messages.load(Ordering::Acquire)
+    }
+
+    pub fn close(&self) {
+        self.state.closed.store(true, Ordering::Release);
+        self.state.closed_notify.notify_waiters();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.is_closed()
+    }
+
+    pub async fn closed(&self) {
+        self.state.closed().await
+    }
+}
+
+#[derive(Debug)]
+pub struct OutputBufferReceiver {
+    receiver: UnboundedReceiver<Vec<u8>>,
+    state: Arc<OutputBufferState>,
+}
+
+impl OutputBufferReceiver {
+    pub async fn recv(&mut self) -> Option<Vec<u8>> {
+        if self.state.is_closed() {
+            return None;
+        }
+        tokio::select! {
+            message = self.receiver.recv() => {
+                if let Some(message) = &message {
+                    self.state.queued_bytes.fetch_sub(message.len(), Ordering::AcqRel);
+                    self.state.queued_messages.fetch_sub(1, Ordering::AcqRel);
+                }
+                message
+            }
+            _ = self.state.closed() => None,
+        }
+    }
+
+    pub async fn closed(&self) {
+        self.state.closed().await
+    }
+}
+
+pub fn output_buffer() -> (OutputBufferSender, OutputBufferReceiver) {
+    let (sender, receiver) = unbounded_channel::<Vec<u8>>();
+    let state = Arc::new(OutputBufferState::default());
+    (
+        OutputBufferSender {
+            sender,
+            state: Arc::clone(&state),
+        },
+        OutputBufferReceiver { receiver, state },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn send_and_recv_should_track_queued_bytes() {
+        let (sender, mut receiver) = output_buffer();
+
+        assert!(sender.send(vec![1u8, 2u8, 3u8]));
+        assert!(sender.send(vec![4u8, 5u8]));
+        assert_eq!(sender.queued_bytes(), 5);
+        assert_eq!(sender.queued_messages(), 2);
+
+        assert_eq!(receiver.recv().await, Some(vec![1u8, 2u8, 3u8]));
+        assert_eq!(sender.queued_bytes(), 2);
+        assert_eq!(sender.queued_messages(), 1);
+        assert_eq!(receiver.recv().await, Some(vec![4u8, 5u8]));
+        assert_eq!(sender.queued_bytes(), 0);
+        assert_eq!(sender.queued_messages(), 0);
+    }
+
+    #[tokio::test]
+    async fn close_should_stop_the_receiver() {
+        let (sender, mut receiver) = output_buffer();
+        assert!(sender.send(vec![1u8]));
+
+        sender.close();
+
+        assert!(sender.is_closed());
+        assert!(!sender.send(vec![2u8]));
+        assert_eq!(receiver.recv().await, None);
+        sender.closed().await;
+        receiver.closed().await;
+    }
+
+    #[tokio::test]
+    async fn close_should_wake_up_a_waiting_receiver() {
+        let (sender, mut receiver) = output_buffer();
+        let waiting = tokio::spawn(async move { receiver.recv().await });
+
+        tokio::task::yield_now().await;
+        sender.close();
+
+        assert_eq!(waiting.await.unwrap(), None);
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The first byte of the cache file of a key having a time to live, followed by its deadline. No tlv type is 0, so
+/// the cache file of a key without one is its tlv alone as it was before keys could expire.
+const DEADLINE_MARKER: u8 = 0;
+/// The size of the header of the cache file of a key having a time to live: the marker then the deadline in big
+/// endian milliseconds since the unix epoch.
+pub const DEADLINE_HEADER_SIZE: usize = 9;
+
+pub fn encode(value: Vec<u8>, deadline: Option<SystemTime>) -> Vec<u8> {
+    let Some(deadline) = deadline else { return value; };
+    let millis = deadline.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let mut contents = Vec::with_capacity(DEADLINE_HEADER_SIZE + value.len());
+    contents.push(DEADLINE_MARKER);
+    contents.extend(millis.to_be_bytes());
+    contents.extend(value);
+    contents
+}
+
+pub fn decode(mut contents: Vec<u8>) -> Option<(Vec<u8>, Option<SystemTime>)> {
+    if contents.first() != Some(&DEADLINE_MARKER) {
+        return Some((contents, None));
+    }
+    let millis = contents.get(1..DEADLINE_HEADER_SIZE)?;
+    let deadline = UNIX_EPOCH + Duration::from_millis(u64::from_be_bytes(millis.try_into().unwrap()));
+    contents.drain(..DEADLINE_HEADER_SIZE);
+    Some((contents, Some(deadline)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::core::cache::deadline::{decode, encode};
+
+    #[test]
+    fn deadline_should_be_decoded() {
+        let deadline = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        let contents = encode(vec![1u8, 2u8], Some(deadline));
+
+        assert_eq!(contents.len(), 11);
+        assert_eq!(decode(contents.clone()), Some((vec![1u8, 2u8], Some(deadline))));
+        assert_eq!(decode(contents[..5].to_vec()), None);
+        assert_eq!(encode(vec![1u8, 2u8], None), vec![1u8, 2u8]);
+        assert_eq!(decode(vec![1u8, 2u8]), Some((vec![1u8, 2u8], None)));
+        assert_eq!(decode(Vec::new()), Some((Vec::new(), None)));
+    }
+}
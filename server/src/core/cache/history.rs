@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::{automock, predicate::*};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::{fs, io};
+
+use crate::core::cache::reserved_name;
+use crate::core::file::{is_temp_file, replace_file};
+
+const HISTORY_FOLDER: &str = "topic-history";
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait HistoryStoreService: Send + Sync {
+    async fn read(&self) -> io::Result<HashMap<String, Vec<u8>>>;
+
+    async fn write(&self, topic: String, history: Vec<u8>) -> io::Result<()>;
+
+    /// Appends the record of a message to the history of a topic, which must have been written before.
+    async fn append(&self, topic: String, record: Vec<u8>) -> io::Result<()>;
+}
+
+pub struct MyHistoryStore {
+    folder: PathBuf,
+}
+
+impl MyHistoryStore {
+    pub fn new(cache_folder: &str) -> Self {
+        Self {
+            folder: Path::new(cache_folder).join(reserved_name(HISTORY_FOLDER.to_owned())),
+        }
+    }
+}
+
+#[async_trait]
+impl HistoryStoreService for MyHistoryStore {
+    async fn read(&self) -> io::Result<HashMap<String, Vec<u8>>> {
+        let mut histories = HashMap::<String, Vec<u8>>::new();
+        if !fs::try_exists(&self.folder).await? {
+            return Ok(histories);
+        }
+        log::info!("reading topic history... from: {}", self.folder.display());
+        let mut dir = fs::read_dir(&self.folder).await?;
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if entry.file_type().await?.is_file() && !is_temp_file(&entry.file_name()) {
+                let file_contents = fs::read(entry.path()).await?;
+                let topic = entry.file_name().to_str().unwrap().to_owned();
+                histories.insert(topic, file_contents);
+            }
+        }
+        log::info!("reading topic history... done");
+        Ok(histories)
+    }
+
+    async fn write(&self, topic: String, history: Vec<u8>) -> io::Result<()> {
+        fs::create_dir_all(&self.folder).await?;
+        replace_file(&self.folder.join(topic), &history, false).await
+    }
+
+    async fn append(&self, topic: String, record: Vec<u8>) -> io::Result<()> {
+        let mut file = OpenOptions::new().append(true).open(self.folder.join(topic)).await?;
+        file.write_all(&record).await?;
+        file.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use tokio::fs;
+
+    use crate::core::cache::history::{HistoryStoreService, MyHistoryStore};
+
+    fn create_temp_folder() -> TempDir {
+        TempDir::new("history-store-tests").unwrap()
+    }
+
+    fn new_instance(temp_dir: &TempDir) -> MyHistoryStore {
+        let temp_dir_path = temp_dir.path().display().to_string();
+        MyHistoryStore::new(temp_dir_path.as_str())
+    }
+
+    #[tokio::test]
+    async fn read_should_be_empty_without_folder() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+
+        let result = instance.read().await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn write_should_be_red() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+
+        let result = instance
+            .write("topicA".to_owned(), vec![1u8, 2u8, 3u8])
+            .await;
+        assert!(result.is_ok());
+        assert!(temp_dir.path().join("topic-history").join("topicA").is_file());
+        // left by a crash during a write
+        fs::write(temp_dir.path().join("topic-history").join("topicA.tmp"), [4u8]).await.unwrap();
+
+        let result = instance.read().await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.get("topicA"), Some(&vec![1u8, 2u8, 3u8]));
+    }
+
+    #[tokio::test]
+    async fn append_should_extend_written_history() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+
+        assert!(instance.append("topicA".to_owned(), vec![4u8]).await.is_err());
+        instance.write("topicA".to_owned(), vec![1u8]).await.unwrap();
+        instance.append("topicA".to_owned(), vec![2u8, 3u8]).await.unwrap();
+
+        let result = instance.read().await.unwrap();
+        assert_eq!(result.get("topicA"), Some(&vec![1u8, 2u8, 3u8]));
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use tokio::{fs, io};
+
+use crate::core::cache::reserved_name;
+
+/// The keys of a multi-key write not entirely applied to the cache files yet, they are written again on startup.
+/// Every write has its own `write-journal-<sequence>` journal so that concurrent ones don't clash.
+const JOURNAL_PREFIX: &str = "write-journal-";
+/// The suffix of a journal being written, it is renamed once complete so that a crash in between leaves none of
+/// its keys written.
+const PENDING_SUFFIX: &str = "-tmp";
+
+const LENGTH_SIZE: usize = 8;
+
+pub fn journal_file(sequence: u64) -> String {
+    reserved_name(format!("{}{}", JOURNAL_PREFIX, sequence))
+}
+
+pub fn pending_journal_file(sequence: u64) -> String {
+    reserved_name(format!("{}{}{}", JOURNAL_PREFIX, sequence, PENDING_SUFFIX))
+}
+
+pub fn encode(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut journal = Vec::new();
+    for (key, value) in entries {
+        journal.extend((key.len() as u64).to_be_bytes());
+        journal.extend(key.as_bytes());
+        journal.extend((value.len() as u64).to_be_bytes());
+        journal.extend(value);
+    }
+    journal
+}
+
+pub fn decode(mut journal: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    while !journal.is_empty() {
+        let key = take_chunk(&mut journal)?;
+        let value = take_chunk(&mut journal)?;
+        entries.push((String::from_utf8(key.to_vec()).ok()?, value.to_vec()));
+    }
+    Some(entries)
+}
+
+fn take_chunk<'a>(journal: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let length = journal.get(..LENGTH_SIZE)?;
+    let length = usize::try_from(u64::from_be_bytes(length.try_into().unwrap())).ok()?;
+    let end = LENGTH_SIZE.checked_add(length)?;
+    let chunk = journal.get(LENGTH_SIZE..end)?;
+    *journal = &journal[end..];
+    Some(chunk)
+}
+
+pub async fn replay(folder: &Path) -> io::Result<()> {
+    let mut journals = Vec::new();
+    let mut dir = fs::read_dir(folder).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(sequence) = file_name.to_str().and_then(|name| name.strip_prefix(JOURNAL_PREFIX)) else {
+            continue;
+        };
+        if sequence.ends_with(PENDING_SUFFIX) {
+            fs::remove_file(entry.path()).await?;
+        } else if let Ok(sequence) = sequence.parse::<u64>() {
+            journals.push((sequence, entry.path()));
+        }
+    }
+    journals.sort();
+    for (_, journal_path) in journals {
+        replay_journal(folder, &journal_path).await?;
+    }
+    Ok(())
+}
+
+async fn replay_journal(folder: &Path, journal_path: &Path) -> io::Result<()> {
+    let journal = fs::read(journal_path).await?;
+    match decode(&journal) {
+        Some(entries) => {
+            log::info!("replaying the write journal of {} keys... from: {}", entries.len(), folder.display());
+            for (key, value) in entries {
+                fs::write(folder.join(key), value).await?;
+            }
+        }
+        None => log::error!("dropping the corrupted write journal: {}", journal_path.display()),
+    }
+    fs::remove_file(journal_path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use tokio::fs;
+
+    use crate::core::cache::journal::{decode, encode, journal_file, pending_journal_file, replay};
+
+    #[test]
+    fn journal_should_be_decoded() {
+        let entries = vec![("a".to_owned(), vec![1u8, 2u8]), ("bc".to_owned(), Vec::new())];
+
+        let journal = encode(&entries);
+
+        assert_eq!(decode(&journal), Some(entries));
+        assert_eq!(decode(&journal[..journal.len() - 1]), None);
+        assert_eq!(decode(&[]), Some(Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn replay_should_write_committed_keys_only() {
+        let temp_dir = TempDir::new("journal-tests").unwrap();
+        let journal = encode(&[("a".to_owned(), vec![1u8]), ("b".to_owned(), vec![2u8])]);
+        fs::write(temp_dir.path().join(journal_file(9)), journal).await.unwrap();
+        // written after the other one
+        let journal = encode(&[("b".to_owned(), vec![4u8])]);
+        fs::write(temp_dir.path().join(journal_file(10)), journal).await.unwrap();
+        let pending = encode(&[("c".to_owned(), vec![3u8])]);
+        fs::write(temp_dir.path().join(pending_journal_file(11)), pending).await.unwrap();
+
+        replay(temp_dir.path()).await.unwrap();
+
+        assert_eq!(fs::read(temp_dir.path().join("a")).await.unwrap(), vec![1u8]);
+        assert_eq!(fs::read(temp_dir.path().join("b")).await.unwrap(), vec![4u8]);
+        assert!(!temp_dir.path().join("c").exists());
+        assert!(!temp_dir.path().join(journal_file(9)).exists());
+        assert!(!temp_dir.path().join(journal_file(10)).exists());
+        assert!(!temp_dir.path().join(pending_journal_file(11)).exists());
+        // nothing to replay
+        replay(temp_dir.path()).await.unwrap();
+    }
+}
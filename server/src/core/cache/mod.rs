@@ -1,2 +1,52 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::core::command::registry::is_valid_key;
+
+pub mod deadline;
+pub mod history;
+pub mod journal;
+pub mod none;
 pub mod reader;
 pub mod writer;
+
+/// Returns the folder of the cache files of a database: the cache folder itself for the database 0, so that
+/// the caches written before there were several databases are still read, and a `db-<index>` sub folder for
+/// the others.
+pub fn db_folder(cache_folder: &Path, db: usize) -> PathBuf {
+    if db == 0 {
+        cache_folder.to_owned()
+    } else {
+        cache_folder.join(reserved_name(format!("db-{}", db)))
+    }
+}
+
+/// Checks the name of a file of the cache folder that isn't the cache file of a key, e.g. a sub folder or a journal.
+/// A key only has ascii alphanumeric characters, see `is_valid_key`, so such a name needs another character
+/// not to clash with the cache file of a key.
+pub fn reserved_name(name: String) -> String {
+    assert!(!is_valid_key(&name), "'{}' could be the cache file of a key", name);
+    name
+}
+
+/// Whether a file of a database folder is the cache file of a key. The other files are left alone: the folder of the
+/// database 0 may be the data directory shared with e.g. the config and ACL files, or hold a write journal.
+pub fn is_cache_file(file_name: &OsStr) -> bool {
+    file_name.to_str().is_some_and(is_valid_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::cache::reserved_name;
+
+    #[test]
+    fn reserved_name_should_not_be_a_key() {
+        assert_eq!(reserved_name("db-1".to_owned()), "db-1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn reserved_name_should_refuse_a_key() {
+        reserved_name("topics".to_owned());
+    }
+}
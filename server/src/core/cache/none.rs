@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use tokio::io;
+
+use crate::core::cache::reader::CacheReaderService;
+use crate::core::cache::writer::CacheWriterService;
+
+pub struct NoCache;
+
+#[async_trait]
+impl CacheReaderService for NoCache {
+    async fn read(&self, _db: usize) -> io::Result<HashMap<String, Vec<u8>>> {
+        Ok(HashMap::new())
+    }
+}
+
+#[async_trait]
+impl CacheWriterService for NoCache {
+    async fn write(&self, _db: usize, _key: String, _value: Vec<u8>) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn write_all(&self, _db: usize, _entries: Vec<(String, Vec<u8>)>) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn write_at(&self, _db: usize, _key: String, _parts: Vec<(u64, Vec<u8>)>) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn remove(&self, _db: usize, _key: String) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn flush(&self, _db: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn swap(&self, _db1: usize, _db2: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,14 +1,18 @@
 use std::collections::HashMap;
+use std::path::Path;
 
 use async_trait::async_trait;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 use tokio::{fs, io};
 
+use crate::core::cache::journal::replay;
+use crate::core::cache::{db_folder, is_cache_file};
+
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait CacheReaderService: Send + Sync {
-    async fn read(&self) -> io::Result<HashMap<String, Vec<u8>>>;
+    async fn read(&self, db: usize) -> io::Result<HashMap<String, Vec<u8>>>;
 }
 
 pub struct MyCacheReader {
@@ -25,20 +29,25 @@ impl MyCacheReader {
 
 #[async_trait]
 impl CacheReaderService for MyCacheReader {
-    async fn read(&self) -> io::Result<HashMap<String, Vec<u8>>> {
+    async fn read(&self, db: usize) -> io::Result<HashMap<String, Vec<u8>>> {
         let mut cache = HashMap::<String, Vec<u8>>::new();
-        println!("reading cache... from: {}", self.folder);
-        let mut dir = fs::read_dir(&self.folder).await?;
+        let folder = db_folder(Path::new(&self.folder), db);
+        if db > 0 && !fs::try_exists(&folder).await? {
+            return Ok(cache);
+        }
+        log::info!("reading cache... from: {}", folder.display());
+        replay(&folder).await?;
+        let mut dir = fs::read_dir(&folder).await?;
         while let Ok(Some(entry)) = dir.next_entry().await {
-            if entry.file_type().await?.is_file() {
-                println!("\tuncache: {}", entry.path().to_str().unwrap());
+            if entry.file_type().await?.is_file() && is_cache_file(&entry.file_name()) {
+                log::debug!("\tuncache: {}", entry.path().to_str().unwrap());
                 let file_contents = fs::read(entry.path()).await?;
                 let file_name = entry.file_name();
                 let file_name = file_name.to_str().unwrap().to_owned();
                 cache.insert(file_name, file_contents.clone());
             }
         }
-        println!("reading cache... done");
+        log::info!("reading cache... done");
         Ok(cache)
     }
 }
@@ -49,6 +58,7 @@ mod tests {
     use tokio::fs::File;
     use tokio::io::AsyncWriteExt;
 
+    use crate::core::cache::journal::{encode, journal_file};
     use crate::core::cache::reader::{CacheReaderService, MyCacheReader};
 
     fn create_temp_folder() -> TempDir {
@@ -70,9 +80,11 @@ mod tests {
     async fn read_should_be_red() {
         let temp_dir = create_temp_folder();
         write_data_to_file(&temp_dir, "Joe", vec![2u8, 4u8, 6u8, 8u8, 10u8]).await;
+        // not a key
+        write_data_to_file(&temp_dir, "server.conf", vec![1u8]).await;
         let instance = new_instance(&temp_dir);
 
-        let result = instance.read().await.unwrap();
+        let result = instance.read(0).await.unwrap();
         assert_eq!(result.len(), 1);
         let joe = result.get_key_value("Joe");
         assert!(joe.is_some());
@@ -81,4 +93,34 @@ mod tests {
             (&"Joe".to_owned(), &vec![2u8, 4u8, 6u8, 8u8, 10u8])
         );
     }
+
+    #[tokio::test]
+    async fn read_should_replay_write_journal() {
+        let temp_dir = create_temp_folder();
+        write_data_to_file(&temp_dir, "Joe", vec![1u8]).await;
+        let journal = encode(&[("Joe".to_owned(), vec![2u8]), ("Jane".to_owned(), vec![3u8])]);
+        write_data_to_file(&temp_dir, &journal_file(0), journal).await;
+        let instance = new_instance(&temp_dir);
+
+        let result = instance.read(0).await.unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.get("Joe"), Some(&vec![2u8]));
+        assert_eq!(result.get("Jane"), Some(&vec![3u8]));
+    }
+
+    #[tokio::test]
+    async fn read_should_keep_databases_separate() {
+        let temp_dir = create_temp_folder();
+        write_data_to_file(&temp_dir, "Joe", vec![1u8]).await;
+        tokio::fs::create_dir(temp_dir.path().join("db-2")).await.unwrap();
+        write_data_to_file(&temp_dir, "db-2/Jane", vec![2u8]).await;
+        let instance = new_instance(&temp_dir);
+
+        let result = instance.read(0).await.unwrap();
+        assert_eq!(result.keys().collect::<Vec<_>>(), vec!["Joe"]);
+        let result = instance.read(2).await.unwrap();
+        assert_eq!(result.get("Jane"), Some(&vec![2u8]));
+        assert!(instance.read(1).await.unwrap().is_empty());
+    }
 }
@@ -1,36 +1,169 @@
-use std::path::Path;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
 
 use async_trait::async_trait;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
-use tokio::fs::File;
-use tokio::io;
-use tokio::io::AsyncWriteExt;
+use tokio::fs::{File, OpenOptions};
+use tokio::{fs, io};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::core::cache::{db_folder, is_cache_file};
+use crate::core::cache::journal::{encode, journal_file, pending_journal_file};
+use crate::core::config::service::ConfigListener;
+use crate::core::config::{AppendFsync, Config};
+use crate::core::file::replace_file;
+
+const SWAP_FOLDER: &str = "swap-tmp";
 
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait CacheWriterService: Send + Sync {
-    async fn write(&self, key: String, value: Vec<u8>) -> io::Result<()>;
+    async fn write(&self, db: usize, key: String, value: Vec<u8>) -> io::Result<()>;
+
+    /// Writes several keys atomically: after a crash either all of them or none are read back.
+    async fn write_all(&self, db: usize, entries: Vec<(String, Vec<u8>)>) -> io::Result<()>;
+
+    async fn write_at(&self, db: usize, key: String, parts: Vec<(u64, Vec<u8>)>) -> io::Result<()>;
+
+    async fn remove(&self, db: usize, key: String) -> io::Result<()>;
+
+    async fn flush(&self, db: usize) -> io::Result<()>;
+
+    async fn swap(&self, db1: usize, db2: usize) -> io::Result<()>;
 }
 
 pub struct MyCacheWriter {
     folder: String,
+    appendfsync: RwLock<AppendFsync>,
+    journal_sequence: AtomicU64,
 }
 
 impl MyCacheWriter {
     pub fn new(folder: &str) -> Self {
         Self {
             folder: folder.to_owned(),
+            appendfsync: RwLock::new(AppendFsync::default()),
+            journal_sequence: AtomicU64::new(0),
         }
     }
+
+    pub fn with_appendfsync(mut self, appendfsync: AppendFsync) -> Self {
+        self.appendfsync = RwLock::new(appendfsync);
+        self
+    }
+
+    fn db_folder(&self, db: usize) -> PathBuf {
+        db_folder(Path::new(&self.folder), db)
+    }
+
+    fn sync(&self) -> bool {
+        *self.appendfsync.read().unwrap() == AppendFsync::Always
+    }
+}
+
+impl ConfigListener for MyCacheWriter {
+    fn apply_config(&self, config: &Config) {
+        *self.appendfsync.write().unwrap() = config.appendfsync;
+    }
+}
+
+async fn move_files(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to).await?;
+    let mut dir = match fs::read_dir(from).await {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        dir => dir?,
+    };
+    while let Some(entry) = dir.next_entry().await? {
+        if entry.file_type().await?.is_file() && is_cache_file(&entry.file_name()) {
+            fs::rename(entry.path(), to.join(entry.file_name())).await?;
+        }
+    }
+    Ok(())
 }
 
 #[async_trait]
 impl CacheWriterService for MyCacheWriter {
-    async fn write(&self, key: String, value: Vec<u8>) -> io::Result<()> {
-        let file_path = Path::new(&self.folder).join(key);
-        let mut cache_file = File::create(file_path).await?;
-        cache_file.write_all(&value).await
+    async fn write(&self, db: usize, key: String, value: Vec<u8>) -> io::Result<()> {
+        let folder = self.db_folder(db);
+        let file_path = folder.join(key);
+        // a crash while writing leaves the previous value rather than a truncated one
+        match replace_file(&file_path, &value, self.sync()).await {
+            // the first key of the database
+            Err(err) if err.kind() == io::ErrorKind::NotFound && db > 0 => {
+                fs::create_dir_all(&folder).await?;
+                replace_file(&file_path, &value, self.sync()).await
+            }
+            result => result,
+        }
+    }
+
+    async fn write_all(&self, db: usize, entries: Vec<(String, Vec<u8>)>) -> io::Result<()> {
+        if let [(key, value)] = entries.as_slice() {
+            return self.write(db, key.clone(), value.clone()).await;
+        }
+        let folder = self.db_folder(db);
+        fs::create_dir_all(&folder).await?;
+        // the keys are written once the journal is, the reader replays it if they may not all be
+        let sequence = self.journal_sequence.fetch_add(1, Ordering::Relaxed);
+        let pending_path = folder.join(pending_journal_file(sequence));
+        let mut journal = File::create(&pending_path).await?;
+        journal.write_all(&encode(&entries)).await?;
+        journal.sync_all().await?;
+        let journal_path = folder.join(journal_file(sequence));
+        fs::rename(&pending_path, &journal_path).await?;
+        for (key, value) in entries {
+            self.write(db, key, value).await?;
+        }
+        fs::remove_file(journal_path).await
+    }
+
+    async fn write_at(&self, db: usize, key: String, parts: Vec<(u64, Vec<u8>)>) -> io::Result<()> {
+        let file_path = self.db_folder(db).join(key);
+        let mut cache_file = OpenOptions::new().write(true).open(&file_path).await?;
+        for (offset, bytes) in parts {
+            cache_file.seek(SeekFrom::Start(offset)).await?;
+            cache_file.write_all(&bytes).await?;
+        }
+        if self.sync() {
+            // the pending writes are completed first
+            cache_file.sync_all().await
+        } else {
+            // tokio writes the file in the background, it is done once flushed
+            cache_file.flush().await
+        }
+    }
+
+    async fn remove(&self, db: usize, key: String) -> io::Result<()> {
+        let file_path = self.db_folder(db).join(key);
+        match fs::remove_file(file_path).await {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    async fn flush(&self, db: usize) -> io::Result<()> {
+        let mut dir = match fs::read_dir(self.db_folder(db)).await {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            dir => dir?,
+        };
+        while let Some(entry) = dir.next_entry().await? {
+            if entry.file_type().await?.is_file() && is_cache_file(&entry.file_name()) {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn swap(&self, db1: usize, db2: usize) -> io::Result<()> {
+        let swap_folder = Path::new(&self.folder).join(SWAP_FOLDER);
+        let (folder1, folder2) = (self.db_folder(db1), self.db_folder(db2));
+        move_files(&folder1, &swap_folder).await?;
+        move_files(&folder2, &folder1).await?;
+        move_files(&swap_folder, &folder2).await?;
+        fs::remove_dir(&swap_folder).await
     }
 }
 
@@ -40,6 +173,8 @@ mod tests {
     use tokio::fs;
 
     use crate::core::cache::writer::{CacheWriterService, MyCacheWriter};
+    use crate::core::config::service::ConfigListener;
+    use crate::core::config::{AppendFsync, Config};
 
     fn create_temp_folder() -> TempDir {
         TempDir::new("cache-writer-tests").unwrap()
@@ -56,7 +191,7 @@ mod tests {
         let instance = new_instance(&temp_dir);
 
         let result = instance
-            .write("hello".to_owned(), vec![200u8, 201u8, 202u8])
+            .write(0, "hello".to_owned(), vec![200u8, 201u8, 202u8])
             .await;
 
         assert!(result.is_ok());
@@ -67,4 +202,113 @@ mod tests {
         assert!(file_contents.is_ok());
         assert_eq!(file_contents.unwrap(), vec![200u8, 201u8, 202u8]);
     }
+
+    #[tokio::test]
+    async fn write_should_follow_appendfsync() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir).with_appendfsync(AppendFsync::Always);
+        instance.write(0, "a".to_owned(), vec![1u8]).await.unwrap();
+        instance.write_at(0, "a".to_owned(), vec![(1, vec![2u8])]).await.unwrap();
+
+        instance.apply_config(&Config::default());
+        instance.write(0, "b".to_owned(), vec![3u8]).await.unwrap();
+
+        assert_eq!(*instance.appendfsync.read().unwrap(), AppendFsync::No);
+        assert_eq!(fs::read(temp_dir.path().join("a")).await.unwrap(), vec![1u8, 2u8]);
+        assert_eq!(fs::read(temp_dir.path().join("b")).await.unwrap(), vec![3u8]);
+    }
+
+    #[tokio::test]
+    async fn write_all_should_be_written() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+
+        let entries = vec![("a".to_owned(), vec![1u8]), ("b".to_owned(), vec![2u8])];
+        instance.write_all(1, entries).await.unwrap();
+
+        assert_eq!(fs::read(temp_dir.path().join("db-1/a")).await.unwrap(), vec![1u8]);
+        assert_eq!(fs::read(temp_dir.path().join("db-1/b")).await.unwrap(), vec![2u8]);
+        let mut dir = fs::read_dir(temp_dir.path().join("db-1")).await.unwrap();
+        let mut files = 0;
+        while dir.next_entry().await.unwrap().is_some() {
+            files += 1;
+        }
+        // the journal is gone
+        assert_eq!(files, 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_write_all_should_not_clash() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+
+        let entries1 = vec![("a".to_owned(), vec![1u8]), ("b".to_owned(), vec![2u8])];
+        let entries2 = vec![("c".to_owned(), vec![3u8]), ("d".to_owned(), vec![4u8])];
+        let (result1, result2) = tokio::join!(instance.write_all(0, entries1), instance.write_all(0, entries2));
+
+        assert!(result1.is_ok());
+        assert!(result2.is_ok());
+        for (key, value) in [("a", 1u8), ("b", 2u8), ("c", 3u8), ("d", 4u8)] {
+            assert_eq!(fs::read(temp_dir.path().join(key)).await.unwrap(), vec![value]);
+        }
+        assert!(!temp_dir.path().join("write-journal-0").exists());
+        assert!(!temp_dir.path().join("write-journal-1").exists());
+    }
+
+    #[tokio::test]
+    async fn write_at_should_overwrite_parts() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+        instance.write(0, "hello".to_owned(), vec![1u8, 2u8, 3u8]).await.unwrap();
+
+        let parts = vec![(4, vec![5u8, 6u8]), (0, vec![9u8])];
+        instance.write_at(0, "hello".to_owned(), parts).await.unwrap();
+
+        let file_contents = fs::read(temp_dir.path().join("hello")).await.unwrap();
+        assert_eq!(file_contents, vec![9u8, 2u8, 3u8, 0u8, 5u8, 6u8]);
+        // the key must exist
+        assert!(instance.write_at(0, "missing".to_owned(), Vec::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn remove_should_be_removed() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+        instance
+            .write(0, "hello".to_owned(), vec![200u8, 201u8, 202u8])
+            .await
+            .unwrap();
+
+        let result = instance.remove(0, "hello".to_owned()).await;
+
+        assert!(result.is_ok());
+        assert!(!temp_dir.path().join("hello").exists());
+        // removing a missing key is not an error
+        assert!(instance.remove(0, "hello".to_owned()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn databases_should_be_written_separately() {
+        let temp_dir = create_temp_folder();
+        let instance = new_instance(&temp_dir);
+        instance.write(0, "a".to_owned(), vec![0u8]).await.unwrap();
+        instance.write(1, "b".to_owned(), vec![1u8]).await.unwrap();
+        instance.write(2, "c".to_owned(), vec![2u8]).await.unwrap();
+        // the data directory may hold other files
+        fs::write(temp_dir.path().join("server.conf"), "port 6380\n").await.unwrap();
+
+        instance.swap(0, 1).await.unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("b")).await.unwrap(), vec![1u8]);
+        assert_eq!(fs::read(temp_dir.path().join("db-1/a")).await.unwrap(), vec![0u8]);
+        assert!(!temp_dir.path().join("a").exists());
+
+        instance.flush(0).await.unwrap();
+        assert!(!temp_dir.path().join("b").exists());
+        // the other databases are kept
+        assert!(temp_dir.path().join("db-1/a").exists());
+        assert!(temp_dir.path().join("db-2/c").exists());
+        assert!(!temp_dir.path().join("swap-tmp").exists());
+        assert!(temp_dir.path().join("server.conf").exists());
+        assert!(!temp_dir.path().join("db-1/server.conf").exists());
+    }
 }
@@ -0,0 +1,183 @@
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+
+pub fn setuser(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if let Err(err) = context.auth_service.set_user(&args[2], &args[3..]) {
+            context.reply(format!("err ACL SETUSER failed: {}\n", err).as_bytes());
+            return;
+        }
+        if save(context).await {
+            context.reply(b"setuser ok\n");
+        }
+    })
+}
+
+pub fn getuser(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        match context.auth_service.user(&args[2]) {
+            Some(user) => context.reply_lines(&user.describe()),
+            None => context.reply(b"nil\n"),
+        }
+    })
+}
+
+pub fn deluser(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let deleted = match context.auth_service.delete_users(&args[2..]) {
+            Ok(deleted) => deleted,
+            Err(err) => {
+                context.reply(format!("err {}\n", err).as_bytes());
+                return;
+            }
+        };
+        if deleted > 0 && !save(context).await {
+            return;
+        }
+        context.reply_line(deleted.to_string().as_bytes());
+    })
+}
+
+pub fn list(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let users: Vec<String> = context
+            .auth_service
+            .users()
+            .iter()
+            .map(|user| format!("user {} {}", user.name(), user.rules().join(" ")))
+            .collect();
+        context.reply_lines(&users);
+    })
+}
+
+pub fn whoami(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let user = context.session.user().to_owned();
+        context.reply_line(user.as_bytes());
+    })
+}
+
+pub fn log(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let count = match &args[2..] {
+            [] => usize::MAX,
+            [arg] if arg.eq_ignore_ascii_case("reset") => {
+                context.auth_service.reset_log();
+                context.reply(b"reset ok\n");
+                return;
+            }
+            [arg] => match arg.parse::<usize>() {
+                Ok(count) => count,
+                Err(_) => {
+                    context.reply(b"err value is not an integer or out of range\n");
+                    return;
+                }
+            },
+            _ => {
+                context.reply(b"err syntax error\n");
+                return;
+            }
+        };
+        let entries: Vec<String> = context
+            .auth_service
+            .log()
+            .iter()
+            .take(count)
+            .map(|entry| entry.describe())
+            .collect();
+        context.reply_lines(&entries);
+    })
+}
+
+async fn save(context: &mut CommandContext) -> bool {
+    if let Err(err) = context.auth_service.save().await {
+        log::error!("failed to save the ACL file: {}", err);
+        context.reply(format!("err the ACL file could not be saved: {}\n", err).as_bytes());
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::acl::{deluser, getuser, list, log, setuser, whoami};
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+
+    fn new_context() -> CommandContext {
+        let (context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn setuser_should_be_handled() {
+        let mut context = new_context();
+
+        setuser(&mut context, args("acl setuser alice on nopass ~cache* &events.* +get")).await;
+        setuser(&mut context, args("acl setuser alice xyz")).await;
+        getuser(&mut context, args("acl getuser alice")).await;
+        getuser(&mut context, args("acl getuser bob")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"setuser ok\n\
+            err ACL SETUSER failed: invalid rule 'xyz'\n\
+            flags on nopass\npasswords \ncommands -@all +get\nkeys ~cache*\nchannels &events.*\n\
+            nil\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn deluser_and_list_should_be_handled() {
+        let mut context = new_context();
+        context.auth_service.set_user("alice", &args("on +get")).unwrap();
+
+        list(&mut context, args("acl list")).await;
+        deluser(&mut context, args("acl deluser default")).await;
+        deluser(&mut context, args("acl deluser alice bob")).await;
+        list(&mut context, args("acl list")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"user alice on resetchannels -@all +get\n\
+            user default on nopass ~* &* +@all\n\
+            err the 'default' user can't be deleted\n\
+            1\n\
+            user default on nopass ~* &* +@all\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn whoami_and_log_should_be_handled() {
+        let mut context = new_context();
+        context.session.authenticate("alice".to_owned());
+        context.auth_service.authenticate(context.address, "bob", "wrong");
+
+        whoami(&mut context, args("acl whoami")).await;
+        log(&mut context, args("acl log 0")).await;
+        log(&mut context, args("acl log x")).await;
+        let response = String::from_utf8(context.take_response()).unwrap();
+        assert_eq!(response, "alice\nempty\nerr value is not an integer or out of range\n");
+
+        log(&mut context, args("acl log")).await;
+        let response = String::from_utf8(context.take_response()).unwrap();
+        assert!(
+            response.starts_with("count 1 reason auth object AUTH username bob age-seconds "),
+            "{}",
+            response
+        );
+        assert!(response.ends_with(" client 127.0.0.1:1111\n"), "{}", response);
+
+        log(&mut context, args("acl log reset")).await;
+        log(&mut context, args("acl log")).await;
+        assert_eq!(context.take_response(), b"reset ok\nempty\n".to_vec());
+    }
+}
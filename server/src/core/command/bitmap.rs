@@ -0,0 +1,737 @@
+use std::ops::Range;
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+use crate::core::command::string::{
+    integer, is_string, set_all, string_length, write_range, MAX_STRING_LENGTH, WRONGTYPE,
+};
+use crate::core::notify::KeyEvent;
+use crate::core::redis::{SetCondition, SetExpiry};
+use crate::core::tlv::{from_tlv, resize_tlv, split_tlv, to_tlv, TLVType, TLV_HEADER_SIZE};
+
+const MAX_BIT_OFFSET: u64 = MAX_STRING_LENGTH as u64 * 8;
+
+const INVALID_BIT_OFFSET: &[u8] = b"err bit offset is not an integer or out of range\n";
+const INVALID_BITFIELD_TYPE: &[u8] =
+    b"err Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.\n";
+
+pub fn setbit(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(offset) = parse_bit_offset(context, &args[2]) else { return; };
+        let Some(bit) = parse_bit(&args[3]) else {
+            context.reply(b"err bit is not an integer or out of range\n");
+            return;
+        };
+        let update = Box::new(move |tlv: &mut Vec<u8>| {
+            if !tlv.is_empty() && !is_string(tlv) {
+                return (WRONGTYPE.to_vec(), Vec::new());
+            }
+            let index = (offset / 8) as usize;
+            let byte = string_value(tlv).get(index).copied().unwrap_or(0);
+            let mask = 0x80u8 >> (offset % 8);
+            let byte = if bit == 1 { byte | mask } else { byte & !mask };
+            let previous = get_bit(string_value(tlv), offset);
+            let (_, ranges) = write_range(tlv, index, &[byte]);
+            (integer(previous), ranges)
+        });
+        let reply = context.redis_service.update(context.session.db(), &args[1], KeyEvent::SetBit, update).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn getbit(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(offset) = parse_bit_offset(context, &args[2]) else { return; };
+        let inspection = Box::new(move |tlv: Option<&[u8]>| match tlv {
+            Some(tlv) if !is_string(tlv) => WRONGTYPE.to_vec(),
+            tlv => integer(get_bit(tlv.map_or(&[][..], string_value), offset)),
+        });
+        let reply = context.redis_service.inspect(context.session.db(), &args[1], inspection).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn bitcount(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let range = match &args[2..] {
+            [] => None,
+            [start, end, unit @ ..] if unit.len() <= 1 => {
+                let Some(range) = parse_range(context, start, end, unit.first()) else { return; };
+                Some(range)
+            }
+            _ => {
+                context.reply(b"err syntax error\n");
+                return;
+            }
+        };
+        let inspection = Box::new(move |tlv: Option<&[u8]>| {
+            let value = match tlv {
+                Some(tlv) if !is_string(tlv) => return WRONGTYPE.to_vec(),
+                tlv => tlv.map_or(&[][..], string_value),
+            };
+            let (start, end, in_bits) = range.unwrap_or((0, -1, false));
+            let count = bit_range(value.len(), start, end, in_bits).map_or(0, |(first, last)| {
+                count_bits(value, first, last)
+            });
+            integer(count)
+        });
+        let reply = context.redis_service.inspect(context.session.db(), &args[1], inspection).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn bitpos(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(bit) = parse_bit(&args[2]) else {
+            context.reply(b"err The bit argument must be 1 or 0.\n");
+            return;
+        };
+        let ((start, end, in_bits), end_given) = match &args[3..] {
+            [] => ((0, -1, false), false),
+            [start] => {
+                let Ok(start) = start.parse::<i64>() else {
+                    context.reply(b"err value is not an integer or out of range\n");
+                    return;
+                };
+                ((start, -1, false), false)
+            }
+            [start, end, unit @ ..] if unit.len() <= 1 => {
+                let Some(range) = parse_range(context, start, end, unit.first()) else { return; };
+                (range, true)
+            }
+            _ => {
+                context.reply(b"err syntax error\n");
+                return;
+            }
+        };
+        let inspection = Box::new(move |tlv: Option<&[u8]>| {
+            let value = match tlv {
+                Some(tlv) if !is_string(tlv) => return WRONGTYPE.to_vec(),
+                tlv => tlv.map_or(&[][..], string_value),
+            };
+            if value.is_empty() {
+                return integer(if bit == 1 { -1 } else { 0 });
+            }
+            let Some((first, last)) = bit_range(value.len(), start, end, in_bits) else { return integer(-1); };
+            match find_bit(value, first, last, bit) {
+                Some(position) => integer(position),
+                None if bit == 0 && !end_given => integer(last + 1),
+                None => integer(-1),
+            }
+        });
+        let reply = context.redis_service.inspect(context.session.db(), &args[1], inspection).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn bitop(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let operation = args[1].to_lowercase();
+        if !matches!(operation.as_str(), "and" | "or" | "xor" | "not") {
+            context.reply(b"err syntax error\n");
+            return;
+        }
+        if operation == "not" && args.len() != 4 {
+            context.reply(b"err BITOP NOT must be called with a single source key.\n");
+            return;
+        }
+        let db = context.session.db();
+        let mut sources = Vec::with_capacity(args.len() - 3);
+        for key in args[3..].iter() {
+            match context.redis_service.get(db, key).await {
+                Some(tlv) if !is_string(&tlv) => {
+                    context.reply(WRONGTYPE);
+                    return;
+                }
+                tlv => sources.push(tlv.map_or(Vec::new(), from_tlv)),
+            }
+        }
+        let length = sources.iter().map(Vec::len).max().unwrap_or(0);
+        let mut result = sources[0].clone();
+        result.resize(length, 0);
+        for source in sources[1..].iter() {
+            let bytes = source.iter().copied().chain(std::iter::repeat(0));
+            for (byte, other) in result.iter_mut().zip(bytes) {
+                match operation.as_str() {
+                    "and" => *byte &= other,
+                    "or" => *byte |= other,
+                    _ => *byte ^= other,
+                }
+            }
+        }
+        if operation == "not" {
+            result.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+        let destination = args[2].clone();
+        if result.is_empty() {
+            context.redis_service.delete(db, &destination).await;
+            context.reply(b"0\n");
+            return;
+        }
+        let entries = vec![(destination, to_tlv(result, TLVType::String))];
+        if set_all(context, entries, SetCondition::Always, SetExpiry::Clear).await.is_some() {
+            context.reply(&integer(length));
+        }
+    })
+}
+
+pub fn bitfield(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let operations = match parse_bitfield(&args[2..], false) {
+            Ok(operations) => operations,
+            Err(err) => {
+                context.reply(err);
+                return;
+            }
+        };
+        if operations.iter().all(|operation| matches!(operation, BitfieldOperation::Get(_))) {
+            read_bitfield(context, &args[1], operations).await;
+            return;
+        }
+        let update = Box::new(move |tlv: &mut Vec<u8>| {
+            if !tlv.is_empty() && !is_string(tlv) {
+                return (WRONGTYPE.to_vec(), Vec::new());
+            }
+            let length = string_length(tlv);
+            // the string grows for the fields written, even if they overflow
+            let needed = operations
+                .iter()
+                .filter(|operation| !matches!(operation, BitfieldOperation::Get(_)))
+                .map(|operation| operation.field().bytes().end)
+                .max()
+                .unwrap_or(0);
+            resize_tlv(tlv, length.max(needed));
+            let mut ranges = Vec::with_capacity(operations.len() + 2);
+            if needed > length {
+                ranges.push(TLV_HEADER_SIZE + length..TLV_HEADER_SIZE + needed);
+            }
+            let value = &mut tlv[TLV_HEADER_SIZE..];
+            let mut lines = Vec::with_capacity(operations.len());
+            for operation in operations.iter() {
+                let (field, previous) = (operation.field(), operation.field().read(value));
+                let written = match *operation {
+                    BitfieldOperation::Get(_) => {
+                        lines.push(previous.to_string());
+                        continue;
+                    }
+                    BitfieldOperation::Set(_, new, overflow) => {
+                        field.fit(new as i128, overflow).map(|new| (new, previous))
+                    }
+                    BitfieldOperation::IncrBy(_, increment, overflow) => {
+                        field.fit(previous + increment as i128, overflow).map(|new| (new, new))
+                    }
+                };
+                let Some((new, line)) = written else {
+                    lines.push("nil".to_owned());
+                    continue;
+                };
+                field.write(value, new);
+                let bytes = field.bytes();
+                ranges.push(TLV_HEADER_SIZE + bytes.start..TLV_HEADER_SIZE + bytes.end);
+                lines.push(line.to_string());
+            }
+            ranges.push(0..TLV_HEADER_SIZE);
+            (reply_lines(lines), ranges)
+        });
+        let reply = context.redis_service.update(context.session.db(), &args[1], KeyEvent::SetBit, update).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn bitfield_ro(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        match parse_bitfield(&args[2..], true) {
+            Ok(operations) => read_bitfield(context, &args[1], operations).await,
+            Err(err) => context.reply(err),
+        }
+    })
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct BitField {
+    signed: bool,
+    bits: u32,
+    offset: u64,
+}
+
+impl BitField {
+    fn bytes(&self) -> Range<usize> {
+        let last = self.offset + self.bits as u64 - 1;
+        (self.offset / 8) as usize..(last / 8) as usize + 1
+    }
+
+    fn read(&self, value: &[u8]) -> i128 {
+        let raw = (self.offset..self.offset + self.bits as u64)
+            .fold(0u64, |raw, position| (raw << 1) | get_bit(value, position) as u64);
+        if !self.signed {
+            return raw as i128;
+        }
+        // sign extension
+        let shift = 64 - self.bits;
+        ((raw << shift) as i64 >> shift) as i128
+    }
+
+    fn write(&self, value: &mut [u8], field: i128) {
+        let raw = field as u64;
+        for index in 0..self.bits {
+            let position = self.offset + index as u64;
+            let mask = 0x80u8 >> (position % 8);
+            let byte = &mut value[(position / 8) as usize];
+            if (raw >> (self.bits - 1 - index)) & 1 == 1 {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+    }
+
+    fn fit(&self, value: i128, overflow: Overflow) -> Option<i128> {
+        let (min, max) = if self.signed {
+            (-(1i128 << (self.bits - 1)), (1i128 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1i128 << self.bits) - 1)
+        };
+        if (min..=max).contains(&value) {
+            return Some(value);
+        }
+        match overflow {
+            Overflow::Wrap => Some((value - min).rem_euclid(1i128 << self.bits) + min),
+            Overflow::Sat => Some(value.clamp(min, max)),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BitfieldOperation {
+    Get(BitField),
+    Set(BitField, i64, Overflow),
+    IncrBy(BitField, i64, Overflow),
+}
+
+impl BitfieldOperation {
+    fn field(&self) -> BitField {
+        match *self {
+            BitfieldOperation::Get(field)
+            | BitfieldOperation::Set(field, _, _)
+            | BitfieldOperation::IncrBy(field, _, _) => field,
+        }
+    }
+}
+
+fn parse_bitfield(args: &[String], read_only: bool) -> Result<Vec<BitfieldOperation>, &'static [u8]> {
+    let (mut operations, mut overflow) = (Vec::new(), Overflow::Wrap);
+    let mut args = args.iter();
+    while let Some(subcommand) = args.next() {
+        let subcommand = subcommand.to_lowercase();
+        if read_only && subcommand != "get" {
+            return Err(b"err BITFIELD_RO only supports the GET subcommand\n");
+        }
+        if subcommand == "overflow" {
+            overflow = match args.next().map(|mode| mode.to_lowercase()).as_deref() {
+                Some("wrap") => Overflow::Wrap,
+                Some("sat") => Overflow::Sat,
+                Some("fail") => Overflow::Fail,
+                Some(_) => return Err(b"err Invalid OVERFLOW type specified\n"),
+                None => return Err(b"err syntax error\n"),
+            };
+            continue;
+        }
+        if !matches!(subcommand.as_str(), "get" | "set" | "incrby") {
+            return Err(b"err syntax error\n");
+        }
+        let (Some(field_type), Some(offset)) = (args.next(), args.next()) else {
+            return Err(b"err syntax error\n");
+        };
+        let field = parse_field(field_type, offset)?;
+        if subcommand == "get" {
+            operations.push(BitfieldOperation::Get(field));
+            continue;
+        }
+        let Some(value) = args.next() else { return Err(b"err syntax error\n"); };
+        let Ok(value) = value.parse::<i64>() else {
+            return Err(b"err value is not an integer or out of range\n");
+        };
+        operations.push(match subcommand.as_str() {
+            "set" => BitfieldOperation::Set(field, value, overflow),
+            _ => BitfieldOperation::IncrBy(field, value, overflow),
+        });
+    }
+    Ok(operations)
+}
+
+fn parse_field(field_type: &str, offset: &str) -> Result<BitField, &'static [u8]> {
+    let field_type = field_type.to_lowercase();
+    let signed = field_type.starts_with('i');
+    // u64 doesn't fit in the replies, they are signed
+    let max_bits = if signed { 64 } else { 63 };
+    let bits = match field_type.get(1..).map(str::parse::<u32>) {
+        Some(Ok(bits)) if (signed || field_type.starts_with('u')) && (1..=max_bits).contains(&bits) => bits,
+        _ => return Err(INVALID_BITFIELD_TYPE),
+    };
+    let offset = match offset.strip_prefix('#') {
+        Some(index) => index.parse::<u64>().ok().and_then(|index| index.checked_mul(bits as u64)),
+        None => offset.parse::<u64>().ok(),
+    };
+    match offset {
+        Some(offset) if offset + bits as u64 <= MAX_BIT_OFFSET => Ok(BitField { signed, bits, offset }),
+        _ => Err(INVALID_BIT_OFFSET),
+    }
+}
+
+async fn read_bitfield(context: &mut CommandContext, key: &str, operations: Vec<BitfieldOperation>) {
+    let inspection = Box::new(move |tlv: Option<&[u8]>| {
+        let value = match tlv {
+            Some(tlv) if !is_string(tlv) => return WRONGTYPE.to_vec(),
+            tlv => tlv.map_or(&[][..], string_value),
+        };
+        let lines = operations
+            .iter()
+            .map(|operation| operation.field().read(value).to_string())
+            .collect();
+        reply_lines(lines)
+    });
+    let reply = context.redis_service.inspect(context.session.db(), key, inspection).await;
+    context.reply(&reply);
+}
+
+fn parse_range(
+    context: &mut CommandContext,
+    start: &str,
+    end: &str,
+    unit: Option<&String>,
+) -> Option<(i64, i64, bool)> {
+    let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) else {
+        context.reply(b"err value is not an integer or out of range\n");
+        return None;
+    };
+    match unit.map(|unit| unit.to_lowercase()).as_deref() {
+        None | Some("byte") => Some((start, end, false)),
+        Some("bit") => Some((start, end, true)),
+        Some(_) => {
+            context.reply(b"err syntax error\n");
+            None
+        }
+    }
+}
+
+fn parse_bit_offset(context: &mut CommandContext, arg: &str) -> Option<u64> {
+    match arg.parse::<u64>() {
+        Ok(offset) if offset < MAX_BIT_OFFSET => Some(offset),
+        _ => {
+            context.reply(INVALID_BIT_OFFSET);
+            None
+        }
+    }
+}
+
+fn parse_bit(arg: &str) -> Option<u8> {
+    match arg {
+        "0" => Some(0),
+        "1" => Some(1),
+        _ => None,
+    }
+}
+
+fn string_value(tlv: &[u8]) -> &[u8] {
+    split_tlv(tlv).map_or(&[][..], |(_, value)| value)
+}
+
+fn get_bit(value: &[u8], position: u64) -> u8 {
+    let byte = value.get((position / 8) as usize).copied().unwrap_or(0);
+    (byte >> (7 - position % 8)) & 1
+}
+
+fn bit_range(length: usize, start: i64, end: i64, in_bits: bool) -> Option<(u64, u64)> {
+    let total = if in_bits { length as i64 * 8 } else { length as i64 };
+    let start = if start < 0 { total + start } else { start }.max(0);
+    let end = if end < 0 { total + end } else { end }.min(total - 1);
+    if start > end {
+        return None;
+    }
+    let (start, end) = (start as u64, end as u64);
+    Some(if in_bits { (start, end) } else { (start * 8, end * 8 + 7) })
+}
+
+fn count_bits(value: &[u8], first: u64, last: u64) -> u64 {
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+    let first_mask = 0xFFu8 >> (first % 8);
+    let last_mask = 0xFFu8 << (7 - last % 8);
+    if first_byte == last_byte {
+        return (value[first_byte] & first_mask & last_mask).count_ones() as u64;
+    }
+    let words = value[first_byte + 1..last_byte].chunks_exact(8);
+    let rest: u64 = words.remainder().iter().map(|byte| byte.count_ones() as u64).sum();
+    let middle: u64 = words
+        .map(|word| u64::from_ne_bytes(word.try_into().unwrap()).count_ones() as u64)
+        .sum();
+    let edges = (value[first_byte] & first_mask).count_ones() + (value[last_byte] & last_mask).count_ones();
+    edges as u64 + middle + rest
+}
+
+fn find_bit(value: &[u8], first: u64, last: u64, bit: u8) -> Option<u64> {
+    // the bytes without such a bit are skipped at once
+    let skipped = if bit == 1 { 0x00 } else { 0xFF };
+    let mut position = first;
+    while position <= last {
+        let byte = value[(position / 8) as usize];
+        if position.is_multiple_of(8) && position + 7 <= last && byte == skipped {
+            position += 8;
+            continue;
+        }
+        if (byte >> (7 - position % 8)) & 1 == bit {
+            return Some(position);
+        }
+        position += 1;
+    }
+    None
+}
+
+fn reply_lines(lines: Vec<String>) -> Vec<u8> {
+    if lines.is_empty() {
+        return b"empty\n".to_vec();
+    }
+    lines.into_iter().flat_map(|line| [line.into_bytes(), vec![b'\n']]).flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use mockall::predicate::{always, eq};
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::bitmap::{
+        bit_range, bitcount, bitfield, bitfield_ro, bitop, bitpos, count_bits, find_bit, getbit, parse_bitfield,
+        setbit, BitField, BitfieldOperation, Overflow, INVALID_BITFIELD_TYPE, INVALID_BIT_OFFSET,
+    };
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::redis::{MockRedisService, SetCondition, SetExpiry};
+    use crate::core::script::MockScriptService;
+    use crate::core::tlv::{to_tlv, TLVType};
+
+    fn string(value: &[u8]) -> Vec<u8> {
+        to_tlv(value.to_vec(), TLVType::String)
+    }
+
+    fn new_context(value: Arc<Mutex<Vec<u8>>>) -> CommandContext {
+        let mut redis_service = MockRedisService::new();
+        let updated = value.clone();
+        redis_service
+            .expect_update()
+            .with(eq(0), eq("key1"), always(), always())
+            .returning(move |_, _, _, update| update(&mut updated.lock().unwrap()).0);
+        redis_service
+            .expect_inspect()
+            .with(eq(0), eq("key1"), always())
+            .returning(move |_, _, inspection| {
+                let value = value.lock().unwrap();
+                inspection(if value.is_empty() { None } else { Some(&value) })
+            });
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[test]
+    fn bit_field_should_read_and_write() {
+        let mut value = vec![0u8; 2];
+        let field = BitField { signed: true, bits: 5, offset: 6 };
+
+        field.write(&mut value, -3);
+
+        assert_eq!(value, vec![0b0000_0011, 0b1010_0000]);
+        assert_eq!(field.read(&value), -3);
+        assert_eq!(BitField { signed: false, ..field }.read(&value), 29);
+        assert_eq!(field.bytes(), 0..2);
+        let field = BitField { signed: true, bits: 64, offset: 0 };
+        assert_eq!(field.read(&[0xFF; 8]), -1);
+        // the missing bytes are zeros
+        assert_eq!(BitField { signed: false, bits: 8, offset: 12 }.read(&value), 0);
+    }
+
+    #[test]
+    fn bit_field_should_handle_overflow() {
+        let signed = BitField { signed: true, bits: 8, offset: 0 };
+        let unsigned = BitField { signed: false, bits: 8, offset: 0 };
+
+        assert_eq!(signed.fit(127, Overflow::Fail), Some(127));
+        assert_eq!(signed.fit(128, Overflow::Wrap), Some(-128));
+        assert_eq!(signed.fit(-130, Overflow::Wrap), Some(126));
+        assert_eq!(signed.fit(200, Overflow::Sat), Some(127));
+        assert_eq!(signed.fit(-200, Overflow::Sat), Some(-128));
+        assert_eq!(signed.fit(128, Overflow::Fail), None);
+        assert_eq!(unsigned.fit(256, Overflow::Wrap), Some(0));
+        assert_eq!(unsigned.fit(-1, Overflow::Wrap), Some(255));
+        assert_eq!(unsigned.fit(-1, Overflow::Sat), Some(0));
+        assert_eq!(unsigned.fit(300, Overflow::Fail), None);
+    }
+
+    #[test]
+    fn bitfield_should_be_parsed() {
+        let field = |signed, bits, offset| BitField { signed, bits, offset };
+
+        let operations = parse_bitfield(&args("get u4 0 overflow sat set i8 #2 -5 incrby I64 1 1"), false);
+
+        assert_eq!(
+            operations,
+            Ok(vec![
+                BitfieldOperation::Get(field(false, 4, 0)),
+                BitfieldOperation::Set(field(true, 8, 16), -5, Overflow::Sat),
+                BitfieldOperation::IncrBy(field(true, 64, 1), 1, Overflow::Sat),
+            ])
+        );
+        assert_eq!(parse_bitfield(&args("get u64 0"), false), Err(INVALID_BITFIELD_TYPE));
+        assert_eq!(parse_bitfield(&args("get x8 0"), false), Err(INVALID_BITFIELD_TYPE));
+        assert_eq!(parse_bitfield(&args("get u8 4294967290"), false), Err(INVALID_BIT_OFFSET));
+        assert_eq!(parse_bitfield(&args("overflow up"), false), Err(&b"err Invalid OVERFLOW type specified\n"[..]));
+        assert_eq!(parse_bitfield(&args("set u8 0"), false), Err(&b"err syntax error\n"[..]));
+        assert_eq!(
+            parse_bitfield(&args("incrby u8 0 1"), true),
+            Err(&b"err BITFIELD_RO only supports the GET subcommand\n"[..])
+        );
+    }
+
+    #[test]
+    fn bits_should_be_counted_and_found() {
+        let value = [0xFFu8, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+        assert_eq!(bit_range(value.len(), 0, -1, false), Some((0, 87)));
+        assert_eq!(bit_range(value.len(), -2, -1, true), Some((86, 87)));
+        assert_eq!(bit_range(value.len(), 5, 2, false), None);
+        assert_eq!(bit_range(0, 0, -1, false), None);
+        assert_eq!(count_bits(&value, 0, 87), 13);
+        assert_eq!(count_bits(&value, 4, 11), 8);
+        assert_eq!(count_bits(&value, 3, 5), 3);
+        assert_eq!(find_bit(&value, 0, 87, 0), Some(12));
+        assert_eq!(find_bit(&value, 12, 87, 1), Some(87));
+        assert_eq!(find_bit(&value, 0, 7, 0), None);
+    }
+
+    #[tokio::test]
+    async fn setbit_and_getbit_should_be_handled() {
+        let value = Arc::new(Mutex::new(Vec::new()));
+        let mut context = new_context(value.clone());
+
+        setbit(&mut context, args("setbit key1 7 1")).await;
+        setbit(&mut context, args("setbit key1 7 0")).await;
+        setbit(&mut context, args("setbit key1 17 1")).await;
+        getbit(&mut context, args("getbit key1 17")).await;
+        getbit(&mut context, args("getbit key1 100")).await;
+        setbit(&mut context, args("setbit key1 1 2")).await;
+        setbit(&mut context, args("setbit key1 4294967296 1")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "0\n1\n0\n1\n0\n\
+            err bit is not an integer or out of range\n\
+            err bit offset is not an integer or out of range\n"
+        );
+        assert_eq!(*value.lock().unwrap(), string(&[0x00, 0x00, 0x40]));
+    }
+
+    #[tokio::test]
+    async fn bitcount_and_bitpos_should_be_handled() {
+        let value = Arc::new(Mutex::new(string(&[0xFF, 0xF0, 0x00])));
+        let mut context = new_context(value.clone());
+
+        bitcount(&mut context, args("bitcount key1")).await;
+        bitcount(&mut context, args("bitcount key1 1 1")).await;
+        bitcount(&mut context, args("bitcount key1 5 30 BIT")).await;
+        bitcount(&mut context, args("bitcount key1 1")).await;
+        bitpos(&mut context, args("bitpos key1 0")).await;
+        bitpos(&mut context, args("bitpos key1 1 2")).await;
+        bitpos(&mut context, args("bitpos key1 0 0 0")).await;
+        bitpos(&mut context, args("bitpos key1 2")).await;
+        *value.lock().unwrap() = string(&[0xFF]);
+        bitpos(&mut context, args("bitpos key1 0")).await;
+        value.lock().unwrap().clear();
+        bitpos(&mut context, args("bitpos key1 0")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "12\n4\n7\n\
+            err syntax error\n\
+            12\n-1\n-1\n\
+            err The bit argument must be 1 or 0.\n\
+            8\n0\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn bitfield_should_be_handled() {
+        let value = Arc::new(Mutex::new(Vec::new()));
+        let mut context = new_context(value.clone());
+
+        bitfield(&mut context, args("bitfield key1 get u8 0")).await;
+        bitfield(&mut context, args("bitfield key1 set u8 0 200 get i8 0 incrby u8 0 100")).await;
+        let overflows = "bitfield key1 overflow fail incrby u8 0 250 overflow sat incrby u8 0 250";
+        bitfield(&mut context, args(overflows)).await;
+        bitfield_ro(&mut context, args("bitfield_ro key1 get u4 #1")).await;
+        bitfield(&mut context, args("bitfield key1 overflow wrap")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "0\n0\n-56\n44\nnil\n255\n15\nempty\n"
+        );
+        assert_eq!(*value.lock().unwrap(), string(&[255]));
+    }
+
+    #[tokio::test]
+    async fn bitop_should_store_result() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key1"))
+            .returning(|_, _| Some(string(&[0b1100, 0xFF])));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key2"))
+            .returning(|_, _| Some(string(&[0b1010])));
+        redis_service.expect_get().with(eq(0), eq("key3")).returning(|_, _| None);
+        for result in [vec![0b1000, 0x00], vec![0b1110, 0xFF], vec![0b0110, 0xFF], vec![0b11110011, 0x00]] {
+            redis_service
+                .expect_set_all()
+                .with(
+                    eq(0),
+                    eq(vec![("dest".to_owned(), string(&result))]),
+                    eq(SetCondition::Always),
+                    eq(SetExpiry::Clear),
+                )
+                .once()
+                .returning(|_, _, _, _| Ok(Some(vec![None])));
+        }
+        redis_service.expect_delete().with(eq(0), eq("dest")).once().returning(|_, _| false);
+        let (mut context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+
+        bitop(&mut context, args("bitop and dest key1 key2")).await;
+        bitop(&mut context, args("bitop OR dest key1 key2 key3")).await;
+        bitop(&mut context, args("bitop xor dest key1 key2")).await;
+        bitop(&mut context, args("bitop not dest key1")).await;
+        bitop(&mut context, args("bitop not dest key1 key2")).await;
+        bitop(&mut context, args("bitop and dest key3")).await;
+        bitop(&mut context, args("bitop nand dest key1")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "2\n2\n2\n2\n\
+            err BITOP NOT must be called with a single source key.\n\
+            0\n\
+            err syntax error\n"
+        );
+    }
+}
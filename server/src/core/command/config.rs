@@ -0,0 +1,142 @@
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+
+pub fn get(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let mut lines: Vec<String> = Vec::new();
+        for pattern in args[2..].iter() {
+            for (name, value) in context.config_service.get(pattern.clone()) {
+                let line = format!("{} {}", name, value);
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+            }
+        }
+        context.reply_lines(&lines);
+    })
+}
+
+pub fn set(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if !args.len().is_multiple_of(2) {
+            context.reply(b"err wrong number of arguments for 'config|set' command\n");
+            return;
+        }
+        let directives = args[2..]
+            .chunks(2)
+            .map(|directive| (directive[0].clone(), directive[1].clone()))
+            .collect();
+        match context.config_service.set(directives) {
+            Ok(()) => context.reply(b"config ok\n"),
+            Err(err) => context.reply(format!("err CONFIG SET failed: {}\n", err).as_bytes()),
+        }
+    })
+}
+
+pub fn rewrite(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        match context.config_service.rewrite().await {
+            Ok(()) => context.reply(b"rewrite ok\n"),
+            Err(err) => context.reply(format!("err CONFIG REWRITE failed: {}\n", err).as_bytes()),
+        }
+    })
+}
+
+pub fn resetstat(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        context.config_service.reset_stats();
+        context.reply(b"resetstat ok\n");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+
+    use mockall::predicate::eq;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::config::{get, resetstat, rewrite, set};
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::config::service::MockConfigService;
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+
+    fn new_context(config_service: MockConfigService) -> CommandContext {
+        let (context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context.with_config_service(Arc::new(config_service))
+    }
+
+    #[tokio::test]
+    async fn get_should_be_handled() {
+        let mut config_service = MockConfigService::new();
+        config_service
+            .expect_get()
+            .with(eq("port".to_owned()))
+            .returning(|_| vec![("port".to_owned(), "6973".to_owned())]);
+        config_service
+            .expect_get()
+            .with(eq("xxx".to_owned()))
+            .returning(|_| Vec::new());
+        let mut context = new_context(config_service);
+
+        get(&mut context, args("config get port port")).await;
+        get(&mut context, args("config get xxx")).await;
+
+        assert_eq!(context.take_response(), b"port 6973\nempty\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn set_should_be_handled() {
+        let mut config_service = MockConfigService::new();
+        config_service
+            .expect_set()
+            .with(eq(vec![("maxclients".to_owned(), "10".to_owned())]))
+            .once()
+            .returning(|_| Ok(()));
+        config_service
+            .expect_set()
+            .with(eq(vec![("port".to_owned(), "1".to_owned())]))
+            .once()
+            .returning(|_| Err("'port' can't be changed while the server runs".to_owned()));
+        let mut context = new_context(config_service);
+
+        set(&mut context, args("config set maxclients 10")).await;
+        set(&mut context, args("config set port 1")).await;
+        set(&mut context, args("config set port 1 maxclients")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"config ok\n\
+            err CONFIG SET failed: 'port' can't be changed while the server runs\n\
+            err wrong number of arguments for 'config|set' command\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn rewrite_and_resetstat_should_be_handled() {
+        let mut config_service = MockConfigService::new();
+        config_service
+            .expect_rewrite()
+            .once()
+            .returning(|| Err(io::Error::other("the server is running without a config file")));
+        config_service.expect_reset_stats().once().returning(|| ());
+        let mut context = new_context(config_service);
+
+        rewrite(&mut context, args("config rewrite")).await;
+        resetstat(&mut context, args("config resetstat")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err CONFIG REWRITE failed: the server is running without a config file\n\
+            resetstat ok\n"
+                .to_vec()
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+use crate::core::session::DEFAULT_USER;
+
+const PROTOCOL_VERSION: &str = "1";
+
+pub fn ping(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if args.len() == 1 {
+            context.reply(b"pong\n");
+        } else {
+            context.reply_line(args[1..].join(" ").as_bytes());
+        }
+    })
+}
+
+pub fn auth(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let (user, password) = match &args[1..] {
+            [password] => {
+                if !context.auth_service.requires_auth() {
+                    context.reply(b"err AUTH called without any password configured for the default user\n");
+                    return;
+                }
+                (DEFAULT_USER, password)
+            }
+            [user, password] => (user.as_str(), password),
+            _ => {
+                context.reply(b"err syntax error\n");
+                return;
+            }
+        };
+        if authenticate(context, user, password) {
+            context.reply(b"auth ok\n");
+        }
+    })
+}
+
+pub fn hello(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if args.get(1).is_some_and(|protover| protover != PROTOCOL_VERSION) {
+            context.reply(b"err NOPROTO unsupported protocol version\n");
+            return;
+        }
+        match args.get(2..).unwrap_or_default() {
+            [] => {}
+            [option, user, password] if option.eq_ignore_ascii_case("auth") => {
+                if !authenticate(context, user, password) {
+                    return;
+                }
+            }
+            _ => {
+                context.reply(b"err syntax error\n");
+                return;
+            }
+        }
+        if !context.session.is_authenticated() && context.auth_service.requires_auth() {
+            context.reply(b"err NOAUTH HELLO must be called with the client already authenticated\n");
+            return;
+        }
+        context.reply_lines(&[
+            "server mini-redis".to_owned(),
+            format!("version {}", env!("CARGO_PKG_VERSION")),
+            format!("proto {}", PROTOCOL_VERSION),
+            format!("user {}", context.session.user()),
+        ]);
+    })
+}
+
+fn authenticate(context: &mut CommandContext, user: &str, password: &str) -> bool {
+    if !context.auth_service.authenticate(context.address, user, password) {
+        log::warn!("[{}] failed to authenticate as '{}'", context.address, user);
+        context.reply(b"err WRONGPASS invalid username-password pair\n");
+        return false;
+    }
+    context.session.authenticate(user.to_owned());
+    true
+}
+
+pub fn client_info(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let mut lines = vec![
+            format!("addr {}", context.address),
+            format!("user {}", context.session.user()),
+            format!("db {}", context.session.db()),
+        ];
+        if let Some(identity) = context.session.identity() {
+            lines.push(format!("cert-subject {}", identity.subject));
+        }
+        context.reply_lines(&lines);
+    })
+}
+
+pub fn info(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let metrics = context.broker_service.metrics().await;
+        context.reply_lines(&[
+            format!("failed_auth_attempts {}", context.auth_service.failed_attempts()),
+            format!("dropped_messages {}", metrics.dropped_messages),
+            format!("disconnected_subscribers {}", metrics.disconnected_subscribers),
+        ]);
+    })
+}
+
+pub fn command(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let infos: Vec<String> = context
+            .registry
+            .commands()
+            .iter()
+            .map(|command| command.info())
+            .collect();
+        context.reply_lines(&infos);
+    })
+}
+
+pub fn command_info(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        for name in args[2..].iter() {
+            match context.registry.command_by_name(name) {
+                Some(command) => context.reply_line(command.info().as_bytes()),
+                None => context.reply(b"nil\n"),
+            }
+        }
+    })
+}
+
+pub fn command_count(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let count = context.registry.commands().len();
+        context.reply_line(count.to_string().as_bytes());
+    })
+}
+
+pub fn module_list(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let modules = context.registry.modules().to_vec();
+        context.reply_lines(&modules);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::core::auth::MyAuthService;
+    use crate::core::broker::{BrokerMetrics, MockBrokerService};
+    use crate::core::command::connection::{
+        auth, client_info, command_count, command_info, hello, info, ping,
+    };
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+    use crate::core::tls::ClientIdentity;
+
+    fn new_context() -> CommandContext {
+        let (context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn ping_should_be_handled() {
+        let mut context = new_context();
+
+        ping(&mut context, args("ping")).await;
+        ping(&mut context, args("ping hello world")).await;
+
+        assert_eq!(context.take_response(), b"pong\nhello world\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn auth_should_authenticate_client() {
+        let mut context = new_context()
+            .with_auth_service(Arc::new(MyAuthService::new().with_requirepass("s3cret")));
+
+        auth(&mut context, args("auth wrong")).await;
+        auth(&mut context, args("auth alice s3cret")).await;
+        assert!(!context.session.is_authenticated());
+        auth(&mut context, args("auth default s3cret extra")).await;
+        auth(&mut context, args("auth s3cret")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err WRONGPASS invalid username-password pair\n\
+            err WRONGPASS invalid username-password pair\n\
+            err syntax error\n\
+            auth ok\n"
+                .to_vec()
+        );
+        assert!(context.session.is_authenticated());
+        assert_eq!(context.session.user(), "default");
+        assert_eq!(context.auth_service.failed_attempts(), 2);
+    }
+
+    #[tokio::test]
+    async fn auth_without_password_should_be_refused() {
+        let mut context = new_context();
+
+        auth(&mut context, args("auth s3cret")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err AUTH called without any password configured for the default user\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn hello_should_be_handled() {
+        let mut context = new_context()
+            .with_auth_service(Arc::new(MyAuthService::new().with_requirepass("s3cret")));
+
+        hello(&mut context, args("hello 3")).await;
+        hello(&mut context, args("hello")).await;
+        hello(&mut context, args("hello 1 auth default wrong")).await;
+        hello(&mut context, args("hello 1 setname x")).await;
+        hello(&mut context, args("hello 1 AUTH default s3cret")).await;
+
+        let response = format!(
+            "err NOPROTO unsupported protocol version\n\
+            err NOAUTH HELLO must be called with the client already authenticated\n\
+            err WRONGPASS invalid username-password pair\n\
+            err syntax error\n\
+            server mini-redis\nversion {}\nproto 1\nuser default\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(context.take_response(), response.into_bytes());
+        assert!(context.session.is_authenticated());
+    }
+
+    #[tokio::test]
+    async fn client_info_should_be_handled() {
+        let mut context = new_context();
+
+        client_info(&mut context, args("client info")).await;
+        context.session.set_identity(Some(ClientIdentity {
+            subject: "CN=alice".to_owned(),
+            common_name: Some("alice".to_owned()),
+        }));
+        context.session.set_user("alice".to_owned());
+        client_info(&mut context, args("client info")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"addr 127.0.0.1:1111\nuser default\ndb 0\n\
+            addr 127.0.0.1:1111\nuser alice\ndb 0\ncert-subject CN=alice\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn info_should_report_statistics() {
+        let mut broker_service = MockBrokerService::new();
+        broker_service.expect_metrics().once().returning(|| BrokerMetrics {
+            dropped_messages: 3,
+            disconnected_subscribers: 1,
+        });
+        let (context, _) = new_test_context(
+            MockRedisService::new(),
+            broker_service,
+            MockScriptService::new(),
+        );
+        let mut context = context.with_auth_service(Arc::new(MyAuthService::new().with_requirepass("s3cret")));
+
+        auth(&mut context, args("auth wrong")).await;
+        context.take_response();
+        info(&mut context, args("info")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"failed_auth_attempts 1\ndropped_messages 3\ndisconnected_subscribers 1\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn command_info_should_be_handled() {
+        let mut context = new_context();
+
+        command_info(&mut context, args("command info get script|kill xxx")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"get 2 readonly 1 1 1\nscript|kill 2 noscript,allow-busy 0 0 0\nnil\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn command_count_should_be_handled() {
+        let mut context = new_context();
+        let count = context.registry.commands().len();
+
+        command_count(&mut context, args("command count")).await;
+
+        assert_eq!(context.take_response(), format!("{}\n", count).into_bytes());
+    }
+}
@@ -0,0 +1,138 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::core::auth::{AuthService, MyAuthService};
+use crate::core::broker::BrokerService;
+use crate::core::buffer::OutputBufferSender;
+use crate::core::command::registry::CommandRegistry;
+use crate::core::config::service::{ConfigService, MyConfigService};
+use crate::core::config::Config;
+use crate::core::redis::RedisService;
+use crate::core::script::ScriptService;
+use crate::core::session::Session;
+use crate::core::tls::TlsService;
+
+pub struct CommandContext {
+    pub redis_service: Arc<dyn RedisService>,
+    pub broker_service: Arc<dyn BrokerService>,
+    pub script_service: Arc<dyn ScriptService>,
+    pub registry: Arc<CommandRegistry>,
+    pub config_service: Arc<dyn ConfigService>,
+    pub auth_service: Arc<dyn AuthService>,
+    pub tls_service: Option<Arc<dyn TlsService>>,
+    pub sender: OutputBufferSender,
+    pub address: SocketAddr,
+    pub session: Session,
+    response: Vec<u8>,
+}
+
+impl CommandContext {
+    pub fn new(
+        redis_service: Arc<dyn RedisService>,
+        broker_service: Arc<dyn BrokerService>,
+        script_service: Arc<dyn ScriptService>,
+        registry: Arc<CommandRegistry>,
+        sender: OutputBufferSender,
+        address: SocketAddr,
+    ) -> Self {
+        Self {
+            redis_service,
+            broker_service,
+            script_service,
+            registry,
+            config_service: Arc::new(MyConfigService::new(Config::default())),
+            auth_service: Arc::new(MyAuthService::new()),
+            tls_service: None,
+            sender,
+            address,
+            session: Session::new(),
+            response: Vec::new(),
+        }
+    }
+
+    pub fn with_config_service(mut self, config_service: Arc<dyn ConfigService>) -> Self {
+        self.config_service = config_service;
+        self
+    }
+
+    pub fn with_auth_service(mut self, auth_service: Arc<dyn AuthService>) -> Self {
+        self.auth_service = auth_service;
+        self
+    }
+
+    pub fn with_tls_service(mut self, tls_service: Option<Arc<dyn TlsService>>) -> Self {
+        self.tls_service = tls_service;
+        self
+    }
+
+    /// Returns a context of the same client with a new session, e.g. for the commands called by a script.
+    /// The client keeps its identity, user and authentication.
+    pub fn fork(&self) -> Self {
+        let mut context = Self::new(
+            self.redis_service.clone(),
+            self.broker_service.clone(),
+            self.script_service.clone(),
+            self.registry.clone(),
+            self.sender.clone(),
+            self.address,
+        )
+        .with_config_service(self.config_service.clone())
+        .with_auth_service(self.auth_service.clone())
+        .with_tls_service(self.tls_service.clone());
+        context.session.set_identity(self.session.identity().cloned());
+        context.session.select(self.session.db());
+        if self.session.is_authenticated() {
+            context.session.authenticate(self.session.user().to_owned());
+        } else {
+            context.session.set_user(self.session.user().to_owned());
+        }
+        context
+    }
+
+    pub fn reply(&mut self, response: &[u8]) {
+        self.response.extend_from_slice(response);
+    }
+
+    pub fn reply_line(&mut self, value: &[u8]) {
+        self.response.extend_from_slice(value);
+        self.response.push(b'\n');
+    }
+
+    pub fn reply_lines<T: AsRef<[u8]>>(&mut self, items: &[T]) {
+        if items.is_empty() {
+            self.reply(b"empty\n");
+        }
+        for item in items.iter() {
+            self.reply_line(item.as_ref());
+        }
+    }
+
+    pub fn take_response(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.response)
+    }
+}
+
+#[cfg(test)]
+pub fn new_test_context(
+    redis_service: crate::core::redis::MockRedisService,
+    broker_service: crate::core::broker::MockBrokerService,
+    script_service: crate::core::script::MockScriptService,
+) -> (CommandContext, crate::core::buffer::OutputBufferReceiver) {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let (sender, receiver) = crate::core::buffer::output_buffer();
+    let context = CommandContext::new(
+        Arc::new(redis_service),
+        Arc::new(broker_service),
+        Arc::new(script_service),
+        Arc::new(CommandRegistry::new().with_builtin_commands()),
+        sender,
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111),
+    );
+    (context, receiver)
+}
+
+#[cfg(test)]
+pub fn args(command: &str) -> Vec<String> {
+    command.split(' ').map(str::to_owned).collect()
+}
@@ -0,0 +1,186 @@
+use std::io;
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+
+pub fn select(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(db) = parse_db(context, &args[1]) else { return; };
+        context.session.select(db);
+        context.reply(b"select ok\n");
+    })
+}
+
+pub fn swapdb(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(db1) = parse_db(context, &args[1]) else { return; };
+        let Some(db2) = parse_db(context, &args[2]) else { return; };
+        let result = context.redis_service.swap(db1, db2).await;
+        reply_result(context, result, b"swapdb ok\n");
+    })
+}
+
+pub fn move_key(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(to) = parse_db(context, &args[2]) else { return; };
+        let from = context.session.db();
+        if from == to {
+            context.reply(b"err source and destination objects are the same\n");
+            return;
+        }
+        match context.redis_service.move_key(&args[1], from, to).await {
+            Ok(moved) => context.reply(if moved { b"1\n" } else { b"0\n" }),
+            Err(err) => {
+                // the key is moved in memory anyway
+                log::error!("error during moving cache: {}", err);
+                context.reply(b"1\n");
+            }
+        }
+    })
+}
+
+pub fn flushdb(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let result = context.redis_service.flush(context.session.db()).await;
+        reply_result(context, result, b"flushdb ok\n");
+    })
+}
+
+pub fn flushall(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let mut result = Ok(());
+        for db in 0..context.redis_service.databases() {
+            result = result.and(context.redis_service.flush(db).await);
+        }
+        reply_result(context, result, b"flushall ok\n");
+    })
+}
+
+fn parse_db(context: &mut CommandContext, arg: &str) -> Option<usize> {
+    let Ok(db) = arg.parse::<i64>() else {
+        context.reply(b"err value is not an integer or out of range\n");
+        return None;
+    };
+    match usize::try_from(db) {
+        Ok(db) if db < context.redis_service.databases() => Some(db),
+        _ => {
+            context.reply(b"err DB index is out of range\n");
+            None
+        }
+    }
+}
+
+fn reply_result(context: &mut CommandContext, result: io::Result<()>, ok: &[u8]) {
+    match result {
+        Ok(()) => context.reply(ok),
+        Err(err) => {
+            log::error!("error during updating cache: {}", err);
+            context.reply(format!("err the cache could not be updated: {}\n", err).as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+
+    use mockall::predicate::eq;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::command::db::{flushall, flushdb, move_key, select, swapdb};
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+
+    fn new_context(mut redis_service: MockRedisService) -> CommandContext {
+        redis_service.expect_databases().returning(|| 4);
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn select_should_be_handled() {
+        let mut context = new_context(MockRedisService::new());
+
+        select(&mut context, args("select 3")).await;
+        select(&mut context, args("select 4")).await;
+        select(&mut context, args("select -1")).await;
+        select(&mut context, args("select x")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"select ok\n\
+            err DB index is out of range\n\
+            err DB index is out of range\n\
+            err value is not an integer or out of range\n"
+                .to_vec()
+        );
+        assert_eq!(context.session.db(), 3);
+    }
+
+    #[tokio::test]
+    async fn move_key_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_move_key()
+            .with(eq("key1"), eq(1), eq(2))
+            .once()
+            .returning(|_, _, _| Ok(true));
+        redis_service
+            .expect_move_key()
+            .with(eq("key2"), eq(1), eq(2))
+            .once()
+            .returning(|_, _, _| Ok(false));
+        let mut context = new_context(redis_service);
+        context.session.select(1);
+
+        move_key(&mut context, args("move key1 2")).await;
+        move_key(&mut context, args("move key2 2")).await;
+        move_key(&mut context, args("move key1 1")).await;
+        move_key(&mut context, args("move key1 9")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"1\n0\n\
+            err source and destination objects are the same\n\
+            err DB index is out of range\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn swapdb_and_flush_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_swap()
+            .with(eq(0), eq(3))
+            .once()
+            .returning(|_, _| Ok(()));
+        redis_service
+            .expect_flush()
+            .with(eq(2))
+            .once()
+            .returning(|_| Err(Error::other("disk full")));
+        redis_service.expect_flush().times(4).returning(|_| Ok(()));
+        let mut context = new_context(redis_service);
+
+        swapdb(&mut context, args("swapdb 0 3")).await;
+        swapdb(&mut context, args("swapdb 0 4")).await;
+        context.session.select(2);
+        flushdb(&mut context, args("flushdb")).await;
+        flushall(&mut context, args("flushall")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"swapdb ok\n\
+            err DB index is out of range\n\
+            err the cache could not be updated: disk full\n\
+            flushall ok\n"
+                .to_vec()
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+use crate::core::redis::ScanFilter;
+use crate::core::tlv::TLVType;
+
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+pub fn del(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let deleted = context.redis_service.delete(context.session.db(), &args[1]).await;
+        context.reply(if deleted { b"1\n" } else { b"0\n" });
+    })
+}
+
+pub fn expire(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Ok(seconds) = args[2].parse::<u64>() else {
+            context.reply(b"err value is not an integer or out of range\n");
+            return;
+        };
+        let expired = context
+            .redis_service
+            .expire(context.session.db(), &args[1], Duration::from_secs(seconds))
+            .await;
+        context.reply(if expired { b"1\n" } else { b"0\n" });
+    })
+}
+
+pub fn keys(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let filter = ScanFilter {
+            pattern: Some(args[1].clone()),
+            tlv_types: None,
+        };
+        let (_, keys) = context
+            .redis_service
+            .scan(context.session.db(), 0, usize::MAX, filter)
+            .await;
+        context.reply_lines(&keys);
+    })
+}
+
+pub fn scan(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Ok(cursor) = args[1].parse::<u64>() else {
+            context.reply(b"err invalid cursor\n");
+            return;
+        };
+        let mut count = DEFAULT_SCAN_COUNT;
+        let mut filter = ScanFilter::default();
+        for option in args[2..].chunks(2) {
+            let [name, value] = option else {
+                context.reply(b"err syntax error\n");
+                return;
+            };
+            match name.to_lowercase().as_str() {
+                "match" => filter.pattern = Some(value.clone()),
+                "count" => match value.parse::<usize>() {
+                    Ok(value) if value > 0 => count = value,
+                    _ => {
+                        context.reply(b"err value is not an integer or out of range\n");
+                        return;
+                    }
+                },
+                "type" => {
+                    let Some(tlv_types) = tlv_types(context, value) else {
+                        context.reply(format!("err unknown type name '{}'\n", value).as_bytes());
+                        return;
+                    };
+                    filter.tlv_types = Some(tlv_types);
+                }
+                _ => {
+                    context.reply(b"err syntax error\n");
+                    return;
+                }
+            }
+        }
+        let (cursor, keys) = context
+            .redis_service
+            .scan(context.session.db(), cursor, count, filter)
+            .await;
+        let mut lines = vec![cursor.to_string()];
+        lines.extend(keys);
+        context.reply_lines(&lines);
+    })
+}
+
+fn tlv_types(context: &CommandContext, name: &str) -> Option<Vec<u8>> {
+    if name.eq_ignore_ascii_case("string") {
+        return Some(vec![TLVType::String as u8, TLVType::Int as u8]);
+    }
+    let value_type = context.registry.value_type_by_name(name)?;
+    Some(vec![value_type.tlv_type])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mockall::predicate::eq;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::command::keys::{del, expire, keys, scan};
+    use crate::core::redis::{MockRedisService, ScanFilter};
+    use crate::core::script::MockScriptService;
+
+    fn new_context(redis_service: MockRedisService) -> CommandContext {
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn del_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_delete()
+            .with(eq(0), eq("key1"))
+            .once()
+            .returning(|_, _| true);
+        let mut context = new_context(redis_service);
+
+        del(&mut context, args("del key1")).await;
+
+        assert_eq!(context.take_response(), b"1\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn del_should_be_handled_when_not_found() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_delete()
+            .with(eq(0), eq("key1"))
+            .once()
+            .returning(|_, _| false);
+        let mut context = new_context(redis_service);
+
+        del(&mut context, args("del key1")).await;
+
+        assert_eq!(context.take_response(), b"0\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn expire_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_expire()
+            .with(eq(0), eq("key1"), eq(Duration::from_secs(10)))
+            .once()
+            .returning(|_, _, _| true);
+        let mut context = new_context(redis_service);
+
+        expire(&mut context, args("expire key1 10")).await;
+        expire(&mut context, args("expire key1 ten")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"1\nerr value is not an integer or out of range\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn keys_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_scan()
+            .with(
+                eq(0),
+                eq(0),
+                eq(usize::MAX),
+                eq(ScanFilter {
+                    pattern: Some("user*".to_owned()),
+                    tlv_types: None,
+                }),
+            )
+            .once()
+            .returning(|_, _, _, _| (0, vec!["user1".to_owned(), "user2".to_owned()]));
+        let mut context = new_context(redis_service);
+
+        keys(&mut context, args("keys user*")).await;
+
+        assert_eq!(context.take_response(), b"user1\nuser2\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn scan_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_scan()
+            .with(eq(0), eq(0), eq(10), eq(ScanFilter::default()))
+            .once()
+            .returning(|_, _, _, _| (42, vec!["a".to_owned()]));
+        redis_service
+            .expect_scan()
+            .with(
+                eq(0),
+                eq(42),
+                eq(100),
+                eq(ScanFilter {
+                    pattern: Some("a*".to_owned()),
+                    tlv_types: Some(vec![1, 2]),
+                }),
+            )
+            .once()
+            .returning(|_, _, _, _| (0, Vec::new()));
+        let mut context = new_context(redis_service);
+
+        scan(&mut context, args("scan 0")).await;
+        scan(&mut context, args("scan 42 MATCH a* COUNT 100 TYPE string")).await;
+        scan(&mut context, args("scan x")).await;
+        scan(&mut context, args("scan 0 count 0")).await;
+        scan(&mut context, args("scan 0 type list")).await;
+        scan(&mut context, args("scan 0 match")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"42\na\n\
+            0\n\
+            err invalid cursor\n\
+            err value is not an integer or out of range\n\
+            err unknown type name 'list'\n\
+            err syntax error\n"
+                .to_vec()
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::core::command::registry::{Command, CommandFlags};
+
+pub mod acl;
+pub mod bitmap;
+pub mod config;
+pub mod connection;
+pub mod context;
+pub mod db;
+pub mod keys;
+pub mod pubsub;
+pub mod registry;
+pub mod scripting;
+pub mod string;
+pub mod tls;
+pub mod transaction;
+
+pub fn builtin_commands() -> Vec<Command> {
+    use CommandFlags as F;
+
+    vec![
+        Command::new("ping", -1, F::NO_AUTH, connection::ping),
+        Command::new("auth", -2, F::NOSCRIPT | F::NO_AUTH, connection::auth),
+        Command::new("hello", -1, F::NOSCRIPT | F::NO_AUTH, connection::hello),
+        Command::new("client|info", 2, F::NONE, connection::client_info),
+        Command::new("info", 1, F::NONE, connection::info),
+        Command::new("command", 1, F::NONE, connection::command),
+        Command::new("command|info", -2, F::NONE, connection::command_info),
+        Command::new("command|count", 2, F::NONE, connection::command_count),
+        Command::new("module|list", 2, F::ADMIN, connection::module_list),
+        Command::new("config|get", -3, F::ADMIN | F::NOSCRIPT, config::get),
+        Command::new("config|set", -4, F::ADMIN | F::NOSCRIPT, config::set),
+        Command::new("config|rewrite", 2, F::ADMIN | F::NOSCRIPT, config::rewrite),
+        Command::new("config|resetstat", 2, F::ADMIN | F::NOSCRIPT, config::resetstat),
+        Command::new("acl|setuser", -3, F::ADMIN | F::NOSCRIPT, acl::setuser),
+        Command::new("acl|getuser", 3, F::ADMIN | F::NOSCRIPT, acl::getuser),
+        Command::new("acl|deluser", -3, F::ADMIN | F::NOSCRIPT, acl::deluser),
+        Command::new("acl|list", 2, F::ADMIN | F::NOSCRIPT, acl::list),
+        Command::new("acl|whoami", 2, F::NOSCRIPT, acl::whoami),
+        Command::new("acl|log", -2, F::ADMIN | F::NOSCRIPT, acl::log),
+        Command::new("tls|reload", 2, F::ADMIN | F::NOSCRIPT, tls::reload),
+        Command::new("select", 2, F::NONE, db::select),
+        Command::new("swapdb", 3, F::WRITE | F::EXCLUSIVE, db::swapdb),
+        Command::new("flushdb", 1, F::WRITE | F::EXCLUSIVE, db::flushdb),
+        Command::new("flushall", 1, F::WRITE | F::EXCLUSIVE, db::flushall),
+        Command::new("move", 3, F::WRITE, db::move_key).with_keys(1, 1, 1),
+        Command::new("get", 2, F::READONLY, string::get).with_keys(1, 1, 1),
+        Command::new("set", -3, F::WRITE | F::DENYOOM, string::set).with_keys(1, 1, 1),
+        Command::new("mset", -3, F::WRITE | F::DENYOOM, string::mset).with_keys(1, -1, 2),
+        Command::new("msetnx", -3, F::WRITE | F::DENYOOM, string::msetnx).with_keys(1, -1, 2),
+        Command::new("mget", -2, F::READONLY, string::mget).with_keys(1, -1, 1),
+        Command::new("getset", 3, F::WRITE | F::DENYOOM, string::getset).with_keys(1, 1, 1),
+        Command::new("getdel", 2, F::WRITE, string::getdel).with_keys(1, 1, 1),
+        Command::new("getex", -2, F::WRITE, string::getex).with_keys(1, 1, 1),
+        Command::new("append", 3, F::WRITE | F::DENYOOM, string::append).with_keys(1, 1, 1),
+        Command::new("strlen", 2, F::READONLY, string::strlen).with_keys(1, 1, 1),
+        Command::new("getrange", 4, F::READONLY, string::getrange).with_keys(1, 1, 1),
+        Command::new("setrange", 4, F::WRITE | F::DENYOOM, string::setrange).with_keys(1, 1, 1),
+        Command::new("lcs", -3, F::READONLY, string::lcs).with_keys(1, 2, 1),
+        Command::new("setbit", 4, F::WRITE | F::DENYOOM, bitmap::setbit).with_keys(1, 1, 1),
+        Command::new("getbit", 3, F::READONLY, bitmap::getbit).with_keys(1, 1, 1),
+        Command::new("bitcount", -2, F::READONLY, bitmap::bitcount).with_keys(1, 1, 1),
+        Command::new("bitpos", -3, F::READONLY, bitmap::bitpos).with_keys(1, 1, 1),
+        Command::new("bitop", -4, F::WRITE | F::DENYOOM, bitmap::bitop).with_keys(2, -1, 1),
+        Command::new("bitfield", -2, F::WRITE | F::DENYOOM, bitmap::bitfield).with_keys(1, 1, 1),
+        Command::new("bitfield_ro", -2, F::READONLY, bitmap::bitfield_ro).with_keys(1, 1, 1),
+        Command::new("del", 2, F::WRITE, keys::del).with_keys(1, 1, 1),
+        Command::new("expire", 3, F::WRITE, keys::expire).with_keys(1, 1, 1),
+        Command::new("keys", 2, F::READONLY, keys::keys),
+        Command::new("scan", -2, F::READONLY, keys::scan),
+        Command::new("subscribe", -2, F::PUBSUB | F::NOSCRIPT | F::NOMULTI, pubsub::subscribe)
+            .with_channels(1, 1, 1),
+        Command::new("durable", 4, F::PUBSUB | F::WRITE, pubsub::durable).with_channels(1, 1, 1),
+        Command::new("pubsub|channels", -2, F::PUBSUB, pubsub::channels),
+        Command::new("pubsub|numsub", -2, F::PUBSUB, pubsub::numsub),
+        Command::new("pubsub|numpat", 2, F::PUBSUB, pubsub::numpat),
+        Command::new("multi", 1, F::TRANSACTION | F::NOSCRIPT, transaction::multi),
+        Command::new("exec", 1, F::TRANSACTION | F::EXCLUSIVE | F::NOSCRIPT, transaction::exec),
+        Command::new("discard", 1, F::TRANSACTION | F::NOSCRIPT, transaction::discard),
+        Command::new("watch", -2, F::NOMULTI | F::NOSCRIPT, transaction::watch).with_keys(1, -1, 1),
+        Command::new("unwatch", 1, F::NOSCRIPT, transaction::unwatch),
+        Command::new("eval", -3, F::EXCLUSIVE | F::NOSCRIPT, scripting::eval),
+        Command::new("evalsha", -3, F::EXCLUSIVE | F::NOSCRIPT, scripting::evalsha),
+        Command::new("script|load", 3, F::NOSCRIPT, scripting::load),
+        Command::new("script|kill", 2, F::NOSCRIPT | F::ALLOW_BUSY, scripting::kill),
+    ]
+}
@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+use crate::core::history::RetentionPolicy;
+
+pub fn subscribe(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let topic = args[1].clone();
+        if !is_valid_topic(&topic) {
+            context.reply(format!("err invalid topic '{}'\n", topic).as_bytes());
+            return;
+        }
+        let sender = context.sender.clone();
+        match &args[2..] {
+            [] => {
+                context
+                    .broker_service
+                    .subscribe(context.address, sender, topic)
+                    .await;
+                context.reply(b"subscribed ok\n");
+            }
+            [from, offset] if from.eq_ignore_ascii_case("from") => {
+                let Ok(offset) = offset.parse::<u64>() else {
+                    context.reply(b"err value is not an integer or out of range\n");
+                    return;
+                };
+                // the acknowledgement goes through the output buffer so that it precedes the replayed messages
+                let _ = sender.send(b"subscribed ok\n".to_vec());
+                context
+                    .broker_service
+                    .subscribe_from(context.address, sender, topic, offset)
+                    .await;
+            }
+            _ => context.reply(b"err syntax error\n"),
+        }
+    })
+}
+
+pub fn durable(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let topic = args[1].clone();
+        if !is_valid_topic(&topic) {
+            context.reply(format!("err invalid topic '{}'\n", topic).as_bytes());
+            return;
+        }
+        let Ok(value) = args[3].parse::<u64>() else {
+            context.reply(b"err value is not an integer or out of range\n");
+            return;
+        };
+        let policy = if args[2].eq_ignore_ascii_case("maxlen") {
+            RetentionPolicy::MaxLen(value)
+        } else if args[2].eq_ignore_ascii_case("maxage") {
+            RetentionPolicy::MaxAge(Duration::from_secs(value))
+        } else {
+            context.reply(b"err syntax error\n");
+            return;
+        };
+
+        match context.broker_service.make_durable(topic, policy).await {
+            Ok(_) => context.reply(b"durable ok\n"),
+            Err(err) => {
+                context.reply(format!("err the topic history could not be written: {}\n", err).as_bytes());
+            }
+        }
+    })
+}
+
+pub fn channels(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if args.len() > 3 {
+            context.reply(b"err syntax error\n");
+            return;
+        }
+        let pattern = args.get(2).cloned();
+        let channels = context.broker_service.channels(pattern).await;
+        context.reply_lines(&channels);
+    })
+}
+
+pub fn numsub(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let channels = args[2..].to_vec();
+        let num_subscribers = context.broker_service.num_subscribers(channels).await;
+        let lines: Vec<String> = num_subscribers
+            .iter()
+            .map(|(channel, count)| format!("{} {}", channel, count))
+            .collect();
+        context.reply_lines(&lines);
+    })
+}
+
+pub fn numpat(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let num_patterns = context.broker_service.num_patterns().await;
+        context.reply_line(num_patterns.to_string().as_bytes());
+    })
+}
+
+/// Topics also allow the characters of the keyspace notification channels, e.g. `__keyspace@0__:key`.
+fn is_valid_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && topic
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '@' | ':' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::eq;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context};
+    use crate::core::command::pubsub::{channels, durable, numpat, numsub, subscribe};
+    use crate::core::history::RetentionPolicy;
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+
+    #[tokio::test]
+    async fn subscribe_should_be_handled() {
+        let mut broker_service = MockBrokerService::new();
+        broker_service
+            .expect_subscribe()
+            .withf(|_, _, topic| topic == "__keyspace@0__:a")
+            .once()
+            .returning(|_, _, _| ());
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            broker_service,
+            MockScriptService::new(),
+        );
+
+        subscribe(&mut context, args("subscribe __keyspace@0__:a")).await;
+        subscribe(&mut context, args("subscribe a/b")).await;
+        subscribe(&mut context, args("subscribe a to 1")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"subscribed ok\nerr invalid topic 'a/b'\nerr syntax error\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribe_from_should_be_handled() {
+        let mut broker_service = MockBrokerService::new();
+        broker_service
+            .expect_subscribe_from()
+            .withf(|_, _, topic, offset| topic == "t1" && *offset == 3)
+            .once()
+            .returning(|_, _, _, _| ());
+        let (mut context, mut receiver) = new_test_context(
+            MockRedisService::new(),
+            broker_service,
+            MockScriptService::new(),
+        );
+
+        subscribe(&mut context, args("subscribe t1 FROM 3")).await;
+
+        assert_eq!(receiver.recv().await, Some(b"subscribed ok\n".to_vec()));
+        assert!(context.take_response().is_empty());
+    }
+
+    #[tokio::test]
+    async fn durable_should_be_handled() {
+        let mut broker_service = MockBrokerService::new();
+        broker_service
+            .expect_make_durable()
+            .with(eq("t1".to_owned()), eq(RetentionPolicy::MaxLen(10)))
+            .once()
+            .returning(|_, _| Ok(()));
+        broker_service
+            .expect_make_durable()
+            .with(eq("t2".to_owned()), eq(RetentionPolicy::MaxLen(10)))
+            .once()
+            .returning(|_, _| Err(std::io::Error::other("disk full")));
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            broker_service,
+            MockScriptService::new(),
+        );
+
+        durable(&mut context, args("durable t1 maxlen 10")).await;
+        durable(&mut context, args("durable t1 maxsize 10")).await;
+        durable(&mut context, args("durable t2 maxlen 10")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"durable ok\nerr syntax error\nerr the topic history could not be written: disk full\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn pubsub_should_be_handled() {
+        let mut broker_service = MockBrokerService::new();
+        broker_service
+            .expect_channels()
+            .with(eq(Some("t*".to_owned())))
+            .once()
+            .returning(|_| vec!["t1".to_owned(), "t2".to_owned()]);
+        broker_service
+            .expect_num_subscribers()
+            .with(eq(vec!["t1".to_owned()]))
+            .once()
+            .returning(|_| vec![("t1".to_owned(), 3)]);
+        broker_service
+            .expect_num_patterns()
+            .once()
+            .returning(|| 0);
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            broker_service,
+            MockScriptService::new(),
+        );
+
+        channels(&mut context, args("pubsub channels t*")).await;
+        numsub(&mut context, args("pubsub numsub t1")).await;
+        numpat(&mut context, args("pubsub numpat")).await;
+
+        assert_eq!(context.take_response(), b"t1\nt2\nt1 3\n0\n".to_vec());
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::ops::BitOr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::core::command::context::CommandContext;
+use crate::core::module::{Module, ValueType};
+use crate::core::tlv::MODULE_TLV_TYPE_MIN;
+
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CommandFlags(u16);
+
+impl CommandFlags {
+    pub const NONE: Self = Self(0);
+    pub const READONLY: Self = Self(1);
+    pub const WRITE: Self = Self(1 << 1);
+    pub const PUBSUB: Self = Self(1 << 2);
+    pub const ADMIN: Self = Self(1 << 3);
+    pub const NOSCRIPT: Self = Self(1 << 4);
+    pub const NOMULTI: Self = Self(1 << 5);
+    pub const TRANSACTION: Self = Self(1 << 6);
+    pub const EXCLUSIVE: Self = Self(1 << 7);
+    pub const ALLOW_BUSY: Self = Self(1 << 8);
+    pub const NO_AUTH: Self = Self(1 << 9);
+    pub const DENYOOM: Self = Self(1 << 10);
+
+    const NAMES: [(Self, &'static str); 11] = [
+        (Self::READONLY, "readonly"),
+        (Self::WRITE, "write"),
+        (Self::PUBSUB, "pubsub"),
+        (Self::ADMIN, "admin"),
+        (Self::NOSCRIPT, "noscript"),
+        (Self::NOMULTI, "nomulti"),
+        (Self::TRANSACTION, "transaction"),
+        (Self::EXCLUSIVE, "exclusive"),
+        (Self::ALLOW_BUSY, "allow-busy"),
+        (Self::NO_AUTH, "no-auth"),
+        (Self::DENYOOM, "denyoom"),
+    ];
+
+    pub fn contains(&self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(_, flag_name)| *flag_name == name)
+            .map(|(flag, _)| *flag)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl BitOr for CommandFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[async_trait]
+pub trait CommandExecutor: Send + Sync {
+    async fn execute(&self, context: &mut CommandContext, args: Vec<String>);
+}
+
+#[async_trait]
+impl<F> CommandExecutor for F
+where
+    F: for<'a> Fn(&'a mut CommandContext, Vec<String>) -> CommandFuture<'a> + Send + Sync,
+{
+    async fn execute(&self, context: &mut CommandContext, args: Vec<String>) {
+        self(context, args).await
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct ArgPositions {
+    first: usize,
+    last: i32,
+    step: usize,
+}
+
+impl ArgPositions {
+    fn select<'a>(&self, args: &'a [String]) -> Vec<&'a str> {
+        if self.first == 0 || self.step == 0 {
+            return Vec::new();
+        }
+        let last = if self.last < 0 {
+            args.len() as i32 + self.last
+        } else {
+            self.last
+        };
+        let Ok(last) = usize::try_from(last) else { return Vec::new(); };
+        (self.first..=last.min(args.len().saturating_sub(1)))
+            .step_by(self.step)
+            .map(|index| args[index].as_str())
+            .collect()
+    }
+}
+
+pub struct Command {
+    name: String,
+    arity: i32,
+    flags: CommandFlags,
+    keys: ArgPositions,
+    channels: ArgPositions,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl Command {
+    /// The name is lower case, a subcommand is named `command|subcommand`.
+    /// The arity counts the command name (and subcommand), a negative arity is a minimum.
+    pub fn new(
+        name: &str,
+        arity: i32,
+        flags: CommandFlags,
+        executor: impl CommandExecutor + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            arity,
+            flags,
+            keys: ArgPositions::default(),
+            channels: ArgPositions::default(),
+            executor: Arc::new(executor),
+        }
+    }
+
+    pub fn with_keys(mut self, first: usize, last: i32, step: usize) -> Self {
+        self.keys = ArgPositions { first, last, step };
+        self
+    }
+
+    pub fn with_channels(mut self, first: usize, last: i32, step: usize) -> Self {
+        self.channels = ArgPositions { first, last, step };
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> i32 {
+        self.arity
+    }
+
+    pub fn flags(&self) -> CommandFlags {
+        self.flags
+    }
+
+    pub fn is_arity_valid(&self, num_args: usize) -> bool {
+        let arity = self.arity.unsigned_abs() as usize;
+        if self.arity < 0 {
+            num_args >= arity
+        } else {
+            num_args == arity
+        }
+    }
+
+    pub fn keys<'a>(&self, args: &'a [String]) -> Vec<&'a str> {
+        self.keys.select(args)
+    }
+
+    pub fn channels<'a>(&self, args: &'a [String]) -> Vec<&'a str> {
+        self.channels.select(args)
+    }
+
+    pub fn info(&self) -> String {
+        let flags = self.flags.names();
+        let flags = if flags.is_empty() { "-".to_owned() } else { flags.join(",") };
+        format!(
+            "{} {} {} {} {} {}",
+            self.name, self.arity, flags, self.keys.first, self.keys.last, self.keys.step
+        )
+    }
+
+    pub async fn execute(&self, context: &mut CommandContext, args: Vec<String>) {
+        self.executor.execute(context, args).await
+    }
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Arc<Command>>,
+    modules: Vec<String>,
+    value_types: HashMap<u8, ValueType>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_builtin_commands(mut self) -> Self {
+        for command in crate::core::command::builtin_commands() {
+            self.register(command);
+        }
+        self
+    }
+
+    pub fn register(&mut self, command: Command) {
+        self.commands.insert(command.name.clone(), Arc::new(command));
+    }
+
+    pub fn load_module(&mut self, module: &dyn Module) -> io::Result<()> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        let name = module.name();
+        if self.modules.iter().any(|module| module == name) {
+            return invalid(format!("module '{}' is already loaded", name));
+        }
+        let commands = module.commands();
+        let value_types = module.value_types();
+        for command in commands.iter() {
+            if self.commands.contains_key(&command.name) {
+                return invalid(format!("command '{}' already exists", command.name));
+            }
+        }
+        for value_type in value_types.iter() {
+            if value_type.tlv_type < MODULE_TLV_TYPE_MIN {
+                return invalid(format!(
+                    "tlv type {} of '{}' is reserved by the server",
+                    value_type.tlv_type, value_type.name
+                ));
+            }
+            if let Some(other) = self.value_types.get(&value_type.tlv_type) {
+                return invalid(format!(
+                    "tlv type {} of '{}' is already used by '{}'",
+                    value_type.tlv_type, value_type.name, other.name
+                ));
+            }
+        }
+
+        for command in commands {
+            self.register(command);
+        }
+        for value_type in value_types {
+            self.value_types.insert(value_type.tlv_type, value_type);
+        }
+        self.modules.push(name.to_owned());
+        Ok(())
+    }
+
+    pub fn modules(&self) -> &[String] {
+        &self.modules
+    }
+
+    pub fn value_type(&self, tlv_type: u8) -> Option<&ValueType> {
+        self.value_types.get(&tlv_type)
+    }
+
+    pub fn value_type_by_name(&self, name: &str) -> Option<&ValueType> {
+        self.value_types.values().find(|value_type| value_type.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn command_by_name(&self, name: &str) -> Option<Arc<Command>> {
+        self.commands.get(&name.to_lowercase()).cloned()
+    }
+
+    pub fn command(&self, args: &[String]) -> Option<Arc<Command>> {
+        let name = args.first()?;
+        if let Some(subcommand) = args.get(1) {
+            let command = self.command_by_name(&format!("{}|{}", name, subcommand));
+            if command.is_some() {
+                return command;
+            }
+        }
+        self.command_by_name(name)
+    }
+
+    pub fn commands(&self) -> Vec<Arc<Command>> {
+        let mut commands: Vec<Arc<Command>> = self.commands.values().cloned().collect();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
+    fn resolve(&self, context: &CommandContext, args: &[String]) -> Result<Arc<Command>, String> {
+        let Some(command) = self.command(args) else { return Err("unknown\n".to_owned()); };
+        if !command.is_arity_valid(args.len()) {
+            return Err(format!(
+                "err wrong number of arguments for '{}' command\n",
+                command.name
+            ));
+        }
+        // keys are also the names of the cache files
+        if let Some(key) = command.keys(args).into_iter().find(|key| !is_valid_key(key)) {
+            return Err(format!("err invalid key '{}'\n", key));
+        }
+        context
+            .auth_service
+            .check(context.address, context.session.user(), &command, args)?;
+        Ok(command)
+    }
+
+    /// Runs a command received from a client: refuses it until the client authenticates unless it is flagged
+    /// `NO_AUTH`, queues it when a transaction is started, otherwise runs it holding the command lock according
+    /// to its flags.
+    pub async fn dispatch(&self, context: &mut CommandContext, args: Vec<String>) {
+        if !context.session.is_authenticated() && context.auth_service.requires_auth() {
+            let allowed = self
+                .command(&args)
+                .is_some_and(|command| command.flags().contains(CommandFlags::NO_AUTH));
+            if !allowed {
+                context.reply(b"err NOAUTH Authentication required.\n");
+                return;
+            }
+        }
+        let command = match self.resolve(context, &args) {
+            Ok(command) => command,
+            Err(reply) => {
+                context.session.abort_transaction();
+                context.reply(reply.as_bytes());
+                return;
+            }
+        };
+
+        let flags = command.flags();
+        if context.session.is_in_transaction() && !flags.contains(CommandFlags::TRANSACTION) {
+            if flags.contains(CommandFlags::NOMULTI) {
+                context.session.abort_transaction();
+                let reply = format!("err '{}' is not allowed in a transaction\n", command.name);
+                context.reply(reply.as_bytes());
+            } else {
+                context.session.queue(args);
+                context.reply(b"queued\n");
+            }
+            return;
+        }
+
+        if flags.contains(CommandFlags::ALLOW_BUSY) {
+            run(&command, context, args).await;
+            return;
+        }
+        let command_lock = context.redis_service.command_lock();
+        if flags.contains(CommandFlags::EXCLUSIVE) {
+            let _guard = command_lock.write().await;
+            run(&command, context, args).await;
+        } else {
+            let _guard = command_lock.read().await;
+            run(&command, context, args).await;
+        }
+    }
+
+    /// Runs a command while the caller already holds the command lock, e.g. EXEC or a script.
+    pub async fn execute(&self, context: &mut CommandContext, args: Vec<String>) {
+        match self.resolve(context, &args) {
+            Ok(command) => run(&command, context, args).await,
+            Err(reply) => context.reply(reply.as_bytes()),
+        }
+    }
+}
+
+async fn run(command: &Command, context: &mut CommandContext, args: Vec<String>) {
+    if command.flags().contains(CommandFlags::DENYOOM) && !context.redis_service.evict().await {
+        context.reply(b"err OOM command not allowed when used memory > 'maxmemory'.\n");
+        return;
+    }
+    command.execute(context, args).await;
+}
+
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use crate::core::auth::MyAuthService;
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::command::registry::{Command, CommandFlags, CommandFuture, CommandRegistry};
+    use crate::core::module::example::CounterModule;
+    use crate::core::module::{Module, ValueType};
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+
+    fn echo(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+        Box::pin(async move {
+            context.reply(args.join(" ").as_bytes());
+        })
+    }
+
+    fn new_registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register(Command::new("echo", -2, CommandFlags::READONLY, echo).with_keys(1, -1, 2));
+        registry.register(Command::new("echo|sub", 3, CommandFlags::NOMULTI, echo));
+        registry
+    }
+
+    fn new_context() -> CommandContext {
+        let mut redis_service = MockRedisService::new();
+        let command_lock = Arc::new(RwLock::new(()));
+        redis_service
+            .expect_command_lock()
+            .returning(move || command_lock.clone());
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[test]
+    fn test_flags() {
+        let flags = CommandFlags::READONLY | CommandFlags::PUBSUB;
+        assert!(flags.contains(CommandFlags::READONLY));
+        assert!(!flags.contains(CommandFlags::WRITE));
+        assert_eq!(flags.names(), vec!["readonly", "pubsub"]);
+    }
+
+    #[test]
+    fn test_command() {
+        let registry = new_registry();
+
+        let command = registry.command(&args("ECHO a b c")).unwrap();
+        assert_eq!(command.name(), "echo");
+        assert!(command.is_arity_valid(2));
+        assert!(!command.is_arity_valid(1));
+        assert_eq!(command.keys(&args("echo a b c")), vec!["a", "c"]);
+        assert_eq!(command.info(), "echo -2 readonly 1 -1 2");
+
+        let command = registry.command(&args("echo sub a")).unwrap();
+        assert_eq!(command.name(), "echo|sub");
+        assert!(command.keys(&args("echo sub a")).is_empty());
+        assert_eq!(command.info(), "echo|sub 3 nomulti 0 0 0");
+
+        assert!(registry.command(&args("xxx")).is_none());
+        assert!(registry.command(&[]).is_none());
+        let names: Vec<String> = registry
+            .commands()
+            .iter()
+            .map(|command| command.name().to_owned())
+            .collect();
+        assert_eq!(names, vec!["echo", "echo|sub"]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let registry = new_registry();
+        let mut context = new_context();
+
+        registry.dispatch(&mut context, args("echo a")).await;
+        assert_eq!(context.take_response(), b"echo a".to_vec());
+        registry.dispatch(&mut context, args("xxx")).await;
+        assert_eq!(context.take_response(), b"unknown\n".to_vec());
+        registry.dispatch(&mut context, args("echo")).await;
+        assert_eq!(
+            context.take_response(),
+            b"err wrong number of arguments for 'echo' command\n".to_vec()
+        );
+        registry.dispatch(&mut context, args("echo a-b")).await;
+        assert_eq!(context.take_response(), b"err invalid key 'a-b'\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_before_auth() {
+        let mut registry = new_registry();
+        registry.register(Command::new("ping", 1, CommandFlags::NO_AUTH, echo));
+        let mut context = new_context()
+            .with_auth_service(Arc::new(MyAuthService::new().with_requirepass("s3cret")));
+
+        registry.dispatch(&mut context, args("echo a")).await;
+        assert_eq!(context.take_response(), b"err NOAUTH Authentication required.\n".to_vec());
+        registry.dispatch(&mut context, args("xxx")).await;
+        assert_eq!(context.take_response(), b"err NOAUTH Authentication required.\n".to_vec());
+        registry.dispatch(&mut context, args("ping")).await;
+        assert_eq!(context.take_response(), b"ping".to_vec());
+
+        context.session.authenticate("default".to_owned());
+        registry.dispatch(&mut context, args("echo a")).await;
+        assert_eq!(context.take_response(), b"echo a".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_in_transaction() {
+        let registry = new_registry();
+        let mut context = new_context();
+        context.session.begin_transaction();
+
+        registry.dispatch(&mut context, args("echo a")).await;
+        assert_eq!(context.take_response(), b"queued\n".to_vec());
+        registry.dispatch(&mut context, args("echo sub a")).await;
+        assert_eq!(
+            context.take_response(),
+            b"err 'echo|sub' is not allowed in a transaction\n".to_vec()
+        );
+
+        let transaction = context.session.take_transaction().unwrap();
+        assert_eq!(transaction.commands, vec![args("echo a")]);
+        assert!(transaction.aborted);
+    }
+
+    struct InvalidModule(u8);
+
+    impl Module for InvalidModule {
+        fn name(&self) -> &str {
+            "invalid"
+        }
+
+        fn commands(&self) -> Vec<Command> {
+            vec![Command::new("invalid.echo", 1, CommandFlags::NONE, echo)]
+        }
+
+        fn value_types(&self) -> Vec<ValueType> {
+            vec![ValueType {
+                name: "invalid".to_owned(),
+                tlv_type: self.0,
+            }]
+        }
+    }
+
+    #[test]
+    fn test_load_module() {
+        let mut registry = new_registry();
+
+        registry.load_module(&CounterModule).unwrap();
+        assert!(registry.command_by_name("counter.incrby").is_some());
+        assert_eq!(registry.value_type(200).unwrap().name, "counter");
+        assert_eq!(registry.modules(), ["counter"]);
+
+        let err = registry.load_module(&CounterModule).unwrap_err();
+        assert_eq!(err.to_string(), "module 'counter' is already loaded");
+        let err = registry.load_module(&InvalidModule(1)).unwrap_err();
+        assert_eq!(err.to_string(), "tlv type 1 of 'invalid' is reserved by the server");
+        let err = registry.load_module(&InvalidModule(200)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tlv type 200 of 'invalid' is already used by 'counter'"
+        );
+        // nothing is registered by a module failing to load
+        assert!(registry.command_by_name("invalid.echo").is_none());
+        assert_eq!(registry.modules(), ["counter"]);
+    }
+}
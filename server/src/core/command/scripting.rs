@@ -0,0 +1,177 @@
+use std::sync::{Arc, Mutex};
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::{CommandFlags, CommandFuture};
+use crate::core::script::CallBridge;
+
+pub fn eval(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let script = args[1].clone();
+        let Some((keys, script_args)) = split_keys(context, &args) else { return; };
+        context.script_service.load(script.clone());
+        run(context, script, keys, script_args).await;
+    })
+}
+
+pub fn evalsha(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some((keys, script_args)) = split_keys(context, &args) else { return; };
+        let Some(script) = context.script_service.script(args[1].to_lowercase()) else {
+            context.reply(b"err NOSCRIPT no matching script\n");
+            return;
+        };
+        run(context, script, keys, script_args).await;
+    })
+}
+
+pub fn load(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let sha = context.script_service.load(args[2].clone());
+        context.reply_line(sha.as_bytes());
+    })
+}
+
+pub fn kill(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if context.script_service.kill() {
+            context.reply(b"kill ok\n");
+        } else {
+            context.reply(b"err NOTBUSY no scripts in execution right now\n");
+        }
+    })
+}
+
+async fn run(context: &mut CommandContext, script: String, keys: Vec<String>, args: Vec<String>) {
+    let call = call_bridge(context);
+    let result = context.script_service.eval(script, keys, args, call).await;
+    match result {
+        Ok(response) => context.reply(&response),
+        Err(err) => context.reply(format!("err {}\n", err).as_bytes()),
+    }
+}
+
+fn split_keys(context: &mut CommandContext, args: &[String]) -> Option<(Vec<String>, Vec<String>)> {
+    let Ok(num_keys) = args[2].parse::<usize>() else {
+        context.reply(b"err value is not an integer or out of range\n");
+        return None;
+    };
+    if num_keys > args.len() - 3 {
+        context.reply(b"err number of keys can't be greater than number of args\n");
+        return None;
+    }
+    let (keys, args) = args[3..].split_at(num_keys);
+    Some((keys.to_vec(), args.to_vec()))
+}
+
+/// Returns the bridge running the commands called by a script. The script runs on a blocking thread
+/// while its caller holds the command lock, so the commands are executed directly in a context of their own.
+fn call_bridge(context: &CommandContext) -> CallBridge {
+    let script_context = Mutex::new(context.fork());
+    let runtime = tokio::runtime::Handle::current();
+    Arc::new(move |args: Vec<String>| {
+        let mut script_context = script_context.lock().unwrap();
+        let registry = script_context.registry.clone();
+        let Some(command) = registry.command(&args) else {
+            return Err(format!("unknown command '{}'", args.join(" ")));
+        };
+        if command.flags().contains(CommandFlags::NOSCRIPT) {
+            return Err(format!("'{}' is not allowed from scripts", command.name()));
+        }
+
+        runtime.block_on(registry.execute(&mut script_context, args));
+        let response = script_context.take_response();
+        let response = String::from_utf8_lossy(&response);
+        let response = response.strip_suffix('\n').unwrap_or(&response);
+        match response.strip_prefix("err ") {
+            Some(err) => Err(err.to_owned()),
+            None => Ok(response.to_owned()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::eq;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context};
+    use crate::core::command::scripting::{eval, evalsha, kill, load};
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+
+    #[tokio::test]
+    async fn eval_should_be_handled() {
+        let mut script_service = MockScriptService::new();
+        script_service
+            .expect_load()
+            .with(eq("return".to_owned()))
+            .once()
+            .returning(|_| "sha".to_owned());
+        script_service
+            .expect_eval()
+            .withf(|script, keys, args, _| script == "return" && *keys == ["a"] && *args == ["b"])
+            .once()
+            .returning(|_, _, _, _| Err("script timed out".to_owned()));
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            script_service,
+        );
+
+        eval(&mut context, args("eval return 1 a b")).await;
+        eval(&mut context, args("eval return 2 a")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err script timed out\nerr number of keys can't be greater than number of args\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn evalsha_should_be_handled() {
+        let mut script_service = MockScriptService::new();
+        script_service
+            .expect_script()
+            .returning(|sha| (sha == "sha").then(|| "return 1".to_owned()));
+        script_service
+            .expect_eval()
+            .once()
+            .returning(|_, _, _, _| Ok(b"1\n".to_vec()));
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            script_service,
+        );
+
+        evalsha(&mut context, args("evalsha SHA 0")).await;
+        evalsha(&mut context, args("evalsha other 0")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"1\nerr NOSCRIPT no matching script\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn script_should_be_handled() {
+        let mut script_service = MockScriptService::new();
+        script_service
+            .expect_load()
+            .once()
+            .returning(|_| "sha".to_owned());
+        script_service.expect_kill().once().returning(|| false);
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            script_service,
+        );
+
+        load(&mut context, args("script load return")).await;
+        kill(&mut context, args("script kill")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"sha\nerr NOTBUSY no scripts in execution right now\n".to_vec()
+        );
+    }
+}
@@ -0,0 +1,906 @@
+use std::fmt::Display;
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+use crate::core::notify::KeyEvent;
+use crate::core::redis::{Checked, PreviousValues, SetCondition, SetExpiry};
+use crate::core::tlv::{from_tlv, resize_tlv, split_tlv, to_tlv, TLVType, TLV_HEADER_SIZE};
+
+pub const WRONGTYPE: &[u8] = b"err WRONGTYPE Operation against a key holding the wrong kind of value\n";
+pub const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+const MAX_LCS_TABLE_SIZE: usize = 32 * 1024 * 1024;
+
+pub type Previous = Option<Vec<Option<Vec<u8>>>>;
+type LcsMatch = (Range<usize>, Range<usize>);
+
+pub fn get(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let tlv = context.redis_service.get(context.session.db(), &args[1]).await;
+        reply_value(context, tlv);
+    })
+}
+
+/// `set <key> <value> [nx | xx] [get] [ex seconds | px milliseconds | exat timestamp | pxat timestamp | keepttl]`,
+/// replies `nil` if the key is not set because of `nx` or `xx`, the previous value with `get`.
+/// The words following the value are part of it when they aren't options, as they were before `set` had options,
+/// e.g. `set greeting hello world`. A client sends them as they are, see `parse_request`. Words that are all
+/// options are taken as such though: `set greeting hello ex 10` sets `hello` for 10 seconds where it used to set
+/// `hello ex 10`, which now has to be quoted, `set greeting "hello ex 10"`.
+pub fn set(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let (mut condition, mut expiry, mut get) = (SetCondition::Always, SetExpiry::Clear, false);
+        let (value, options) = if are_set_options(&args[3..]) {
+            (args[2].clone(), &args[3..])
+        } else {
+            (args[2..].join(" "), &[][..])
+        };
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match option.to_lowercase().as_str() {
+                "nx" if condition == SetCondition::Always => condition = SetCondition::NoneExists,
+                "xx" if condition == SetCondition::Always => condition = SetCondition::AllExist,
+                "get" if !get => get = true,
+                "keepttl" if expiry == SetExpiry::Clear => expiry = SetExpiry::Keep,
+                unit @ ("ex" | "px" | "exat" | "pxat") if expiry == SetExpiry::Clear => {
+                    let Some(deadline) = parse_deadline(context, "set", unit, options.next()) else { return; };
+                    expiry = SetExpiry::At(deadline);
+                }
+                _ => {
+                    context.reply(b"err syntax error\n");
+                    return;
+                }
+            }
+        }
+        let entries = vec![(args[1].clone(), to_tlv(value.into_bytes(), TLVType::String))];
+        if get {
+            // the previous value, whether the key is set or not
+            let Some((_, mut previous)) = set_strings(context, entries, condition, expiry).await else { return; };
+            reply_value(context, previous.remove(0));
+            return;
+        }
+        let Some(previous) = set_all(context, entries, condition, expiry).await else { return; };
+        context.reply(if previous.is_some() { b"set ok\n" } else { b"nil\n" });
+    })
+}
+
+pub fn mset(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(entries) = parse_entries(context, "mset", &args) else { return; };
+        if set_all(context, entries, SetCondition::Always, SetExpiry::Clear).await.is_some() {
+            context.reply(b"mset ok\n");
+        }
+    })
+}
+
+pub fn msetnx(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(entries) = parse_entries(context, "msetnx", &args) else { return; };
+        let Some(previous) = set_all(context, entries, SetCondition::NoneExists, SetExpiry::Clear).await else {
+            return;
+        };
+        context.reply(if previous.is_some() { b"1\n" } else { b"0\n" });
+    })
+}
+
+pub fn mget(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let db = context.session.db();
+        let mut values = Vec::with_capacity(args.len() - 1);
+        for key in args[1..].iter() {
+            match context.redis_service.get(db, key).await {
+                Some(tlv) if is_string(&tlv) => values.push(from_tlv(tlv)),
+                _ => values.push(b"nil".to_vec()),
+            }
+        }
+        context.reply_lines(&values);
+    })
+}
+
+pub fn getset(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let tlv = to_tlv(args[2].clone().into_bytes(), TLVType::String);
+        let entries = vec![(args[1].clone(), tlv)];
+        let (condition, expiry) = (SetCondition::Always, SetExpiry::Clear);
+        let Some((_, mut previous)) = set_strings(context, entries, condition, expiry).await else { return; };
+        reply_value(context, previous.remove(0));
+    })
+}
+
+pub fn getdel(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let db = context.session.db();
+        match context.redis_service.take_checked(db, &args[1], is_string).await {
+            Checked::Passed(tlv) => reply_value(context, tlv),
+            Checked::Failed => context.reply(WRONGTYPE),
+        }
+    })
+}
+
+pub fn getex(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let db = context.session.db();
+        let key = &args[1];
+        let expiry = match &args[2..] {
+            [] => None,
+            [option] if option.eq_ignore_ascii_case("persist") => Some(SetExpiry::Clear),
+            [option, value] => {
+                let unit = option.to_lowercase();
+                if !matches!(unit.as_str(), "ex" | "px" | "exat" | "pxat") {
+                    context.reply(b"err syntax error\n");
+                    return;
+                }
+                let Some(deadline) = parse_deadline(context, "getex", &unit, Some(value)) else { return; };
+                Some(SetExpiry::At(deadline))
+            }
+            _ => {
+                context.reply(b"err syntax error\n");
+                return;
+            }
+        };
+        let tlv = context.redis_service.get(db, key).await;
+        if tlv.as_ref().is_some_and(|tlv| is_string(tlv)) {
+            match expiry {
+                Some(SetExpiry::At(deadline)) => {
+                    let ttl = deadline.saturating_duration_since(Instant::now());
+                    context.redis_service.expire(db, key, ttl).await;
+                }
+                Some(_) => {
+                    context.redis_service.persist(db, key).await;
+                }
+                None => {}
+            }
+        }
+        reply_value(context, tlv);
+    })
+}
+
+pub fn append(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let bytes = args[2].clone().into_bytes();
+        let update = Box::new(move |tlv: &mut Vec<u8>| {
+            if !tlv.is_empty() && !is_string(tlv) {
+                return (WRONGTYPE.to_vec(), Vec::new());
+            }
+            let length = string_length(tlv);
+            if length + bytes.len() > MAX_STRING_LENGTH {
+                return (b"err string exceeds maximum allowed size\n".to_vec(), Vec::new());
+            }
+            write_range(tlv, length, &bytes)
+        });
+        let reply = context.redis_service.update(context.session.db(), &args[1], KeyEvent::Append, update).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn strlen(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let inspection = Box::new(|tlv: Option<&[u8]>| match tlv {
+            Some(tlv) if !is_string(tlv) => WRONGTYPE.to_vec(),
+            tlv => integer(tlv.map_or(0, string_length)),
+        });
+        let reply = context.redis_service.inspect(context.session.db(), &args[1], inspection).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn getrange(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let (Ok(start), Ok(end)) = (args[2].parse::<i64>(), args[3].parse::<i64>()) else {
+            context.reply(b"err value is not an integer or out of range\n");
+            return;
+        };
+        let inspection = Box::new(move |tlv: Option<&[u8]>| {
+            let value = match tlv {
+                Some(tlv) if !is_string(tlv) => return WRONGTYPE.to_vec(),
+                tlv => tlv.and_then(split_tlv).map_or(&[][..], |(_, value)| value),
+            };
+            let length = value.len() as i64;
+            let start = if start < 0 { length + start } else { start }.max(0);
+            let end = if end < 0 { length + end } else { end }.min(length - 1);
+            let mut line = if start > end { Vec::new() } else { value[start as usize..=end as usize].to_vec() };
+            line.push(b'\n');
+            line
+        });
+        let reply = context.redis_service.inspect(context.session.db(), &args[1], inspection).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn setrange(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Ok(offset) = args[2].parse::<i64>() else {
+            context.reply(b"err value is not an integer or out of range\n");
+            return;
+        };
+        let Ok(offset) = usize::try_from(offset) else {
+            context.reply(b"err offset is out of range\n");
+            return;
+        };
+        let bytes = args[3].clone().into_bytes();
+        if offset.saturating_add(bytes.len()) > MAX_STRING_LENGTH {
+            context.reply(b"err string exceeds maximum allowed size\n");
+            return;
+        }
+        let update = Box::new(move |tlv: &mut Vec<u8>| {
+            if !tlv.is_empty() && !is_string(tlv) {
+                return (WRONGTYPE.to_vec(), Vec::new());
+            }
+            if bytes.is_empty() {
+                // nothing to write, a missing key isn't created
+                return (integer(string_length(tlv)), Vec::new());
+            }
+            write_range(tlv, offset, &bytes)
+        });
+        let reply = context.redis_service.update(context.session.db(), &args[1], KeyEvent::SetRange, update).await;
+        context.reply(&reply);
+    })
+}
+
+pub fn lcs(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let (mut len, mut idx, mut min_match_length, mut with_match_length) = (false, false, 0, false);
+        let mut options = args[3..].iter();
+        while let Some(option) = options.next() {
+            match option.to_lowercase().as_str() {
+                "len" => len = true,
+                "idx" => idx = true,
+                "withmatchlen" => with_match_length = true,
+                "minmatchlen" => {
+                    let Some(Ok(length)) = options.next().map(|length| length.parse::<i64>()) else {
+                        context.reply(b"err value is not an integer or out of range\n");
+                        return;
+                    };
+                    min_match_length = length.max(0) as usize;
+                }
+                _ => {
+                    context.reply(b"err syntax error\n");
+                    return;
+                }
+            }
+        }
+        if len && idx {
+            context.reply(b"err If you want both the length and indexes, please just use IDX.\n");
+            return;
+        }
+        let db = context.session.db();
+        let mut strings = Vec::with_capacity(2);
+        for key in args[1..3].iter() {
+            match context.redis_service.get(db, key).await {
+                Some(tlv) if !is_string(&tlv) => {
+                    context.reply(WRONGTYPE);
+                    return;
+                }
+                tlv => strings.push(tlv.map_or(Vec::new(), from_tlv)),
+            }
+        }
+        let (b, a) = (strings.pop().unwrap(), strings.pop().unwrap());
+        let table_size = (a.len() + 1)
+            .checked_mul(b.len() + 1)
+            .and_then(|cells| cells.checked_mul(size_of::<u32>()));
+        if table_size.is_none_or(|size| size > MAX_LCS_TABLE_SIZE) {
+            context.reply(b"err the strings are too long to compare\n");
+            return;
+        }
+        // it takes up to a few hundred milliseconds, the other connections are served meanwhile
+        let comparison = tokio::task::spawn_blocking(move || longest_common_subsequence(&a, &b)).await;
+        let Ok((subsequence, matches)) = comparison else {
+            context.reply(b"err the strings could not be compared\n");
+            return;
+        };
+        if len {
+            context.reply(&integer(subsequence.len()));
+        } else if idx {
+            let mut lines: Vec<String> = matches
+                .iter()
+                .filter(|(range_a, _)| range_a.len() >= min_match_length)
+                .map(|(range_a, range_b)| {
+                    let line = format!("{}-{} {}-{}", range_a.start, range_a.end - 1, range_b.start, range_b.end - 1);
+                    if with_match_length {
+                        format!("{} {}", line, range_a.len())
+                    } else {
+                        line
+                    }
+                })
+                .collect();
+            lines.push(format!("len {}", subsequence.len()));
+            context.reply_lines(&lines);
+        } else {
+            context.reply_line(&subsequence);
+        }
+    })
+}
+
+/// Returns the longest common subsequence of two strings and the ranges of the strings it matches, the last
+/// ones first. The common subsequences are as long as each other, the one redis returns is chosen.
+fn longest_common_subsequence(a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<LcsMatch>) {
+    let width = b.len() + 1;
+    // lengths[i * width + j] is the length of the subsequence of a[..i] and b[..j]
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            lengths[i * width + j] = if a[i - 1] == b[j - 1] {
+                lengths[(i - 1) * width + j - 1] + 1
+            } else {
+                lengths[(i - 1) * width + j].max(lengths[i * width + j - 1])
+            };
+        }
+    }
+    let (mut subsequence, mut matches) = (Vec::new(), Vec::<LcsMatch>::new());
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            subsequence.push(a[i - 1]);
+            match matches.last_mut() {
+                // the match extends the current ranges backwards
+                Some((range_a, range_b)) if range_a.start == i && range_b.start == j => {
+                    range_a.start -= 1;
+                    range_b.start -= 1;
+                }
+                _ => matches.push((i - 1..i, j - 1..j)),
+            }
+            i -= 1;
+            j -= 1;
+        } else if lengths[(i - 1) * width + j] > lengths[i * width + j - 1] {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    subsequence.reverse();
+    (subsequence, matches)
+}
+
+/// Writes bytes into a string tlv at an offset, padding it with zeros if it is shorter, for a `ValueUpdate`.
+/// The written bytes are persisted before the length, so that the length never covers bytes not written.
+pub fn write_range(tlv: &mut Vec<u8>, offset: usize, bytes: &[u8]) -> (Vec<u8>, Vec<Range<usize>>) {
+    let length = string_length(tlv);
+    let end = offset + bytes.len();
+    resize_tlv(tlv, length.max(end));
+    tlv[TLV_HEADER_SIZE + offset..TLV_HEADER_SIZE + end].copy_from_slice(bytes);
+    let written = TLV_HEADER_SIZE + offset.min(length)..TLV_HEADER_SIZE + end;
+    (integer(length.max(end)), vec![written, 0..TLV_HEADER_SIZE])
+}
+
+pub fn string_length(tlv: &[u8]) -> usize {
+    split_tlv(tlv).map_or(0, |(_, value)| value.len())
+}
+
+pub fn integer(value: impl Display) -> Vec<u8> {
+    format!("{}\n", value).into_bytes()
+}
+
+pub fn is_string(tlv: &[u8]) -> bool {
+    tlv.first().is_some_and(|&tlv_type| TLVType::from_u8(tlv_type).is_some())
+}
+
+fn reply_value(context: &mut CommandContext, tlv: Option<Vec<u8>>) {
+    match tlv {
+        Some(tlv) if !is_string(&tlv) => context.reply(WRONGTYPE),
+        Some(tlv) => context.reply_line(&from_tlv(tlv)),
+        None => context.reply(b"not found\n"),
+    }
+}
+
+pub async fn set_all(
+    context: &mut CommandContext,
+    entries: Vec<(String, Vec<u8>)>,
+    condition: SetCondition,
+    expiry: SetExpiry,
+) -> Option<Previous> {
+    let db = context.session.db();
+    match context.redis_service.set_all(db, entries, condition, expiry).await {
+        Ok(previous) => Some(previous),
+        Err(err) => {
+            log::error!("error during writing cache: {}", err);
+            context.reply(format!("err the cache could not be updated: {}\n", err).as_bytes());
+            None
+        }
+    }
+}
+
+async fn set_strings(
+    context: &mut CommandContext,
+    entries: Vec<(String, Vec<u8>)>,
+    condition: SetCondition,
+    expiry: SetExpiry,
+) -> Option<(bool, PreviousValues)> {
+    let db = context.session.db();
+    match context.redis_service.set_checked(db, entries, condition, expiry, is_string).await {
+        Ok(Checked::Passed(previous)) => Some(previous),
+        Ok(Checked::Failed) => {
+            context.reply(WRONGTYPE);
+            None
+        }
+        Err(err) => {
+            log::error!("error during writing cache: {}", err);
+            context.reply(format!("err the cache could not be updated: {}\n", err).as_bytes());
+            None
+        }
+    }
+}
+
+fn parse_entries(context: &mut CommandContext, command: &str, args: &[String]) -> Option<Vec<(String, Vec<u8>)>> {
+    if args.len().is_multiple_of(2) {
+        context.reply(format!("err wrong number of arguments for '{}' command\n", command).as_bytes());
+        return None;
+    }
+    let entries = args[1..]
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), to_tlv(pair[1].clone().into_bytes(), TLVType::String)))
+        .collect();
+    Some(entries)
+}
+
+pub fn are_set_options(words: &[String]) -> bool {
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        match word.to_lowercase().as_str() {
+            "nx" | "xx" | "get" | "keepttl" => {}
+            "ex" | "px" | "exat" | "pxat" => {
+                if words.next().is_none_or(|value| value.parse::<i64>().is_err()) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn parse_deadline(context: &mut CommandContext, command: &str, unit: &str, value: Option<&String>) -> Option<Instant> {
+    let Some(value) = value else {
+        context.reply(b"err syntax error\n");
+        return None;
+    };
+    let Ok(value) = value.parse::<i64>() else {
+        context.reply(b"err value is not an integer or out of range\n");
+        return None;
+    };
+    let ttl = match u64::try_from(value) {
+        Ok(value) if value > 0 => {
+            let until = |timestamp: Duration| {
+                let time = UNIX_EPOCH.checked_add(timestamp)?;
+                Some(time.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+            };
+            match unit {
+                "ex" => Some(Duration::from_secs(value)),
+                "px" => Some(Duration::from_millis(value)),
+                "exat" => until(Duration::from_secs(value)),
+                _ => until(Duration::from_millis(value)),
+            }
+        }
+        _ => None,
+    };
+    let deadline = ttl.and_then(|ttl| Instant::now().checked_add(ttl));
+    if deadline.is_none() {
+        context.reply(format!("err invalid expire time in '{}' command\n", command).as_bytes());
+    }
+    deadline
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use mockall::predicate::{always, eq, function};
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context, CommandContext};
+    use crate::core::command::string::{
+        append, get, getdel, getex, getrange, getset, lcs, mget, mset, msetnx, set, setrange, strlen, WRONGTYPE,
+    };
+    use crate::core::redis::{Checked, MockRedisService, SetCondition, SetExpiry};
+    use crate::core::script::MockScriptService;
+    use crate::core::tlv::{to_tlv, TLVType};
+
+    fn new_context(redis_service: MockRedisService) -> CommandContext {
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn get_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key1"))
+            .once()
+            .returning(|_, _| Some(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 104, 105]));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key2"))
+            .once()
+            .returning(|_, _| None);
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key3"))
+            .once()
+            .returning(|_, _| Some(vec![200, 0, 0, 0, 0, 0, 0, 0, 1, 1]));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key4"))
+            .once()
+            .returning(|_, _| Some(Vec::new()));
+        let mut context = new_context(redis_service);
+
+        get(&mut context, args("get key1")).await;
+        get(&mut context, args("get key2")).await;
+        get(&mut context, args("get key3")).await;
+        get(&mut context, args("get key4")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"hi\nnot found\n\
+            err WRONGTYPE Operation against a key holding the wrong kind of value\n\
+            err WRONGTYPE Operation against a key holding the wrong kind of value\n"
+                .to_vec()
+        );
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        to_tlv(value.as_bytes().to_vec(), TLVType::String)
+    }
+
+    #[tokio::test]
+    async fn set_should_be_handled_when_cache_ok() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_set_all()
+            .with(
+                eq(0),
+                eq(vec![("key1".to_owned(), vec![1, 0, 0, 0, 0, 0, 0, 0, 1, 55])]),
+                eq(SetCondition::Always),
+                eq(SetExpiry::Clear),
+            )
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None])));
+        let mut context = new_context(redis_service);
+
+        set(&mut context, args("set key1 7")).await;
+
+        assert_eq!(context.take_response(), b"set ok\n".to_vec());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::io_other_error)]
+    async fn set_should_be_handled_when_cache_err() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_set_all()
+            .once()
+            .returning(|_, _, _, _| Err(Error::new(ErrorKind::Other, "Other")));
+        let mut context = new_context(redis_service);
+
+        set(&mut context, args("set key1 ,-")).await;
+
+        assert_eq!(context.take_response(), b"err the cache could not be updated: Other\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn set_options_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_set_all()
+            .with(always(), always(), eq(SetCondition::NoneExists), eq(SetExpiry::Keep))
+            .once()
+            .returning(|_, _, _, _| Ok(None));
+        redis_service
+            .expect_set_checked()
+            .with(
+                always(),
+                always(),
+                eq(SetCondition::AllExist),
+                function(|expiry| {
+                    let SetExpiry::At(deadline) = expiry else { return false; };
+                    let ttl = deadline.saturating_duration_since(Instant::now());
+                    ttl > Duration::from_secs(9) && ttl <= Duration::from_secs(10)
+                }),
+                always(),
+            )
+            .once()
+            .returning(|_, _, _, _, _| Ok(Checked::Passed((true, vec![Some(string("old"))]))));
+        redis_service
+            .expect_set_checked()
+            .with(always(), always(), eq(SetCondition::NoneExists), always(), always())
+            .once()
+            .returning(|_, _, _, _, _| Ok(Checked::Passed((false, vec![Some(string("old"))]))));
+        redis_service
+            .expect_set_all()
+            .with(
+                eq(0),
+                eq(vec![("key1".to_owned(), string("a ex x"))]),
+                eq(SetCondition::Always),
+                eq(SetExpiry::Clear),
+            )
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None])));
+        let mut context = new_context(redis_service);
+
+        set(&mut context, args("set key1 a NX keepttl")).await;
+        set(&mut context, args("set key1 a xx get px 10000")).await;
+        set(&mut context, args("set key1 a nx get")).await;
+        set(&mut context, args("set key1 a nx xx")).await;
+        set(&mut context, args("set key1 a ex 10 keepttl")).await;
+        // not an option, part of the value
+        set(&mut context, args("set key1 a ex x")).await;
+        set(&mut context, args("set key1 a ex 0")).await;
+        set(&mut context, args("set key1 a pxat -5")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"nil\nold\nold\n\
+            err syntax error\n\
+            err syntax error\n\
+            set ok\n\
+            err invalid expire time in 'set' command\n\
+            err invalid expire time in 'set' command\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn set_should_take_trailing_words_as_options_only_if_they_all_are() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_set_all()
+            .with(
+                eq(0),
+                eq(vec![("key1".to_owned(), string("hello"))]),
+                eq(SetCondition::Always),
+                function(|expiry| matches!(expiry, SetExpiry::At(_))),
+            )
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None])));
+        redis_service
+            .expect_set_all()
+            .with(
+                eq(0),
+                eq(vec![("key1".to_owned(), string("hello world EX 10"))]),
+                eq(SetCondition::Always),
+                eq(SetExpiry::Clear),
+            )
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None])));
+        redis_service
+            .expect_set_all()
+            .with(
+                eq(0),
+                eq(vec![("key1".to_owned(), string("hello EX 10"))]),
+                eq(SetCondition::Always),
+                eq(SetExpiry::Clear),
+            )
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None])));
+        let mut context = new_context(redis_service);
+
+        set(&mut context, args("set key1 hello EX 10")).await;
+        set(&mut context, args("set key1 hello world EX 10")).await;
+        // a quoted value
+        set(&mut context, vec!["set".to_owned(), "key1".to_owned(), "hello EX 10".to_owned()]).await;
+
+        assert_eq!(context.take_response(), b"set ok\nset ok\nset ok\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn multi_key_commands_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        let entries = vec![("key1".to_owned(), string("a")), ("key2".to_owned(), string("b"))];
+        redis_service
+            .expect_set_all()
+            .with(eq(0), eq(entries.clone()), eq(SetCondition::Always), eq(SetExpiry::Clear))
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None, None])));
+        redis_service
+            .expect_set_all()
+            .with(eq(0), eq(entries), eq(SetCondition::NoneExists), eq(SetExpiry::Clear))
+            .once()
+            .returning(|_, _, _, _| Ok(None));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key1"))
+            .returning(|_, _| Some(string("a")));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key2"))
+            .returning(|_, _| Some(vec![200, 0, 0, 0, 0, 0, 0, 0, 1, 1]));
+        redis_service.expect_get().with(eq(0), eq("key3")).returning(|_, _| None);
+        let mut context = new_context(redis_service);
+
+        mset(&mut context, args("mset key1 a key2 b")).await;
+        mset(&mut context, args("mset key1 a key2")).await;
+        msetnx(&mut context, args("msetnx key1 a key2 b")).await;
+        mget(&mut context, args("mget key1 key2 key3")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"mset ok\n\
+            err wrong number of arguments for 'mset' command\n\
+            0\n\
+            a\nnil\nnil\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn get_and_modify_commands_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key1"))
+            .returning(|_, _| Some(string("a")));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key2"))
+            .returning(|_, _| Some(vec![200, 0, 0, 0, 0, 0, 0, 0, 1, 1]));
+        redis_service
+            .expect_set_checked()
+            .with(eq(0), eq(vec![("key1".to_owned(), string("b"))]), always(), always(), always())
+            .once()
+            .returning(|_, _, _, _, _| Ok(Checked::Passed((true, vec![Some(string("a"))]))));
+        redis_service
+            .expect_set_checked()
+            .with(eq(0), eq(vec![("key2".to_owned(), string("b"))]), always(), always(), always())
+            .once()
+            .returning(|_, _, _, _, _| Ok(Checked::Failed));
+        redis_service
+            .expect_take_checked()
+            .with(eq(0), eq("key1"), always())
+            .once()
+            .returning(|_, _, _| Checked::Passed(Some(string("a"))));
+        redis_service
+            .expect_take_checked()
+            .with(eq(0), eq("key2"), always())
+            .once()
+            .returning(|_, _, _| Checked::Failed);
+        redis_service
+            .expect_expire()
+            .with(eq(0), eq("key1"), function(|ttl: &Duration| *ttl > Duration::from_secs(59)))
+            .once()
+            .returning(|_, _, _| true);
+        redis_service
+            .expect_persist()
+            .with(eq(0), eq("key1"))
+            .once()
+            .returning(|_, _| true);
+        let mut context = new_context(redis_service);
+
+        getset(&mut context, args("getset key1 b")).await;
+        getset(&mut context, args("getset key2 b")).await;
+        getdel(&mut context, args("getdel key1")).await;
+        getdel(&mut context, args("getdel key2")).await;
+        getex(&mut context, args("getex key1 EX 60")).await;
+        getex(&mut context, args("getex key1 persist")).await;
+        getex(&mut context, args("getex key2 persist")).await;
+        getex(&mut context, args("getex key1 ex")).await;
+        getex(&mut context, args("getex key1 keepttl 60")).await;
+        getex(&mut context, args("getex key1 px -1")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"a\n\
+            err WRONGTYPE Operation against a key holding the wrong kind of value\n\
+            a\n\
+            err WRONGTYPE Operation against a key holding the wrong kind of value\n\
+            a\na\n\
+            err WRONGTYPE Operation against a key holding the wrong kind of value\n\
+            err syntax error\n\
+            err syntax error\n\
+            err invalid expire time in 'getex' command\n"
+                .to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn append_and_setrange_should_update_value_in_place() {
+        let mut redis_service = MockRedisService::new();
+        let value = Arc::new(Mutex::new(Vec::new()));
+        let updated = value.clone();
+        redis_service
+            .expect_update()
+            .with(eq(0), eq("key1"), always(), always())
+            .returning(move |_, _, _, update| {
+                let (reply, ranges) = update(&mut updated.lock().unwrap());
+                let ranges: Vec<String> = ranges.iter().map(|range| format!("{:?} ", range)).collect();
+                [reply, ranges.concat().into_bytes()].concat()
+            });
+        let mut context = new_context(redis_service);
+
+        setrange(&mut context, args("setrange key1 0 ")).await;
+        append(&mut context, args("append key1 ab")).await;
+        append(&mut context, args("append key1 c")).await;
+        setrange(&mut context, args("setrange key1 5 d")).await;
+        setrange(&mut context, args("setrange key1 1 x")).await;
+        setrange(&mut context, args("setrange key1 -1 x")).await;
+        setrange(&mut context, args("setrange key1 536870912 x")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "0\n\
+            2\n9..11 0..9 \
+            3\n11..12 0..9 \
+            6\n12..15 0..9 \
+            6\n10..11 0..9 \
+            err offset is out of range\n\
+            err string exceeds maximum allowed size\n"
+        );
+        assert_eq!(*value.lock().unwrap(), string("axc\0\0d"));
+
+        *value.lock().unwrap() = vec![200, 0, 0, 0, 0, 0, 0, 0, 1, 1];
+        append(&mut context, args("append key1 a")).await;
+        assert_eq!(context.take_response(), WRONGTYPE.to_vec());
+    }
+
+    #[tokio::test]
+    async fn strlen_and_getrange_should_inspect_value() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_inspect()
+            .with(eq(0), eq("key1"), always())
+            .returning(|_, _, inspection| inspection(Some(&string("Hello World"))));
+        redis_service
+            .expect_inspect()
+            .with(eq(0), eq("key2"), always())
+            .returning(|_, _, inspection| inspection(None));
+        let mut context = new_context(redis_service);
+
+        strlen(&mut context, args("strlen key1")).await;
+        strlen(&mut context, args("strlen key2")).await;
+        getrange(&mut context, args("getrange key1 0 4")).await;
+        getrange(&mut context, args("getrange key1 -5 -1")).await;
+        getrange(&mut context, args("getrange key1 6 100")).await;
+        getrange(&mut context, args("getrange key1 5 1")).await;
+        getrange(&mut context, args("getrange key2 0 -1")).await;
+        getrange(&mut context, args("getrange key1 0 x")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "11\n0\nHello\nWorld\nWorld\n\n\n\
+            err value is not an integer or out of range\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn lcs_should_be_handled() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key1"))
+            .returning(|_, _| Some(string("ohmytext")));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key2"))
+            .returning(|_, _| Some(string("mynewtext")));
+        redis_service.expect_get().with(eq(0), eq("key3")).returning(|_, _| None);
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("key4"))
+            .returning(|_, _| Some(string(&"a".repeat(4096))));
+        let mut context = new_context(redis_service);
+
+        lcs(&mut context, args("lcs key1 key2")).await;
+        lcs(&mut context, args("lcs key1 key2 LEN")).await;
+        lcs(&mut context, args("lcs key1 key2 idx")).await;
+        lcs(&mut context, args("lcs key1 key2 idx minmatchlen 4 withmatchlen")).await;
+        lcs(&mut context, args("lcs key1 key3")).await;
+        lcs(&mut context, args("lcs key1 key2 len idx")).await;
+        lcs(&mut context, args("lcs key4 key4")).await;
+
+        assert_eq!(
+            String::from_utf8(context.take_response()).unwrap(),
+            "mytext\n6\n\
+            4-7 5-8\n2-3 0-1\nlen 6\n\
+            4-7 5-8 4\nlen 6\n\
+            \n\
+            err If you want both the length and indexes, please just use IDX.\n\
+            err the strings are too long to compare\n"
+        );
+    }
+}
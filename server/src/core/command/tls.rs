@@ -0,0 +1,57 @@
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+
+pub fn reload(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(tls_service) = context.tls_service.clone() else {
+            context.reply(b"err the server is running without TLS\n");
+            return;
+        };
+        match tls_service.reload() {
+            Ok(()) => context.reply(b"reload ok\n"),
+            Err(err) => context.reply(format!("err TLS RELOAD failed: {}\n", err).as_bytes()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::new_test_context;
+    use crate::core::command::tls::reload;
+    use crate::core::redis::MockRedisService;
+    use crate::core::script::MockScriptService;
+    use crate::core::tls::MockTlsService;
+
+    #[tokio::test]
+    async fn reload_should_be_handled() {
+        let (mut context, _) = new_test_context(
+            MockRedisService::new(),
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        let args = || vec!["tls".to_owned(), "reload".to_owned()];
+
+        reload(&mut context, args()).await;
+        let mut tls_service = MockTlsService::new();
+        tls_service.expect_reload().once().returning(|| Ok(()));
+        tls_service
+            .expect_reload()
+            .once()
+            .returning(|| Err(io::Error::other("no private key in 'server.key'")));
+        let mut context = context.with_tls_service(Some(Arc::new(tls_service)));
+        reload(&mut context, args()).await;
+        reload(&mut context, args()).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err the server is running without TLS\n\
+            reload ok\n\
+            err TLS RELOAD failed: no private key in 'server.key'\n"
+                .to_vec()
+        );
+    }
+}
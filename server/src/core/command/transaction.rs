@@ -0,0 +1,169 @@
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandFuture;
+use crate::core::redis::DbKey;
+
+pub fn multi(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if context.session.is_in_transaction() {
+            context.reply(b"err MULTI calls can not be nested\n");
+        } else {
+            context.session.begin_transaction();
+            context.reply(b"multi ok\n");
+        }
+    })
+}
+
+pub fn exec(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Some(transaction) = context.session.take_transaction() else {
+            context.reply(b"err EXEC without MULTI\n");
+            return;
+        };
+        if transaction.aborted {
+            context.reply(b"err EXECABORT transaction discarded because of previous errors\n");
+        } else if is_watched_key_modified(context).await {
+            context.reply(b"nil\n");
+        } else if transaction.commands.is_empty() {
+            context.reply(b"empty\n");
+        } else {
+            let registry = context.registry.clone();
+            for args in transaction.commands.into_iter() {
+                registry.execute(context, args).await;
+            }
+        }
+        let watched_keys = context.session.take_watched_keys();
+        context.redis_service.unwatch(watched_keys).await;
+    })
+}
+
+pub fn discard(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        if context.session.take_transaction().is_none() {
+            context.reply(b"err DISCARD without MULTI\n");
+            return;
+        }
+        let watched_keys = context.session.take_watched_keys();
+        context.redis_service.unwatch(watched_keys).await;
+        context.reply(b"discard ok\n");
+    })
+}
+
+pub fn watch(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let db = context.session.db();
+        let keys: Vec<DbKey> = args[1..].iter().map(|key| (db, key.clone())).collect();
+        let versions = context.redis_service.watch(keys.clone()).await;
+        context.session.watch(keys, versions);
+        context.reply(b"watch ok\n");
+    })
+}
+
+pub fn unwatch(context: &mut CommandContext, _args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let watched_keys = context.session.take_watched_keys();
+        context.redis_service.unwatch(watched_keys).await;
+        context.reply(b"unwatch ok\n");
+    })
+}
+
+async fn is_watched_key_modified(context: &CommandContext) -> bool {
+    let (keys, versions): (Vec<DbKey>, Vec<u64>) =
+        context.session.watched_keys().iter().cloned().unzip();
+    if keys.is_empty() {
+        return false;
+    }
+    context.redis_service.versions(keys).await != versions
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::eq;
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::{args, new_test_context};
+    use crate::core::command::transaction::{discard, exec, multi, watch};
+    use crate::core::redis::{DbKey, MockRedisService};
+    use crate::core::script::MockScriptService;
+
+    #[tokio::test]
+    async fn exec_should_run_queued_commands() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_unwatch()
+            .with(eq(Vec::<DbKey>::new()))
+            .returning(|_| ());
+        let (mut context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+
+        exec(&mut context, args("exec")).await;
+        multi(&mut context, args("multi")).await;
+        multi(&mut context, args("multi")).await;
+        context.session.queue(args("ping"));
+        context.session.queue(args("ping a"));
+        exec(&mut context, args("exec")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err EXEC without MULTI\nmulti ok\nerr MULTI calls can not be nested\npong\na\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_should_abort_when_watched_key_is_modified() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_watch()
+            .with(eq(vec![(3, "a".to_owned())]))
+            .once()
+            .returning(|_| vec![1]);
+        redis_service
+            .expect_versions()
+            .with(eq(vec![(3, "a".to_owned())]))
+            .once()
+            .returning(|_| vec![2]);
+        redis_service
+            .expect_unwatch()
+            .with(eq(vec![(3, "a".to_owned())]))
+            .once()
+            .returning(|_| ());
+        let (mut context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+
+        context.session.select(3);
+        watch(&mut context, args("watch a")).await;
+        multi(&mut context, args("multi")).await;
+        context.session.queue(args("ping"));
+        exec(&mut context, args("exec")).await;
+
+        assert_eq!(context.take_response(), b"watch ok\nmulti ok\nnil\n".to_vec());
+        assert!(context.session.watched_keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn discard_should_drop_queued_commands() {
+        let mut redis_service = MockRedisService::new();
+        redis_service.expect_unwatch().once().returning(|_| ());
+        let (mut context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+
+        discard(&mut context, args("discard")).await;
+        multi(&mut context, args("multi")).await;
+        context.session.queue(args("ping"));
+        discard(&mut context, args("discard")).await;
+
+        assert_eq!(
+            context.take_response(),
+            b"err DISCARD without MULTI\nmulti ok\ndiscard ok\n".to_vec()
+        );
+        assert!(!context.session.is_in_transaction());
+    }
+}
@@ -0,0 +1,637 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::LevelFilter;
+
+use crate::core::buffer::OutputBufferLimits;
+use crate::core::eviction::{EvictionPolicy, MaxMemory, DEFAULT_MAXMEMORY_SAMPLES};
+use crate::core::notify::KeyspaceEvents;
+use crate::core::parser::parse_command;
+use crate::core::redis::DEFAULT_DATABASES;
+use crate::core::tls::{CertUser, ClientAuth};
+
+pub mod rewrite;
+pub mod service;
+
+pub const USAGE: &str = "\
+usage: server [config-file] [--<directive> <value> ...]
+       server gen-certs --out <dir> [--san <name> ...] [--client <name> ...]
+
+The directives are those of the config file, e.g. `--port 6380 --tls yes`,
+a directive given on the command line overrides the config file.
+
+gen-certs generates certificates for development: a CA, a server certificate
+for the --san names and IP addresses (localhost and 127.0.0.1 by default) and
+a certificate per --client name (client by default), each with its key.";
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Persistence {
+    #[default]
+    Files,
+    None,
+}
+
+impl FromStr for Persistence {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_lowercase().as_str() {
+            "files" => Ok(Persistence::Files),
+            "none" => Ok(Persistence::None),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Persistence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Persistence::Files => write!(f, "files"),
+            Persistence::None => write!(f, "none"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AppendFsync {
+    Always,
+    #[default]
+    No,
+}
+
+impl FromStr for AppendFsync {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppendFsync::Always => write!(f, "always"),
+            AppendFsync::No => write!(f, "no"),
+        }
+    }
+}
+
+/// The settings of the server, read from a config file in the style of `redis.conf`: one directive
+/// per line followed by its arguments, quoted if they contain white spaces, and `#` starting a comment line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub bind: String,
+    pub port: u16,
+    pub tls: bool,
+    pub tls_cert_file: Option<PathBuf>,
+    pub tls_key_file: Option<PathBuf>,
+    pub tls_ca_cert_file: Option<PathBuf>,
+    pub tls_auth_clients: ClientAuth,
+    pub tls_auth_clients_user: CertUser,
+    pub requirepass: String,
+    pub aclfile: Option<PathBuf>,
+    pub persistence: Persistence,
+    pub dir: PathBuf,
+    pub appendfsync: AppendFsync,
+    pub databases: usize,
+    pub maxclients: usize,
+    pub maxmemory: usize,
+    pub maxmemory_policy: EvictionPolicy,
+    pub maxmemory_samples: usize,
+    pub timeout: Duration,
+    pub lua_time_limit: Duration,
+    pub client_output_buffer_limit: OutputBufferLimits,
+    pub notify_keyspace_events: String,
+    pub loglevel: LevelFilter,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "localhost".to_owned(),
+            port: 6973,
+            tls: false,
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_ca_cert_file: None,
+            tls_auth_clients: ClientAuth::No,
+            tls_auth_clients_user: CertUser::Off,
+            requirepass: String::new(),
+            aclfile: None,
+            persistence: Persistence::Files,
+            dir: PathBuf::from("cache"),
+            appendfsync: AppendFsync::No,
+            databases: DEFAULT_DATABASES,
+            maxclients: 10000,
+            maxmemory: 0,
+            maxmemory_policy: EvictionPolicy::NoEviction,
+            maxmemory_samples: DEFAULT_MAXMEMORY_SAMPLES,
+            timeout: Duration::ZERO,
+            lua_time_limit: Duration::from_secs(5),
+            client_output_buffer_limit: OutputBufferLimits::default(),
+            notify_keyspace_events: String::new(),
+            loglevel: LevelFilter::Info,
+        }
+    }
+}
+
+impl Config {
+    pub const NAMES: [&'static str; 23] = [
+        "bind",
+        "port",
+        "tls",
+        "tls-cert-file",
+        "tls-key-file",
+        "tls-ca-cert-file",
+        "tls-auth-clients",
+        "tls-auth-clients-user",
+        "requirepass",
+        "aclfile",
+        "persistence",
+        "dir",
+        "appendfsync",
+        "databases",
+        "maxclients",
+        "maxmemory",
+        "maxmemory-policy",
+        "maxmemory-samples",
+        "timeout",
+        "lua-time-limit",
+        "client-output-buffer-limit",
+        "notify-keyspace-events",
+        "loglevel",
+    ];
+
+    const STARTUP_NAMES: [&'static str; 12] = [
+        "bind",
+        "port",
+        "tls",
+        "tls-cert-file",
+        "tls-key-file",
+        "tls-ca-cert-file",
+        "tls-auth-clients",
+        "tls-auth-clients-user",
+        "aclfile",
+        "persistence",
+        "dir",
+        "databases",
+    ];
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|err| {
+            io::Error::new(err.kind(), format!("can't read '{}': {}", path.display(), err))
+        })?;
+        let mut config = Self::default();
+        config.apply(&contents).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", path.display(), err))
+        })?;
+        Ok(config)
+    }
+
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> io::Result<(Self, Option<PathBuf>)> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let mut args = args.into_iter().peekable();
+        let path = args.next_if(|arg| !arg.starts_with("--")).map(PathBuf::from);
+        let mut config = match &path {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                return Err(invalid(format!("unexpected argument '{}'", arg)));
+            };
+            let mut values = Vec::new();
+            while let Some(value) = args.next_if(|arg| !arg.starts_with("--")) {
+                values.push(value);
+            }
+            config
+                .set(name, &values)
+                .map_err(|err| invalid(format!("--{}: {}", name, err)))?;
+        }
+        config.validate()?;
+        Ok((config, path))
+    }
+
+    pub fn apply(&mut self, contents: &str) -> Result<(), String> {
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some(args) = parse_command(line.as_bytes()) else {
+                return Err(format!("line {}: unbalanced quotes", index + 1));
+            };
+            self.set(&args[0], &args[1..])
+                .map_err(|err| format!("line {}: {}", index + 1, err))?;
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, name: &str, args: &[String]) -> Result<(), String> {
+        let name = name.to_lowercase();
+        let value = match args {
+            [value] => value.as_str(),
+            _ if name == "client-output-buffer-limit" => "",
+            _ => return Err(format!("'{}' expects one argument", name)),
+        };
+        match name.as_str() {
+            "bind" => {
+                if value.is_empty() {
+                    return Err("the bind address can't be empty".to_owned());
+                }
+                self.bind = value.to_owned();
+            }
+            "port" => self.port = parse(value, "a port number")?,
+            "tls" => self.tls = parse_bool(value)?,
+            "tls-cert-file" => self.tls_cert_file = parse_path(value),
+            "tls-key-file" => self.tls_key_file = parse_path(value),
+            "tls-ca-cert-file" => self.tls_ca_cert_file = parse_path(value),
+            "tls-auth-clients" => self.tls_auth_clients = parse(value, "yes, no or optional")?,
+            "tls-auth-clients-user" => self.tls_auth_clients_user = parse(value, "off or CN")?,
+            "requirepass" => self.requirepass = value.to_owned(),
+            "aclfile" => self.aclfile = parse_path(value),
+            "persistence" => self.persistence = parse(value, "files or none")?,
+            "dir" => {
+                self.dir = parse_path(value).ok_or("the data directory can't be empty")?;
+            }
+            "appendfsync" => self.appendfsync = parse(value, "always or no")?,
+            "databases" => {
+                self.databases = parse(value, "a positive integer")?;
+                if self.databases == 0 {
+                    return Err("invalid value '0', expected a positive integer".to_owned());
+                }
+            }
+            "maxclients" => {
+                self.maxclients = parse(value, "a positive integer")?;
+                if self.maxclients == 0 {
+                    return Err("invalid value '0', expected a positive integer".to_owned());
+                }
+            }
+            "maxmemory" => self.maxmemory = parse_memory(value)?,
+            "maxmemory-policy" => {
+                self.maxmemory_policy = parse(
+                    value,
+                    "noeviction, allkeys-lru, allkeys-lfu, allkeys-random, volatile-lru or volatile-ttl",
+                )?;
+            }
+            "maxmemory-samples" => {
+                self.maxmemory_samples = parse(value, "a positive integer")?;
+                if self.maxmemory_samples == 0 {
+                    return Err("invalid value '0', expected a positive integer".to_owned());
+                }
+            }
+            "timeout" => self.timeout = Duration::from_secs(parse(value, "seconds")?),
+            "lua-time-limit" => {
+                self.lua_time_limit = Duration::from_millis(parse(value, "milliseconds")?);
+            }
+            "client-output-buffer-limit" => {
+                let [hard_limit, soft_limit, soft_seconds] = args else {
+                    return Err(format!("'{}' expects <hard limit> <soft limit> <soft seconds>", name));
+                };
+                self.client_output_buffer_limit = OutputBufferLimits {
+                    hard_limit: parse_memory(hard_limit)?,
+                    soft_limit: parse_memory(soft_limit)?,
+                    soft_limit_duration: Duration::from_secs(parse(soft_seconds, "seconds")?),
+                };
+            }
+            "notify-keyspace-events" => {
+                if KeyspaceEvents::parse(value).is_none() {
+                    return Err(format!("invalid value '{}', expected flags among KEg$xeA", value));
+                }
+                self.notify_keyspace_events = value.to_owned();
+            }
+            "loglevel" => {
+                self.loglevel = parse(value, "off, error, warn, info, debug or trace")?;
+            }
+            _ => return Err(format!("unknown directive '{}'", name)),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let path = |path: &Option<PathBuf>| {
+            quote(&path.as_ref().map(|path| path.display().to_string()).unwrap_or_default())
+        };
+        let value = match name.to_lowercase().as_str() {
+            "bind" => quote(&self.bind),
+            "port" => self.port.to_string(),
+            "tls" => if self.tls { "yes" } else { "no" }.to_owned(),
+            "tls-cert-file" => path(&self.tls_cert_file),
+            "tls-key-file" => path(&self.tls_key_file),
+            "tls-ca-cert-file" => path(&self.tls_ca_cert_file),
+            "tls-auth-clients" => self.tls_auth_clients.to_string(),
+            "tls-auth-clients-user" => self.tls_auth_clients_user.to_string(),
+            "requirepass" => quote(&self.requirepass),
+            "aclfile" => path(&self.aclfile),
+            "persistence" => self.persistence.to_string(),
+            "dir" => quote(&self.dir.display().to_string()),
+            "appendfsync" => self.appendfsync.to_string(),
+            "databases" => self.databases.to_string(),
+            "maxclients" => self.maxclients.to_string(),
+            "maxmemory" => self.maxmemory.to_string(),
+            "maxmemory-policy" => self.maxmemory_policy.to_string(),
+            "maxmemory-samples" => self.maxmemory_samples.to_string(),
+            "timeout" => self.timeout.as_secs().to_string(),
+            "lua-time-limit" => self.lua_time_limit.as_millis().to_string(),
+            "client-output-buffer-limit" => {
+                let limits = &self.client_output_buffer_limit;
+                format!(
+                    "{} {} {}",
+                    limits.hard_limit,
+                    limits.soft_limit,
+                    limits.soft_limit_duration.as_secs()
+                )
+            }
+            "notify-keyspace-events" => quote(&self.notify_keyspace_events),
+            "loglevel" => self.loglevel.to_string().to_lowercase(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    pub fn is_mutable(name: &str) -> bool {
+        !Self::STARTUP_NAMES.contains(&name.to_lowercase().as_str())
+    }
+
+    pub fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name.eq_ignore_ascii_case("client-output-buffer-limit") {
+            let args: Vec<String> = value.split_whitespace().map(str::to_owned).collect();
+            return self.set(name, &args);
+        }
+        self.set(name, &[value.to_owned()])
+    }
+
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        if self.tls {
+            let mut files = vec![("tls-cert-file", &self.tls_cert_file), ("tls-key-file", &self.tls_key_file)];
+            if self.tls_auth_clients != ClientAuth::No {
+                files.push(("tls-ca-cert-file", &self.tls_ca_cert_file));
+            }
+            for (name, path) in files {
+                let Some(path) = path else { return invalid(format!("tls is enabled but '{}' is not set", name)); };
+                if !path.is_file() {
+                    return invalid(format!("{} '{}' is not a file", name, path.display()));
+                }
+            }
+        }
+        if self.persistence == Persistence::Files && self.dir.exists() && !self.dir.is_dir() {
+            return invalid(format!("dir '{}' is not a directory", self.dir.display()));
+        }
+        Ok(())
+    }
+
+    pub fn maxmemory(&self) -> MaxMemory {
+        MaxMemory {
+            limit: self.maxmemory,
+            policy: self.maxmemory_policy,
+            samples: self.maxmemory_samples,
+        }
+    }
+
+    pub fn keyspace_events(&self) -> KeyspaceEvents {
+        // validated when set
+        KeyspaceEvents::parse(&self.notify_keyspace_events).unwrap_or_default()
+    }
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return value.to_owned();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn parse<T: FromStr>(value: &str, expected: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}', expected {}", value, expected))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("invalid value '{}', expected yes or no", value)),
+    }
+}
+
+fn parse_path(value: &str) -> Option<PathBuf> {
+    (!value.is_empty()).then(|| PathBuf::from(value))
+}
+
+/// Parses a number of bytes with an optional unit like redis: `k`, `m` and `g` are powers of 1000,
+/// `kb`, `mb` and `gb` powers of 1024.
+fn parse_memory(value: &str) -> Result<usize, String> {
+    let lower = value.to_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit: usize = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid memory unit in '{}'", value)),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+        .ok_or_else(|| format!("invalid value '{}', expected a number of bytes", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use log::LevelFilter;
+    use tempdir::TempDir;
+
+    use crate::core::buffer::OutputBufferLimits;
+    use crate::core::command::context::args;
+    use crate::core::config::{parse_memory, AppendFsync, Config, Persistence};
+    use crate::core::eviction::EvictionPolicy;
+    use crate::core::tls::{CertUser, ClientAuth};
+
+    #[test]
+    fn test_apply() {
+        let mut config = Config::default();
+        let contents = "\
+# a comment
+bind 0.0.0.0
+  port 6380
+
+persistence none
+dir \"/var/lib/mini redis\"
+appendfsync always
+client-output-buffer-limit 64mb 16mb 30
+maxmemory 100mb
+maxmemory-policy allkeys-lru
+tls-auth-clients optional
+tls-auth-clients-user CN
+LOGLEVEL debug
+";
+        config.apply(contents).unwrap();
+
+        assert_eq!(config.bind, "0.0.0.0");
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.persistence, Persistence::None);
+        assert_eq!(config.dir, PathBuf::from("/var/lib/mini redis"));
+        assert_eq!(config.appendfsync, AppendFsync::Always);
+        assert_eq!(
+            config.client_output_buffer_limit,
+            OutputBufferLimits {
+                hard_limit: 64 * 1024 * 1024,
+                soft_limit: 16 * 1024 * 1024,
+                soft_limit_duration: Duration::from_secs(30),
+            }
+        );
+        assert_eq!(config.maxmemory, 100 * 1024 * 1024);
+        assert_eq!(config.maxmemory_policy, EvictionPolicy::AllKeysLru);
+        assert_eq!(config.tls_auth_clients, ClientAuth::Optional);
+        assert_eq!(config.tls_auth_clients_user, CertUser::CommonName);
+        assert_eq!(config.loglevel, LevelFilter::Debug);
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let mut config = Config::default();
+
+        assert_eq!(
+            config.apply("port 6380\nport 70000"),
+            Err("line 2: invalid value '70000', expected a port number".to_owned())
+        );
+        assert_eq!(
+            config.apply("tls true"),
+            Err("line 1: invalid value 'true', expected yes or no".to_owned())
+        );
+        assert_eq!(
+            config.apply("appendonly yes"),
+            Err("line 1: unknown directive 'appendonly'".to_owned())
+        );
+        assert_eq!(
+            config.apply("maxmemory-policy volatile-lfu"),
+            Err(
+                "line 1: invalid value 'volatile-lfu', expected noeviction, allkeys-lru, allkeys-lfu, \
+                allkeys-random, volatile-lru or volatile-ttl"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            config.apply("bind a b"),
+            Err("line 1: 'bind' expects one argument".to_owned())
+        );
+        assert_eq!(
+            config.apply("notify-keyspace-events Kz"),
+            Err("line 1: invalid value 'Kz', expected flags among KEg$xeA".to_owned())
+        );
+        assert_eq!(config.apply("dir \"a"), Err("line 1: unbalanced quotes".to_owned()));
+    }
+
+    #[test]
+    fn test_from_args() {
+        let temp_dir = TempDir::new("config-tests").unwrap();
+        let path = temp_dir.path().join("server.conf");
+        std::fs::write(&path, "port 6380\nmaxclients 10\n").unwrap();
+
+        let mut command_line = vec![path.display().to_string()];
+        command_line.extend(args("--maxclients 20 --lua-time-limit 100 --databases 4"));
+        let (config, config_path) = Config::from_args(command_line).unwrap();
+
+        assert_eq!(config_path, Some(path));
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.maxclients, 20);
+        assert_eq!(config.lua_time_limit, Duration::from_millis(100));
+        assert_eq!(config.databases, 4);
+
+        let err = Config::from_args(args("--maxclients 0")).unwrap_err();
+        assert_eq!(err.to_string(), "--maxclients: invalid value '0', expected a positive integer");
+        let err = Config::from_args(args("--databases 0")).unwrap_err();
+        assert_eq!(err.to_string(), "--databases: invalid value '0', expected a positive integer");
+        let err = Config::from_args(args("--port 1 2")).unwrap_err();
+        assert_eq!(err.to_string(), "--port: 'port' expects one argument");
+        let err = Config::from_args(args("--port 1 extra.conf")).unwrap_err();
+        assert_eq!(err.to_string(), "--port: 'port' expects one argument");
+        let err = Config::from_args(args("missing.conf")).unwrap_err();
+        assert!(err.to_string().starts_with("can't read 'missing.conf'"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let temp_dir = TempDir::new("config-tests").unwrap();
+        let cert_file = temp_dir.path().join("server.crt");
+        std::fs::write(&cert_file, "").unwrap();
+
+        let err = Config::from_args(args("--tls yes")).unwrap_err();
+        assert_eq!(err.to_string(), "tls is enabled but 'tls-cert-file' is not set");
+
+        let mut config = Config {
+            tls: true,
+            tls_cert_file: Some(cert_file.clone()),
+            tls_key_file: Some(temp_dir.path().join("server.key")),
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().ends_with("server.key' is not a file"));
+        config.tls_key_file = Some(cert_file.clone());
+        config.tls_auth_clients = ClientAuth::Optional;
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.to_string(), "tls is enabled but 'tls-ca-cert-file' is not set");
+
+        config.tls = false;
+        config.dir = cert_file;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().ends_with("server.crt' is not a directory"));
+    }
+
+    #[test]
+    fn test_get() {
+        let mut config = Config::default();
+        config
+            .apply("dir \"/var/lib/mini redis\"\nloglevel warn\ntimeout 30\nrequirepass \"open sesame\"\nmaxmemory 1k")
+            .unwrap();
+
+        assert_eq!(config.get("PORT"), Some("6973".to_owned()));
+        assert_eq!(config.get("dir"), Some("\"/var/lib/mini redis\"".to_owned()));
+        assert_eq!(config.get("tls-cert-file"), Some("\"\"".to_owned()));
+        assert_eq!(config.get("loglevel"), Some("warn".to_owned()));
+        assert_eq!(config.get("timeout"), Some("30".to_owned()));
+        assert_eq!(config.get("requirepass"), Some("\"open sesame\"".to_owned()));
+        assert_eq!(
+            config.get("client-output-buffer-limit"),
+            Some("33554432 8388608 60".to_owned())
+        );
+        assert_eq!(config.get("maxmemory"), Some("1000".to_owned()));
+        assert_eq!(config.get("maxmemory-policy"), Some("noeviction".to_owned()));
+        assert_eq!(config.get("appendonly"), None);
+
+        // every directive reads back as it is written
+        let mut read_back = Config::default();
+        for name in Config::NAMES {
+            let line = format!("{} {}", name, config.get(name).unwrap());
+            read_back.apply(&line).unwrap();
+        }
+        assert_eq!(read_back, config);
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("100"), Ok(100));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("2KB"), Ok(2048));
+        assert_eq!(parse_memory("1gb"), Ok(1024 * 1024 * 1024));
+        assert!(parse_memory("1tb").is_err());
+        assert!(parse_memory("mb").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::core::config::Config;
+use crate::core::parser::parse_command;
+
+const GENERATED_COMMENT: &str = "# Generated by CONFIG REWRITE";
+
+/// Rewrites the contents of a config file with the current settings: comments and blank lines are kept,
+/// every directive is updated where it first appears and its repetitions are dropped. The directives
+/// the file doesn't contain are appended, unless they keep their default value.
+pub fn rewrite(contents: &str, config: &Config) -> String {
+    let mut written: Vec<&str> = Vec::new();
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            lines.push(line.to_owned());
+            continue;
+        }
+        let name = parse_command(trimmed.as_bytes())
+            .and_then(|args| args.into_iter().next())
+            .map(|name| name.to_lowercase());
+        let Some(name) = name.and_then(|name| Config::NAMES.into_iter().find(|known| *known == name)) else {
+            lines.push(line.to_owned());
+            continue;
+        };
+        if !written.contains(&name) {
+            written.push(name);
+            lines.push(directive(config, name));
+        }
+    }
+
+    let default = Config::default();
+    let missing: Vec<String> = Config::NAMES
+        .into_iter()
+        .filter(|name| !written.contains(name) && config.get(name) != default.get(name))
+        .map(|name| directive(config, name))
+        .collect();
+    if !missing.is_empty() {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(GENERATED_COMMENT.to_owned());
+        lines.extend(missing);
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+fn directive(config: &Config, name: &str) -> String {
+    format!("{} {}", name, config.get(name).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::config::rewrite::rewrite;
+    use crate::core::config::Config;
+
+    #[test]
+    fn test_rewrite() {
+        let contents = "\
+# the port
+port 6380
+  # indented comment
+
+maxclients 10
+maxclients 20
+";
+        let mut config = Config::default();
+        config.apply(contents).unwrap();
+        config.maxclients = 30;
+        config.loglevel = log::LevelFilter::Debug;
+
+        assert_eq!(
+            rewrite(contents, &config),
+            "\
+# the port
+port 6380
+  # indented comment
+
+maxclients 30
+
+# Generated by CONFIG REWRITE
+loglevel debug
+"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_unchanged() {
+        let contents = "port 6380\n# tls no\n";
+        let mut config = Config::default();
+        config.apply(contents).unwrap();
+
+        assert_eq!(rewrite(contents, &config), contents);
+    }
+}
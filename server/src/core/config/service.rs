@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::automock;
+use tokio::{fs, io};
+
+use crate::core::config::rewrite::rewrite;
+use crate::core::config::Config;
+use crate::core::file::replace_file;
+use crate::core::glob::glob_match;
+
+pub trait ConfigListener: Send + Sync {
+    fn apply_config(&self, config: &Config);
+
+    fn reset_stats(&self) {}
+}
+
+impl<F> ConfigListener for F
+where
+    F: Fn(&Config) + Send + Sync,
+{
+    fn apply_config(&self, config: &Config) {
+        self(config)
+    }
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait ConfigService: Send + Sync {
+    fn get(&self, pattern: String) -> Vec<(String, String)>;
+
+    fn set(&self, directives: Vec<(String, String)>) -> Result<(), String>;
+
+    async fn rewrite(&self) -> io::Result<()>;
+
+    fn reset_stats(&self);
+}
+
+pub struct MyConfigService {
+    config: RwLock<Config>,
+    path: Option<PathBuf>,
+    listeners: Vec<Arc<dyn ConfigListener>>,
+}
+
+impl MyConfigService {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config: RwLock::new(config),
+            path: None,
+            listeners: Vec::new(),
+        }
+    }
+
+    pub fn with_path(mut self, path: PathBuf) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    pub fn with_listener(mut self, listener: Arc<dyn ConfigListener>) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+}
+
+#[async_trait]
+impl ConfigService for MyConfigService {
+    fn get(&self, pattern: String) -> Vec<(String, String)> {
+        let config = self.config.read().unwrap();
+        let pattern = pattern.to_lowercase();
+        Config::NAMES
+            .into_iter()
+            .filter(|name| glob_match(pattern.as_bytes(), name.as_bytes()))
+            .filter_map(|name| Some((name.to_owned(), config.get(name)?)))
+            .collect()
+    }
+
+    fn set(&self, directives: Vec<(String, String)>) -> Result<(), String> {
+        let mut config = self.config.write().unwrap();
+        let mut new_config = config.clone();
+        for (name, value) in directives.iter() {
+            if !Config::is_mutable(name) {
+                return Err(format!("'{}' can't be changed while the server runs", name));
+            }
+            new_config.set_value(name, value)?;
+        }
+        new_config.validate().map_err(|err| err.to_string())?;
+
+        *config = new_config;
+        for listener in self.listeners.iter() {
+            listener.apply_config(&config);
+        }
+        Ok(())
+    }
+
+    async fn rewrite(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the server is running without a config file",
+            ));
+        };
+        let contents = match fs::read_to_string(path).await {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            contents => contents?,
+        };
+        let contents = rewrite(&contents, &self.config.read().unwrap());
+        replace_file(path, contents.as_bytes(), false).await
+    }
+
+    fn reset_stats(&self) {
+        for listener in self.listeners.iter() {
+            listener.reset_stats();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use crate::core::config::service::{ConfigService, MyConfigService};
+    use crate::core::config::Config;
+
+    fn directives(directives: &[(&str, &str)]) -> Vec<(String, String)> {
+        directives
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn get_should_match_pattern() {
+        let instance = MyConfigService::new(Config::default());
+
+        assert_eq!(
+            instance.get("TLS*".to_owned()),
+            directives(&[
+                ("tls", "no"),
+                ("tls-cert-file", "\"\""),
+                ("tls-key-file", "\"\""),
+                ("tls-ca-cert-file", "\"\""),
+                ("tls-auth-clients", "no"),
+                ("tls-auth-clients-user", "off"),
+            ])
+        );
+        assert!(instance.get("xxx".to_owned()).is_empty());
+    }
+
+    #[test]
+    fn set_should_notify_listeners() {
+        let notified = Arc::new(AtomicUsize::new(0));
+        let listener_notified = notified.clone();
+        let instance = MyConfigService::new(Config::default()).with_listener(Arc::new(
+            move |config: &Config| {
+                assert_eq!(config.lua_time_limit, Duration::from_millis(100));
+                listener_notified.fetch_add(1, Ordering::Relaxed);
+            },
+        ));
+
+        let result = instance.set(directives(&[
+            ("lua-time-limit", "100"),
+            ("client-output-buffer-limit", "1mb 0 0"),
+        ]));
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(notified.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            instance.get("client-output-buffer-limit".to_owned()),
+            directives(&[("client-output-buffer-limit", "1048576 0 0")])
+        );
+    }
+
+    #[test]
+    fn set_should_change_nothing_on_error() {
+        let instance = MyConfigService::new(Config::default());
+
+        let result = instance.set(directives(&[("maxclients", "5"), ("timeout", "-1")]));
+        assert_eq!(result, Err("invalid value '-1', expected seconds".to_owned()));
+        let result = instance.set(directives(&[("port", "6380")]));
+        assert_eq!(result, Err("'port' can't be changed while the server runs".to_owned()));
+        let result = instance.set(directives(&[("appendonly", "yes")]));
+        assert_eq!(result, Err("unknown directive 'appendonly'".to_owned()));
+
+        assert_eq!(
+            instance.get("maxclients".to_owned()),
+            directives(&[("maxclients", "10000")])
+        );
+    }
+
+    #[tokio::test]
+    async fn rewrite_should_write_config_file() {
+        let temp_dir = TempDir::new("config-service-tests").unwrap();
+        let path = temp_dir.path().join("server.conf");
+        std::fs::write(&path, "# clients\nmaxclients 10\n").unwrap();
+        let instance = MyConfigService::new(Config::default()).with_path(path.clone());
+
+        instance.set(directives(&[("maxclients", "20")])).unwrap();
+        instance.rewrite().await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "# clients\nmaxclients 20\n");
+        let err = MyConfigService::new(Config::default()).rewrite().await.unwrap_err();
+        assert_eq!(err.to_string(), "the server is running without a config file");
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::core::eviction::{lfu_decay, lfu_increment, random, LFU_INIT_VAL};
+
+/// The memory taken by a key besides its name and its value: its entry in the map, its slot among the sampled
+/// keys, its place in the scan order and the reference counts of its name.
+const ENTRY_OVERHEAD: usize = size_of::<(Arc<str>, Entry)>()
+    + size_of::<Arc<str>>()
+    + size_of::<(u64, Vec<Arc<str>>)>()
+    + size_of::<Arc<str>>()
+    + 2 * size_of::<usize>();
+const EXPIRY_OVERHEAD: usize = size_of::<(Arc<str>, Expiry)>() + size_of::<Arc<str>>();
+
+#[derive(Debug)]
+pub struct Entry {
+    value: Vec<u8>,
+    slot: usize,
+    access: AtomicU64,
+    frequency: AtomicU8,
+}
+
+impl Entry {
+    pub fn value(&self) -> &Vec<u8> {
+        &self.value
+    }
+
+    pub fn idle(&self, clock: u64) -> u64 {
+        clock.saturating_sub(self.access.load(Ordering::Relaxed))
+    }
+
+    pub fn frequency(&self, clock: u64) -> u8 {
+        lfu_decay(self.frequency.load(Ordering::Relaxed), self.idle(clock))
+    }
+
+    fn access(&self, clock: u64) {
+        let frequency = lfu_increment(self.frequency(clock));
+        self.frequency.store(frequency, Ordering::Relaxed);
+        self.access.store(clock, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct Expiry {
+    deadline: Instant,
+    slot: usize,
+}
+
+/// The keys of a numbered database, selected by the clients with `SELECT`. It keeps the memory they take,
+/// a list of the keys to sample them in constant time and their scan order to scan them from a cursor.
+#[derive(Debug, Default)]
+pub struct Db {
+    data: HashMap<Arc<str>, Entry>,
+    expires: HashMap<Arc<str>, Expiry>,
+    keys: Vec<Arc<str>>,
+    volatile_keys: Vec<Arc<str>>,
+    scan_order: BTreeMap<u64, Vec<Arc<str>>>,
+    used_memory: usize,
+}
+
+impl Db {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn used_memory(&self) -> usize {
+        self.used_memory
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Vec<u8>> {
+        self.data.get(key).map(Entry::value)
+    }
+
+    pub fn entry(&self, key: &str) -> Option<&Entry> {
+        self.data.get(key)
+    }
+
+    pub fn access(&self, key: &str, clock: u64) {
+        if let Some(entry) = self.data.get(key) {
+            entry.access(clock);
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|key| key.as_ref())
+    }
+
+    pub fn insert(&mut self, key: &str, value: Vec<u8>, clock: u64) {
+        if let Some(entry) = self.data.get_mut(key) {
+            self.used_memory = self.used_memory - entry.value.len() + value.len();
+            entry.value = value;
+            entry.access(clock);
+            return;
+        }
+        let key: Arc<str> = Arc::from(key);
+        self.used_memory += ENTRY_OVERHEAD + key.len() + value.len();
+        let entry = Entry {
+            value,
+            slot: self.keys.len(),
+            access: AtomicU64::new(clock),
+            frequency: AtomicU8::new(LFU_INIT_VAL),
+        };
+        self.keys.push(key.clone());
+        self.scan_order.entry(scan_hash(&key)).or_default().push(key.clone());
+        self.data.insert(key, entry);
+    }
+
+    pub fn update<R>(&mut self, key: &str, clock: u64, update: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+        let Some(entry) = self.data.get_mut(key) else {
+            let mut value = Vec::new();
+            let result = update(&mut value);
+            if !value.is_empty() {
+                self.insert(key, value, clock);
+            }
+            return result;
+        };
+        let length = entry.value.len();
+        let result = update(&mut entry.value);
+        self.used_memory = self.used_memory - length + entry.value.len();
+        entry.access(clock);
+        result
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.clear_deadline(key);
+        let entry = self.data.remove(key)?;
+        self.used_memory -= ENTRY_OVERHEAD + key.len() + entry.value.len();
+        self.keys.swap_remove(entry.slot);
+        if let Some(moved) = self.keys.get(entry.slot) {
+            self.data.get_mut(moved).unwrap().slot = entry.slot;
+        }
+        let hash = scan_hash(key);
+        let bucket = self.scan_order.get_mut(&hash).unwrap();
+        bucket.retain(|other| other.as_ref() != key);
+        if bucket.is_empty() {
+            self.scan_order.remove(&hash);
+        }
+        Some(entry.value)
+    }
+
+    /// Returns the keys from a cursor on in the order of their `scan_hash`, at least `count` unless there are fewer,
+    /// and the cursor following them, 0 if there is no key after. The keys sharing a hash are returned together.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&str>) {
+        let mut keys = Vec::new();
+        let mut buckets = self.scan_order.range(cursor..).peekable();
+        while let Some((hash, bucket)) = buckets.next() {
+            keys.extend(bucket.iter().map(|key| key.as_ref()));
+            if keys.len() >= count.max(1) && buckets.peek().is_some() {
+                // a key can't have the hash u64::MAX + 1, there is a bucket after this one
+                return (hash + 1, keys);
+            }
+        }
+        (0, keys)
+    }
+
+    pub fn deadline(&self, key: &str) -> Option<Instant> {
+        self.expires.get(key).map(|expiry| expiry.deadline)
+    }
+
+    pub fn is_expired(&self, key: &str, now: Instant) -> bool {
+        self.deadline(key).is_some_and(|deadline| deadline <= now)
+    }
+
+    pub fn set_deadline(&mut self, key: &str, deadline: Instant) {
+        if let Some(expiry) = self.expires.get_mut(key) {
+            expiry.deadline = deadline;
+            return;
+        }
+        let Some((key, _)) = self.data.get_key_value(key) else { return; };
+        let key = key.clone();
+        self.used_memory += EXPIRY_OVERHEAD;
+        let expiry = Expiry {
+            deadline,
+            slot: self.volatile_keys.len(),
+        };
+        self.volatile_keys.push(key.clone());
+        self.expires.insert(key, expiry);
+    }
+
+    pub fn clear_deadline(&mut self, key: &str) {
+        let Some(expiry) = self.expires.remove(key) else { return; };
+        self.used_memory -= EXPIRY_OVERHEAD;
+        self.volatile_keys.swap_remove(expiry.slot);
+        if let Some(moved) = self.volatile_keys.get(expiry.slot) {
+            self.expires.get_mut(moved).unwrap().slot = expiry.slot;
+        }
+    }
+
+    pub fn sample(&self, count: usize, volatile: bool) -> Vec<&str> {
+        let keys = if volatile { &self.volatile_keys } else { &self.keys };
+        if keys.is_empty() {
+            return Vec::new();
+        }
+        (0..count)
+            .map(|_| keys[(random() % keys.len() as u64) as usize].as_ref())
+            .collect()
+    }
+}
+
+/// The FNV-1a hash of a key, the order of `Db::scan`. It never changes so that a cursor stays valid.
+fn scan_hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::core::db::{scan_hash, Db, ENTRY_OVERHEAD, EXPIRY_OVERHEAD};
+
+    #[test]
+    fn used_memory_should_follow_changes() {
+        let mut db = Db::default();
+
+        db.insert("john", vec![0u8; 100], 0);
+        db.insert("jane", vec![0u8; 10], 0);
+        assert_eq!(db.used_memory(), 2 * ENTRY_OVERHEAD + 8 + 110);
+
+        db.insert("john", vec![0u8; 50], 0);
+        db.set_deadline("jane", Instant::now());
+        assert_eq!(db.used_memory(), 2 * ENTRY_OVERHEAD + EXPIRY_OVERHEAD + 8 + 60);
+
+        assert_eq!(db.remove("jane"), Some(vec![0u8; 10]));
+        assert_eq!(db.used_memory(), ENTRY_OVERHEAD + 4 + 50);
+        db.remove("john");
+        assert_eq!(db.used_memory(), 0);
+        assert!(db.is_empty());
+    }
+
+    #[test]
+    fn update_should_change_value_in_place() {
+        let mut db = Db::default();
+
+        db.update("john", 0, |value| assert!(value.is_empty()));
+        assert!(db.is_empty());
+        db.update("john", 0, |value| value.extend([1u8, 2u8]));
+        db.update("john", 0, |value| value.push(3u8));
+
+        assert_eq!(db.get("john"), Some(&vec![1u8, 2u8, 3u8]));
+        assert_eq!(db.used_memory(), ENTRY_OVERHEAD + 4 + 3);
+    }
+
+    #[test]
+    fn sample_should_draw_existing_keys() {
+        let mut db = Db::default();
+        assert!(db.sample(5, false).is_empty());
+        for index in 0..10 {
+            db.insert(&format!("key{}", index), vec![1u8], 0);
+        }
+        db.set_deadline("key3", Instant::now() + Duration::from_secs(60));
+        db.remove("key0");
+        db.remove("key5");
+
+        let sampled = db.sample(100, false);
+        assert_eq!(sampled.len(), 100);
+        assert!(sampled.iter().all(|key| db.contains_key(key)));
+        assert!(db.sample(10, true).iter().all(|key| *key == "key3"));
+        let mut keys: Vec<&str> = db.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["key1", "key2", "key3", "key4", "key6", "key7", "key8", "key9"]);
+    }
+
+    #[test]
+    fn scan_should_return_keys_from_cursor() {
+        let mut db = Db::default();
+        for index in 0..10 {
+            db.insert(&format!("key{}", index), vec![1u8], 0);
+        }
+        db.remove("key4");
+        let mut expected: Vec<&str> = db.keys().collect();
+        expected.sort_by_key(|key| scan_hash(key));
+
+        let (cursor, first) = db.scan(0, 4);
+        assert_eq!(first, expected[..4].to_vec());
+        assert_eq!(cursor, scan_hash(first[3]) + 1);
+        let (cursor, second) = db.scan(cursor, 4);
+        assert_eq!(second, expected[4..8].to_vec());
+        assert_eq!(db.scan(cursor, 4), (0, expected[8..].to_vec()));
+        assert_eq!(db.scan(0, 9), (0, expected));
+    }
+
+    #[test]
+    fn access_should_update_idle_time() {
+        let mut db = Db::default();
+        db.insert("john", vec![1u8], 1000);
+
+        assert_eq!(db.entry("john").unwrap().idle(1500), 500);
+        db.access("john", 1400);
+        assert_eq!(db.entry("john").unwrap().idle(1500), 100);
+    }
+}
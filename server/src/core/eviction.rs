@@ -0,0 +1,142 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The LFU counter of a new key, so that it isn't evicted before it has a chance to be accessed.
+pub const LFU_INIT_VAL: u8 = 5;
+const LFU_LOG_FACTOR: u64 = 10;
+const LFU_DECAY_PERIOD: u64 = 60 * 1000;
+
+pub const DEFAULT_MAXMEMORY_SAMPLES: usize = 5;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MaxMemory {
+    pub limit: usize,
+    pub policy: EvictionPolicy,
+    pub samples: usize,
+}
+
+impl Default for MaxMemory {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            policy: EvictionPolicy::NoEviction,
+            samples: DEFAULT_MAXMEMORY_SAMPLES,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EvictionPolicy {
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    VolatileLru,
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    pub fn is_volatile(&self) -> bool {
+        matches!(self, EvictionPolicy::VolatileLru | EvictionPolicy::VolatileTtl)
+    }
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_lowercase().as_str() {
+            "noeviction" => Ok(EvictionPolicy::NoEviction),
+            "allkeys-lru" => Ok(EvictionPolicy::AllKeysLru),
+            "allkeys-lfu" => Ok(EvictionPolicy::AllKeysLfu),
+            "allkeys-random" | "random" => Ok(EvictionPolicy::AllKeysRandom),
+            "volatile-lru" => Ok(EvictionPolicy::VolatileLru),
+            "volatile-ttl" => Ok(EvictionPolicy::VolatileTtl),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for EvictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvictionPolicy::NoEviction => write!(f, "noeviction"),
+            EvictionPolicy::AllKeysLru => write!(f, "allkeys-lru"),
+            EvictionPolicy::AllKeysLfu => write!(f, "allkeys-lfu"),
+            EvictionPolicy::AllKeysRandom => write!(f, "allkeys-random"),
+            EvictionPolicy::VolatileLru => write!(f, "volatile-lru"),
+            EvictionPolicy::VolatileTtl => write!(f, "volatile-ttl"),
+        }
+    }
+}
+
+/// Returns a pseudo random number, good enough to sample keys but not for anything secret.
+pub fn random() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let mut state = STATE.load(Ordering::Relaxed);
+    if state == 0 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        state = nanos | 1;
+    }
+    // xorshift64
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    STATE.store(state, Ordering::Relaxed);
+    state
+}
+
+pub fn lfu_decay(counter: u8, idle_millis: u64) -> u8 {
+    let periods = idle_millis / LFU_DECAY_PERIOD;
+    counter.saturating_sub(periods.min(u8::MAX as u64) as u8)
+}
+
+/// Increments a LFU counter with a probability decreasing as it grows, so that 255 stands for about
+/// a million accesses.
+pub fn lfu_increment(counter: u8) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let base = counter.saturating_sub(LFU_INIT_VAL) as u64;
+    // the probability 1 / (base * LFU_LOG_FACTOR + 1)
+    if random().is_multiple_of(base * LFU_LOG_FACTOR + 1) {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::eviction::{lfu_decay, lfu_increment, EvictionPolicy, LFU_INIT_VAL};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("ALLKEYS-LRU".parse(), Ok(EvictionPolicy::AllKeysLru));
+        assert_eq!("random".parse(), Ok(EvictionPolicy::AllKeysRandom));
+        assert_eq!("volatile-lfu".parse::<EvictionPolicy>(), Err(()));
+        assert_eq!(EvictionPolicy::VolatileTtl.to_string(), "volatile-ttl");
+        assert!(EvictionPolicy::VolatileLru.is_volatile());
+        assert!(!EvictionPolicy::AllKeysLfu.is_volatile());
+    }
+
+    #[test]
+    fn test_lfu_counter() {
+        let mut counter = LFU_INIT_VAL;
+        for _ in 0..1000 {
+            counter = lfu_increment(counter);
+        }
+        // logarithmic: a thousand accesses are far from saturating it
+        assert!(counter > LFU_INIT_VAL && counter < 100, "{}", counter);
+        assert_eq!(lfu_increment(u8::MAX), u8::MAX);
+
+        assert_eq!(lfu_decay(10, 59 * 1000), 10);
+        assert_eq!(lfu_decay(10, 3 * 60 * 1000), 7);
+        assert_eq!(lfu_decay(10, u64::MAX), 0);
+    }
+}
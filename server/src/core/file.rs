@@ -0,0 +1,60 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::{fs, io};
+
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Replaces the contents of a file at once: they are written to a temporary file next to it which is then renamed,
+/// so that a failure never leaves a truncated file. With `sync` the contents reach the disk before the rename.
+pub async fn replace_file(path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+    let temp_path = temp_path(path);
+    if let Err(err) = write_file(&temp_path, contents, sync).await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(err);
+    }
+    fs::rename(&temp_path, path).await
+}
+
+async fn write_file(path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+    let mut file = File::create(path).await?;
+    file.write_all(contents).await?;
+    if sync {
+        file.sync_all().await
+    } else {
+        // tokio writes the file in the background, it is done once flushed
+        file.flush().await
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.to_owned().into_os_string();
+    temp_path.push(TEMP_SUFFIX);
+    PathBuf::from(temp_path)
+}
+
+pub fn is_temp_file(file_name: &OsStr) -> bool {
+    file_name.to_str().is_some_and(|file_name| file_name.ends_with(TEMP_SUFFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use tokio::fs;
+
+    use crate::core::file::replace_file;
+
+    #[tokio::test]
+    async fn replace_file_should_replace_contents() {
+        let temp_dir = TempDir::new("file-tests").unwrap();
+        let path = temp_dir.path().join("a");
+
+        replace_file(&path, b"hello world", false).await.unwrap();
+        replace_file(&path, b"hi", true).await.unwrap();
+
+        assert_eq!(fs::read(&path).await.unwrap(), b"hi".to_vec());
+        assert!(!temp_dir.path().join("a.tmp").exists());
+    }
+}
@@ -0,0 +1,137 @@
+/// Matches a text against a glob-style pattern:
+/// `*` matches any sequence, `?` matches a single byte, `[abc]`, `[^abc]` and `[a-z]` match a set of
+/// bytes and `\` escapes the next byte.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0usize, 0usize);
+    // the position of the last `*` in the pattern and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                b'[' => {
+                    if let Some((matched, next)) = match_class(pattern, p, text[t]) {
+                        if matched {
+                            p = next;
+                            t += 1;
+                            continue;
+                        }
+                    } else if text[t] == b'[' {
+                        // an unterminated class is a literal `[`
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == text[t] {
+                        p += 2;
+                        t += 1;
+                        continue;
+                    }
+                }
+                byte => {
+                    if byte == text[t] {
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+        let Some((star, star_t)) = backtrack else { return false; };
+        p = star + 1;
+        t = star_t + 1;
+        backtrack = Some((star, star_t + 1));
+    }
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
+
+fn match_class(pattern: &[u8], start: usize, byte: u8) -> Option<(bool, usize)> {
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while p < pattern.len() && (pattern[p] != b']' || first) {
+        first = false;
+        let mut low = pattern[p];
+        if low == b'\\' && p + 1 < pattern.len() {
+            p += 1;
+            low = pattern[p];
+        }
+        if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let high = pattern[p + 2];
+            let (low, high) = if low <= high { (low, high) } else { (high, low) };
+            matched |= low <= byte && byte <= high;
+            p += 3;
+        } else {
+            matched |= low == byte;
+            p += 1;
+        }
+    }
+    if p >= pattern.len() {
+        return None;
+    }
+    Some((matched != negate, p + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::glob::glob_match;
+
+    #[test]
+    fn test_glob_match_literal() {
+        assert!(glob_match(b"hello", b"hello"));
+        assert!(!glob_match(b"hello", b"hell"));
+        assert!(!glob_match(b"hell", b"hello"));
+        assert!(glob_match(b"", b""));
+    }
+
+    #[test]
+    fn test_glob_match_star() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"anything"));
+        assert!(glob_match(b"h*o", b"hello"));
+        assert!(glob_match(b"cache:*", b"cache:user:1"));
+        assert!(!glob_match(b"cache:*", b"other:1"));
+        assert!(glob_match(b"*b*b*", b"abcabc"));
+        assert!(!glob_match(b"*b*b*b*", b"abcabc"));
+    }
+
+    #[test]
+    fn test_glob_match_question_mark() {
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(glob_match(b"h?llo", b"hallo"));
+        assert!(!glob_match(b"h?llo", b"hllo"));
+    }
+
+    #[test]
+    fn test_glob_match_class() {
+        assert!(glob_match(b"h[ae]llo", b"hello"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[ae]llo", b"hillo"));
+        assert!(glob_match(b"h[^e]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-b]llo", b"hbllo"));
+        assert!(!glob_match(b"h[a-b]llo", b"hcllo"));
+        assert!(glob_match(b"[", b"["));
+    }
+
+    #[test]
+    fn test_glob_match_escape() {
+        assert!(glob_match(b"h\\*llo", b"h*llo"));
+        assert!(!glob_match(b"h\\*llo", b"hello"));
+    }
+}
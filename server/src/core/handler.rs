@@ -4,12 +4,20 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use tokio::io;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 
+use crate::core::auth::{AuthService, MyAuthService};
 use crate::core::broker::BrokerService;
-use crate::core::redis::RedisService;
-use crate::core::tlv::{from_tlv, TLVType, to_tlv};
+use crate::core::buffer::OutputBufferSender;
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::CommandRegistry;
+use crate::core::config::service::{ConfigService, MyConfigService};
+use crate::core::config::Config;
+use crate::core::module::Module;
+use crate::core::redis::{DbKey, RedisService};
+use crate::core::script::{MyScriptService, ScriptService};
+use crate::core::session::DEFAULT_USER;
+use crate::core::tls::{CertUser, ClientIdentity, TlsService};
 
 #[async_trait]
 pub trait HandlerService: Send + Sync {
@@ -19,31 +27,14 @@ pub trait HandlerService: Send + Sync {
         &self,
         writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
     ) -> io::Result<()>;
-    async fn handle_ping_cmd(&self, writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>);
-    async fn handle_ping_value_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        value: Vec<u8>,
-    );
-    async fn handle_get_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        key: &str,
-    );
-    async fn handle_set_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        key: String,
-        value: Vec<u8>,
-    );
-    async fn handle_subscribe_cmd(
+
+    fn new_command_context(
         &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        sender: UnboundedSender<Vec<u8>>,
-        topic: SocketAddr,
-        topic0: String,
-    );
-    async fn handle_other_cmd(&self, writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>);
+        sender: OutputBufferSender,
+        socket_addr: SocketAddr,
+        identity: Option<ClientIdentity>,
+    ) -> CommandContext;
+    async fn handle_cmd(&self, context: &mut CommandContext, args: Vec<String>);
 
     async fn handle_publish_cmd(&self, publisher_addr: SocketAddr, message: Vec<u8>);
     async fn handle_unsubscribe_cmd(
@@ -53,11 +44,25 @@ pub trait HandlerService: Send + Sync {
     );
 
     async fn is_subscription_connection(&self, socket_addr: SocketAddr) -> bool;
+
+    async fn handle_active_expiry(&self);
+
+    /// Returns the lock serializing commands: a command holds it shared while a transaction holds it
+    /// exclusively so that no other client sees its intermediate state.
+    fn command_lock(&self) -> Arc<RwLock<()>>;
+
+    async fn unwatch_keys(&self, keys: Vec<DbKey>);
 }
 
 pub struct MyHandlerService {
     redis_service: Arc<dyn RedisService>,
     broker_service: Arc<dyn BrokerService>,
+    script_service: Arc<dyn ScriptService>,
+    config_service: Arc<dyn ConfigService>,
+    auth_service: Arc<dyn AuthService>,
+    tls_service: Option<Arc<dyn TlsService>>,
+    registry: Arc<CommandRegistry>,
+    cert_user: CertUser,
 }
 
 impl MyHandlerService {
@@ -68,91 +73,99 @@ impl MyHandlerService {
         Self {
             redis_service,
             broker_service,
+            script_service: Arc::new(MyScriptService::new()),
+            config_service: Arc::new(MyConfigService::new(Config::default())),
+            auth_service: Arc::new(MyAuthService::new()),
+            tls_service: None,
+            registry: Arc::new(CommandRegistry::new().with_builtin_commands()),
+            cert_user: CertUser::Off,
         }
     }
-}
 
-#[async_trait]
-impl HandlerService for MyHandlerService {
-    async fn handle_cache_recovering(&self) -> io::Result<()> {
-        self.redis_service.read_cache().await
+    pub fn with_script_service(mut self, script_service: Arc<dyn ScriptService>) -> Self {
+        self.script_service = script_service;
+        self
     }
 
-    async fn handle_exit_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-    ) -> io::Result<()> {
-        writer.lock().await.shutdown().await
+    pub fn with_config_service(mut self, config_service: Arc<dyn ConfigService>) -> Self {
+        self.config_service = config_service;
+        self
     }
 
-    async fn handle_ping_cmd(&self, writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>) {
-        writer.lock().await.write_all(b"pong\n").await.unwrap();
+    pub fn with_auth_service(mut self, auth_service: Arc<dyn AuthService>) -> Self {
+        self.auth_service = auth_service;
+        self
     }
 
-    async fn handle_ping_value_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        mut value: Vec<u8>,
-    ) {
-        value.push(b'\n');
-        writer.lock().await.write_all(&value).await.unwrap();
+    pub fn with_tls_service(mut self, tls_service: Arc<dyn TlsService>) -> Self {
+        self.tls_service = Some(tls_service);
+        self
     }
 
-    async fn handle_get_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        key: &str,
-    ) {
-        let tlv = self.redis_service.get(key).await;
-        if let Some(tlv) = tlv {
-            let mut value = from_tlv(tlv);
-            value.push(b'\n');
-            writer.lock().await.write_all(&value).await.unwrap();
-        } else {
-            writer.lock().await.write_all(b"not found\n").await.unwrap();
-        }
+    pub fn with_cert_user(mut self, cert_user: CertUser) -> Self {
+        self.cert_user = cert_user;
+        self
     }
 
-    async fn handle_set_cmd(
-        &self,
-        writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        key: String,
-        value: Vec<u8>,
-    ) {
-        let tlv = to_tlv(value, TLVType::String);
-        self.redis_service.set(key.clone(), tlv.clone()).await;
-        let cache_result = self.redis_service.write_cache(key.clone(), tlv).await;
-        if cache_result.is_err() {
-            self.redis_service.remove(&key).await;
-            eprintln!(
-                "error during writing cache: {}",
-                cache_result.err().unwrap()
-            );
-        }
+    pub fn with_module(mut self, module: &dyn Module) -> io::Result<Self> {
+        let registry = Arc::get_mut(&mut self.registry).expect("the server has already started");
+        registry.load_module(module)?;
+        Ok(self)
+    }
+}
 
-        writer.lock().await.write_all(b"set ok\n").await.unwrap();
+#[async_trait]
+impl HandlerService for MyHandlerService {
+    async fn handle_cache_recovering(&self) -> io::Result<()> {
+        self.redis_service.read_cache().await?;
+        self.broker_service.read_history().await
     }
 
-    async fn handle_subscribe_cmd(
+    async fn handle_exit_cmd(
         &self,
         writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
-        sender: UnboundedSender<Vec<u8>>,
+    ) -> io::Result<()> {
+        writer.lock().await.shutdown().await
+    }
+
+    fn new_command_context(
+        &self,
+        sender: OutputBufferSender,
         socket_addr: SocketAddr,
-        topic: String,
-    ) {
-        self.broker_service
-            .subscribe(socket_addr, sender, topic)
-            .await;
-        writer
-            .lock()
-            .await
-            .write_all(b"subscribed ok\n")
-            .await
-            .unwrap();
+        identity: Option<ClientIdentity>,
+    ) -> CommandContext {
+        let mut context = CommandContext::new(
+            self.redis_service.clone(),
+            self.broker_service.clone(),
+            self.script_service.clone(),
+            self.registry.clone(),
+            sender,
+            socket_addr,
+        )
+        .with_config_service(self.config_service.clone())
+        .with_auth_service(self.auth_service.clone())
+        .with_tls_service(self.tls_service.clone());
+        let cert_user = identity.as_ref().and_then(|identity| self.cert_user.user(identity));
+        let cert_user = cert_user.filter(|user| {
+            let enabled = self.auth_service.user(user).is_some_and(|user| user.is_enabled());
+            if !enabled {
+                log::warn!("[{}] the certificate user '{}' doesn't exist or is disabled", socket_addr, user);
+            }
+            enabled
+        });
+        if let Some(user) = cert_user {
+            log::debug!("[{}] authenticated as '{}' by its certificate", socket_addr, user);
+            context.session.authenticate(user);
+        } else if !self.auth_service.requires_auth() {
+            // the clients connected before a password is set stay authenticated
+            context.session.authenticate(DEFAULT_USER.to_owned());
+        }
+        context.session.set_identity(identity);
+        context
     }
 
-    async fn handle_other_cmd(&self, writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>) {
-        writer.lock().await.write_all(b"unknown\n").await.unwrap();
+    async fn handle_cmd(&self, context: &mut CommandContext, args: Vec<String>) {
+        self.registry.dispatch(context, args).await;
     }
 
     async fn handle_publish_cmd(&self, publisher_addr: SocketAddr, message: Vec<u8>) {
@@ -165,7 +178,10 @@ impl HandlerService for MyHandlerService {
         writer: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
     ) {
         self.broker_service.unsubscribe(socket_addr).await;
-        let _ = writer.lock().await.write_all(b"ubnsubscribed ok\n").await;
+        let mut writer = writer.lock().await;
+        if writer.write_all(b"ubnsubscribed ok\n").await.is_ok() {
+            let _ = writer.flush().await;
+        }
     }
 
     async fn is_subscription_connection(&self, socket_addr: SocketAddr) -> bool {
@@ -173,12 +189,23 @@ impl HandlerService for MyHandlerService {
             .is_subscription_connection(socket_addr)
             .await
     }
+
+    async fn handle_active_expiry(&self) {
+        self.redis_service.remove_expired_keys().await;
+    }
+
+    fn command_lock(&self) -> Arc<RwLock<()>> {
+        self.redis_service.command_lock()
+    }
+
+    async fn unwatch_keys(&self, keys: Vec<DbKey>) {
+        self.redis_service.unwatch(keys).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io;
-    use std::io::{Error, ErrorKind};
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use std::pin::Pin;
     use std::sync::Arc;
@@ -189,9 +216,12 @@ mod tests {
     use tokio::io::AsyncWrite;
     use tokio::sync::Mutex;
 
+    use crate::core::auth::{AuthService, MyAuthService};
     use crate::core::broker::MockBrokerService;
+    use crate::core::buffer::output_buffer;
     use crate::core::handler::{HandlerService, MyHandlerService};
     use crate::core::redis::MockRedisService;
+    use crate::core::tls::{CertUser, ClientIdentity};
 
     fn mock_deps() -> (MockRedisService, MockBrokerService) {
         (MockRedisService::new(), MockBrokerService::new())
@@ -216,11 +246,15 @@ mod tests {
 
     #[tokio::test]
     async fn handle_cache_recovering_should_be_handled() {
-        let (mut redis_service, broker_service) = mock_deps();
+        let (mut redis_service, mut broker_service) = mock_deps();
         redis_service
             .expect_read_cache()
             .once()
             .returning(|| Ok(()));
+        broker_service
+            .expect_read_history()
+            .once()
+            .returning(|| Ok(()));
         let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service));
 
         let result = instance.handle_cache_recovering().await;
@@ -242,115 +276,6 @@ mod tests {
         assert!(result.is_ok());
     }
 
-    #[tokio::test]
-    async fn handle_ping_cmd_should_be_handled() {
-        let (redis_service, broker_service) = mock_deps();
-        let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service));
-        let mut writer = MockMyAsyncWriter::new();
-
-        writer
-            .expect_poll_write()
-            .returning(|_, _| Poll::Ready(Ok(1usize)));
-
-        instance.handle_ping_cmd(Arc::new(Mutex::new(writer))).await;
-    }
-
-    #[tokio::test]
-    async fn handle_ping_value_cmd_should_be_handled() {
-        let (redis_service, broker_service) = mock_deps();
-        let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service));
-        let mut writer = MockMyAsyncWriter::new();
-
-        writer
-            .expect_poll_write()
-            .returning(|_, _| Poll::Ready(Ok(1usize)));
-        let value = vec![111u8, 122u8];
-
-        instance
-            .handle_ping_value_cmd(Arc::new(Mutex::new(writer)), value)
-            .await;
-    }
-
-    #[tokio::test]
-    async fn handle_set_cmd_should_be_handled_when_cache_ok() {
-        let (mut redis_service, broker_service) = mock_deps();
-
-        redis_service
-            .expect_set()
-            .with(
-                eq("key1".to_owned()),
-                eq(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 55, 66]),
-            )
-            .once()
-            .returning(|_, _| ());
-        redis_service
-            .expect_write_cache()
-            .with(
-                eq("key1".to_owned()),
-                eq(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 55, 66]),
-            )
-            .once()
-            .returning(|_, _| Ok(()));
-
-        redis_service.expect_remove().never();
-
-        let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service));
-        let mut writer = MockMyAsyncWriter::new();
-        writer
-            .expect_poll_write()
-            .returning(|_, _| Poll::Ready(Ok(1usize)));
-
-        instance
-            .handle_set_cmd(
-                Arc::new(Mutex::new(writer)),
-                "key1".to_owned(),
-                vec![55u8, 66u8],
-            )
-            .await;
-    }
-
-    #[tokio::test]
-    async fn handle_set_cmd_should_be_handled_when_cache_err() {
-        let (mut redis_service, broker_service) = mock_deps();
-
-        redis_service
-            .expect_set()
-            .with(
-                eq("key1".to_owned()),
-                eq(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 44, 45]),
-            )
-            .once()
-            .returning(|_, _| ());
-        redis_service
-            .expect_write_cache()
-            .with(
-                eq("key1".to_owned()),
-                eq(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 44, 45]),
-            )
-            .once()
-            .returning(|_, _| Err(Error::new(ErrorKind::Other, "Other")));
-
-        redis_service
-            .expect_remove()
-            .with(eq("key1".to_owned()))
-            .once()
-            .returning(|_| ());
-
-        let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service));
-        let mut writer = MockMyAsyncWriter::new();
-        writer
-            .expect_poll_write()
-            .returning(|_, _| Poll::Ready(Ok(1usize)));
-
-        instance
-            .handle_set_cmd(
-                Arc::new(Mutex::new(writer)),
-                "key1".to_owned(),
-                vec![44u8, 45u8],
-            )
-            .await;
-    }
-
     #[tokio::test]
     async fn handle_publish_cmd_should_be_handled() {
         let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
@@ -383,6 +308,10 @@ mod tests {
         writer
             .expect_poll_write()
             .returning(|_, _| Poll::Ready(Ok(1usize)));
+        writer
+            .expect_poll_flush()
+            .once()
+            .returning(|_| Poll::Ready(Ok(())));
 
         instance
             .handle_unsubscribe_cmd(socket_addr, Arc::new(Mutex::new(writer)))
@@ -405,4 +334,52 @@ mod tests {
 
         assert!(result)
     }
+
+    #[test]
+    fn new_command_context_should_authenticate_cert_user() {
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let (redis_service, broker_service) = mock_deps();
+        let auth_service = Arc::new(MyAuthService::new().with_requirepass("s3cret"));
+        auth_service.set_user("alice", &["on".to_owned()]).unwrap();
+        let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service))
+            .with_cert_user(CertUser::CommonName)
+            .with_auth_service(auth_service);
+        let identity = ClientIdentity {
+            subject: "CN=alice, O=acme".to_owned(),
+            common_name: Some("alice".to_owned()),
+        };
+        let (sender, _receiver) = output_buffer();
+
+        let context = instance.new_command_context(sender.clone(), socket_addr, Some(identity.clone()));
+        assert_eq!(context.session.identity(), Some(&identity));
+        assert_eq!(context.session.user(), "alice");
+        assert!(context.session.is_authenticated());
+
+        let context = instance.new_command_context(sender.clone(), socket_addr, None);
+        assert_eq!(context.session.identity(), None);
+        assert_eq!(context.session.user(), "default");
+        assert!(!context.session.is_authenticated());
+
+        // a certificate naming an unknown user doesn't authenticate the client
+        let identity = ClientIdentity {
+            subject: "CN=bob".to_owned(),
+            common_name: Some("bob".to_owned()),
+        };
+        let context = instance.new_command_context(sender, socket_addr, Some(identity));
+        assert_eq!(context.session.user(), "default");
+        assert!(!context.session.is_authenticated());
+    }
+
+    #[test]
+    fn new_command_context_should_authenticate_without_password() {
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1111);
+        let (redis_service, broker_service) = mock_deps();
+        let instance = new_instance(Arc::new(redis_service), Arc::new(broker_service));
+        let (sender, _receiver) = output_buffer();
+
+        let context = instance.new_command_context(sender, socket_addr, None);
+
+        assert_eq!(context.session.user(), "default");
+        assert!(context.session.is_authenticated());
+    }
 }
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const U64_SIZE: usize = 8;
+const COMPACTION_MIN_RECORDS: usize = 64;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetentionPolicy {
+    MaxLen(u64),
+    MaxAge(Duration),
+}
+
+impl RetentionPolicy {
+    fn encode(&self) -> (u8, u64) {
+        match self {
+            RetentionPolicy::MaxLen(max_len) => (1, *max_len),
+            RetentionPolicy::MaxAge(max_age) => (2, max_age.as_millis() as u64),
+        }
+    }
+
+    fn decode(policy_type: u8, value: u64) -> Option<Self> {
+        match policy_type {
+            1 => Some(RetentionPolicy::MaxLen(value)),
+            2 => Some(RetentionPolicy::MaxAge(Duration::from_millis(value))),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetainedMessage {
+    pub offset: u64,
+    pub timestamp: u64,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HistoryWrite {
+    Append(Vec<u8>),
+    Replace(Vec<u8>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TopicHistory {
+    policy: RetentionPolicy,
+    next_offset: u64,
+    messages: VecDeque<RetainedMessage>,
+    /// the records of the stored history, the dropped messages included until it is compacted
+    stored_records: usize,
+}
+
+impl TopicHistory {
+    pub fn new(policy: RetentionPolicy) -> Self {
+        Self {
+            policy,
+            next_offset: 0,
+            messages: VecDeque::new(),
+            stored_records: 0,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: RetentionPolicy) {
+        self.policy = policy;
+        self.prune(now_millis());
+    }
+
+    /// Retains a new message, returns its offset and how to store it: its record is appended to the stored
+    /// history, which is compacted once most of its records are no longer retained.
+    pub fn append(&mut self, payload: Vec<u8>) -> (u64, HistoryWrite) {
+        let offset = self.next_offset;
+        let timestamp = now_millis();
+        self.next_offset += 1;
+        let message = RetainedMessage {
+            offset,
+            timestamp,
+            payload,
+        };
+        let mut record = Vec::new();
+        write_record(&message, &mut record);
+        self.messages.push_back(message);
+        self.prune(timestamp);
+        self.stored_records += 1;
+        if self.stored_records > (2 * self.messages.len()).max(COMPACTION_MIN_RECORDS) {
+            return (offset, HistoryWrite::Replace(self.compact()));
+        }
+        (offset, HistoryWrite::Append(record))
+    }
+
+    pub fn compact(&mut self) -> Vec<u8> {
+        self.stored_records = self.messages.len();
+        self.to_bytes()
+    }
+
+    pub fn since(&mut self, offset: u64) -> Vec<(u64, Vec<u8>)> {
+        self.prune(now_millis());
+        self.messages
+            .iter()
+            .filter(|message| message.offset >= offset)
+            .map(|message| (message.offset, message.payload.clone()))
+            .collect()
+    }
+
+    fn prune(&mut self, now: u64) {
+        match self.policy {
+            RetentionPolicy::MaxLen(max_len) => {
+                while self.messages.len() as u64 > max_len {
+                    self.messages.pop_front();
+                }
+            }
+            RetentionPolicy::MaxAge(max_age) => {
+                let oldest = now.saturating_sub(max_age.as_millis() as u64);
+                while self
+                    .messages
+                    .front()
+                    .is_some_and(|message| message.timestamp < oldest)
+                {
+                    self.messages.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Converts the history to bytes:
+    /// policy type (1 byte), policy value, next offset, then offset, timestamp, payload length and payload of
+    /// every message, all numbers being 8 bytes big endian. The records of new messages are appended to them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (policy_type, policy_value) = self.policy.encode();
+        let mut bytes = vec![policy_type];
+        bytes.extend(policy_value.to_be_bytes());
+        bytes.extend(self.next_offset.to_be_bytes());
+        for message in self.messages.iter() {
+            write_record(message, &mut bytes);
+        }
+        bytes
+    }
+
+    /// Converts bytes written by `to_bytes` and the records appended since back to the history, None if they are
+    /// malformed. A truncated last record, left by a crash during its write, is dropped.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&policy_type, mut rest) = bytes.split_first()?;
+        let policy = RetentionPolicy::decode(policy_type, read_u64(&mut rest)?)?;
+        let mut next_offset = read_u64(&mut rest)?;
+        let mut messages = VecDeque::new();
+        while let Some(message) = read_record(&mut rest) {
+            next_offset = next_offset.max(message.offset + 1);
+            messages.push_back(message);
+        }
+        let mut history = Self {
+            policy,
+            next_offset,
+            stored_records: messages.len(),
+            messages,
+        };
+        history.prune(now_millis());
+        Some(history)
+    }
+}
+
+fn write_record(message: &RetainedMessage, bytes: &mut Vec<u8>) {
+    bytes.extend(message.offset.to_be_bytes());
+    bytes.extend(message.timestamp.to_be_bytes());
+    bytes.extend((message.payload.len() as u64).to_be_bytes());
+    bytes.extend(&message.payload);
+}
+
+fn read_record(bytes: &mut &[u8]) -> Option<RetainedMessage> {
+    let offset = read_u64(bytes)?;
+    let timestamp = read_u64(bytes)?;
+    let length = usize::try_from(read_u64(bytes)?).ok()?;
+    let payload = bytes.get(..length)?.to_vec();
+    *bytes = &bytes[length..];
+    Some(RetainedMessage {
+        offset,
+        timestamp,
+        payload,
+    })
+}
+
+fn read_u64(bytes: &mut &[u8]) -> Option<u64> {
+    if bytes.len() < U64_SIZE {
+        return None;
+    }
+    let (value, rest) = bytes.split_at(U64_SIZE);
+    *bytes = rest;
+    Some(u64::from_be_bytes(value.try_into().unwrap()))
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::core::history::{HistoryWrite, RetentionPolicy, TopicHistory};
+
+    #[test]
+    fn test_append_and_since() {
+        let mut history = TopicHistory::new(RetentionPolicy::MaxLen(10));
+        assert_eq!(history.append(vec![1u8]).0, 0);
+        assert_eq!(history.append(vec![2u8]).0, 1);
+        assert_eq!(history.append(vec![3u8]).0, 2);
+
+        assert_eq!(history.since(0), vec![(0, vec![1u8]), (1, vec![2u8]), (2, vec![3u8])]);
+        assert_eq!(history.since(2), vec![(2, vec![3u8])]);
+        assert!(history.since(3).is_empty());
+    }
+
+    #[test]
+    fn test_max_len_policy() {
+        let mut history = TopicHistory::new(RetentionPolicy::MaxLen(2));
+        history.append(vec![1u8]);
+        history.append(vec![2u8]);
+        history.append(vec![3u8]);
+
+        assert_eq!(history.since(0), vec![(1, vec![2u8]), (2, vec![3u8])]);
+
+        history.set_policy(RetentionPolicy::MaxLen(1));
+        assert_eq!(history.since(0), vec![(2, vec![3u8])]);
+    }
+
+    #[test]
+    fn test_max_age_policy() {
+        let mut history = TopicHistory::new(RetentionPolicy::MaxAge(Duration::from_secs(60)));
+        history.append(vec![1u8]);
+        history.messages[0].timestamp -= 61_000;
+        history.append(vec![2u8]);
+
+        assert_eq!(history.since(0), vec![(1, vec![2u8])]);
+    }
+
+    #[test]
+    fn test_to_bytes_and_from_bytes() {
+        let mut history = TopicHistory::new(RetentionPolicy::MaxAge(Duration::from_secs(60)));
+        history.append(vec![104u8, 105u8]);
+        history.append(vec![]);
+
+        let bytes = history.to_bytes();
+        assert_eq!(TopicHistory::from_bytes(&bytes), Some(history.clone()));
+        assert_eq!(TopicHistory::from_bytes(&bytes[..16]), None);
+        assert_eq!(TopicHistory::from_bytes(&[]), None);
+
+        // appended records are read too, a truncated one is dropped
+        let HistoryWrite::Append(record) = history.append(vec![106u8]).1 else { panic!("not appended") };
+        let appended = [bytes.as_slice(), &record, &record[..10]].concat();
+        assert_eq!(TopicHistory::from_bytes(&appended), Some(history));
+    }
+
+    #[test]
+    fn test_compaction() {
+        let mut history = TopicHistory::new(RetentionPolicy::MaxLen(2));
+        let writes: Vec<HistoryWrite> = (0..65u8).map(|payload| history.append(vec![payload]).1).collect();
+
+        assert!(writes[..64].iter().all(|write| matches!(write, HistoryWrite::Append(_))));
+        let HistoryWrite::Replace(bytes) = &writes[64] else { panic!("not compacted") };
+        assert_eq!(TopicHistory::from_bytes(bytes).unwrap().since(0), vec![(63, vec![63u8]), (64, vec![64u8])]);
+        assert!(matches!(history.append(vec![65u8]).1, HistoryWrite::Append(_)));
+    }
+}
@@ -1,7 +1,23 @@
+pub mod acl;
+pub mod auth;
 pub mod broker;
+pub mod buffer;
 pub mod cache;
+pub mod command;
+pub mod config;
+pub mod db;
+pub mod glob;
+pub mod eviction;
+pub mod file;
 pub mod handler;
+pub mod history;
+pub mod logger;
+pub mod module;
+pub mod notify;
 pub mod parser;
 pub mod redis;
+pub mod script;
 pub mod server;
+pub mod session;
+pub mod tls;
 pub mod tlv;
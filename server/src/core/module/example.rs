@@ -0,0 +1,88 @@
+//! An example module storing counters as a value type of its own:
+//! `counter.incrby <key> <increment>` and `counter.get <key>`.
+
+use std::io;
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::{Command, CommandFlags, CommandFuture};
+use crate::core::module::{get_value, update_value, Module, ModuleType, ValueType};
+
+pub struct CounterModule;
+
+impl Module for CounterModule {
+    fn name(&self) -> &str {
+        "counter"
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new("counter.incrby", 3, CommandFlags::WRITE | CommandFlags::DENYOOM, incrby).with_keys(1, 1, 1),
+            Command::new("counter.get", 2, CommandFlags::READONLY, get).with_keys(1, 1, 1),
+        ]
+    }
+
+    fn value_types(&self) -> Vec<ValueType> {
+        vec![ValueType::of::<Counter>()]
+    }
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Counter(pub i64);
+
+impl ModuleType for Counter {
+    const NAME: &'static str = "counter";
+    const TLV_TYPE: u8 = 200;
+
+    fn serialize(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
+    }
+
+    fn deserialize(bytes: &[u8]) -> Option<Self> {
+        Some(Self(i64::from_be_bytes(bytes.try_into().ok()?)))
+    }
+}
+
+fn incrby(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        let Ok(increment) = args[2].parse::<i64>() else {
+            context.reply(b"err value is not an integer or out of range\n");
+            return;
+        };
+        let increment = move |counter: Option<Counter>| {
+            let counter = counter.unwrap_or_default();
+            counter
+                .0
+                .checked_add(increment)
+                .map(Counter)
+                .ok_or_else(|| io::Error::other("increment or decrement would overflow"))
+        };
+        match update_value(context, &args[1], increment).await {
+            Ok(counter) => context.reply_line(counter.0.to_string().as_bytes()),
+            Err(err) => context.reply(format!("err {}\n", err).as_bytes()),
+        }
+    })
+}
+
+fn get(context: &mut CommandContext, args: Vec<String>) -> CommandFuture<'_> {
+    Box::pin(async move {
+        match get_value::<Counter>(context, &args[1]).await {
+            Ok(Some(counter)) => context.reply_line(counter.0.to_string().as_bytes()),
+            Ok(None) => context.reply(b"not found\n"),
+            Err(err) => context.reply(format!("err {}\n", err).as_bytes()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::module::example::Counter;
+    use crate::core::module::ModuleType;
+
+    #[test]
+    fn test_counter_serialization() {
+        let bytes = Counter(-2).serialize();
+        assert_eq!(bytes, vec![255, 255, 255, 255, 255, 255, 255, 254]);
+        assert_eq!(Counter::deserialize(&bytes), Some(Counter(-2)));
+        assert_eq!(Counter::deserialize(&bytes[1..]), None);
+    }
+}
@@ -0,0 +1,193 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::core::command::context::CommandContext;
+use crate::core::command::registry::Command;
+use crate::core::notify::KeyEvent;
+use crate::core::redis::{SetCondition, SetExpiry};
+use crate::core::tlv::{split_tlv, to_module_tlv, TLV_HEADER_SIZE};
+
+pub mod example;
+
+/// A set of custom commands and value types loaded into the server,
+/// see `MyHandlerService::with_module` and the `example` module.
+pub trait Module: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn commands(&self) -> Vec<Command>;
+
+    fn value_types(&self) -> Vec<ValueType> {
+        Vec::new()
+    }
+}
+
+pub trait ModuleType: Sized {
+    const NAME: &'static str;
+    /// The tlv type, from `tlv::MODULE_TLV_TYPE_MIN` on.
+    const TLV_TYPE: u8;
+
+    fn serialize(&self) -> Vec<u8>;
+
+    fn deserialize(bytes: &[u8]) -> Option<Self>;
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValueType {
+    pub name: String,
+    pub tlv_type: u8,
+}
+
+impl ValueType {
+    pub fn of<T: ModuleType>() -> Self {
+        Self {
+            name: T::NAME.to_owned(),
+            tlv_type: T::TLV_TYPE,
+        }
+    }
+}
+
+pub async fn get_value<T: ModuleType>(context: &CommandContext, key: &str) -> io::Result<Option<T>> {
+    let Some(tlv) = context.redis_service.get(context.session.db(), key).await else { return Ok(None); };
+    from_value_tlv(&tlv).map(Some)
+}
+
+pub async fn set_value<T: ModuleType>(context: &CommandContext, key: &str, value: &T) -> io::Result<()> {
+    let entries = vec![(key.to_owned(), to_module_tlv(value.serialize(), T::TLV_TYPE))];
+    context
+        .redis_service
+        .set_all(context.session.db(), entries, SetCondition::Always, SetExpiry::Clear)
+        .await
+        .map(|_| ())
+}
+
+/// Changes the value of a key atomically and writes it to the cache: `update` is given the current value, None if
+/// the key does not exist, and returns the new one. Returns the new value, or the error of `update` or of reading
+/// the current value, in which case the key is left as it is.
+pub async fn update_value<T, F>(context: &CommandContext, key: &str, update: F) -> io::Result<T>
+where
+    T: ModuleType + Send + 'static,
+    F: FnOnce(Option<T>) -> io::Result<T> + Send + 'static,
+{
+    let result = Arc::new(Mutex::new(None));
+    let updated = Arc::clone(&result);
+    let value_update = Box::new(move |tlv: &mut Vec<u8>| {
+        let current = if tlv.is_empty() { Ok(None) } else { from_value_tlv(tlv).map(Some) };
+        let value = current.and_then(update);
+        let ranges = match &value {
+            Ok(value) => {
+                *tlv = to_module_tlv(value.serialize(), T::TLV_TYPE);
+                vec![TLV_HEADER_SIZE..tlv.len(), 0..TLV_HEADER_SIZE]
+            }
+            Err(_) => Vec::new(),
+        };
+        *updated.lock().unwrap() = Some(value);
+        (Vec::new(), ranges)
+    });
+    context
+        .redis_service
+        .update(context.session.db(), key, KeyEvent::Set, value_update)
+        .await;
+    let value = result.lock().unwrap().take();
+    value.unwrap_or_else(|| Err(io::Error::other("the value was not updated")))
+}
+
+fn from_value_tlv<T: ModuleType>(tlv: &[u8]) -> io::Result<T> {
+    match split_tlv(tlv) {
+        Some((tlv_type, _)) if tlv_type != T::TLV_TYPE => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WRONGTYPE Operation against a key holding the wrong kind of value",
+        )),
+        Some((_, value)) => T::deserialize(value).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("corrupted {} value", T::NAME))
+        }),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "corrupted value")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use mockall::predicate::{always, eq};
+
+    use crate::core::broker::MockBrokerService;
+    use crate::core::command::context::new_test_context;
+    use crate::core::module::example::Counter;
+    use crate::core::module::{get_value, set_value, update_value};
+    use crate::core::redis::{MockRedisService, SetCondition, SetExpiry};
+    use crate::core::script::MockScriptService;
+
+    #[tokio::test]
+    async fn get_value_should_check_type() {
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("a"))
+            .returning(|_, _| Some(vec![200, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 3]));
+        redis_service
+            .expect_get()
+            .with(eq(0), eq("b"))
+            .returning(|_, _| Some(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 104, 105]));
+        redis_service.expect_get().with(eq(0), eq("c")).returning(|_, _| None);
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+
+        assert_eq!(get_value::<Counter>(&context, "a").await.unwrap(), Some(Counter(3)));
+        let err = get_value::<Counter>(&context, "b").await.unwrap_err();
+        assert!(err.to_string().starts_with("WRONGTYPE"));
+        assert_eq!(get_value::<Counter>(&context, "c").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn set_value_should_write_cache() {
+        let tlv = vec![200, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 3];
+        let mut redis_service = MockRedisService::new();
+        redis_service
+            .expect_set_all()
+            .with(eq(0), eq(vec![("a".to_owned(), tlv)]), eq(SetCondition::Always), eq(SetExpiry::Clear))
+            .once()
+            .returning(|_, _, _, _| Ok(Some(vec![None])));
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+
+        assert!(set_value(&context, "a", &Counter(3)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn update_value_should_change_value_in_place() {
+        let mut redis_service = MockRedisService::new();
+        let value = Arc::new(Mutex::new(Vec::new()));
+        let updated = Arc::clone(&value);
+        redis_service
+            .expect_update()
+            .with(eq(0), eq("a"), always(), always())
+            .returning(move |_, _, _, update| {
+                let (reply, ranges) = update(&mut updated.lock().unwrap());
+                assert!(ranges.is_empty() || ranges == vec![9..17, 0..9]);
+                reply
+            });
+        let (context, _) = new_test_context(
+            redis_service,
+            MockBrokerService::new(),
+            MockScriptService::new(),
+        );
+        let increment = |counter: Option<Counter>| Ok(Counter(counter.unwrap_or_default().0 + 2));
+
+        assert_eq!(update_value(&context, "a", increment).await.unwrap(), Counter(2));
+        assert_eq!(update_value(&context, "a", increment).await.unwrap(), Counter(4));
+        let err = update_value(&context, "a", |_: Option<Counter>| Err(io::Error::other("overflow"))).await;
+        assert_eq!(err.unwrap_err().to_string(), "overflow");
+        assert_eq!(*value.lock().unwrap(), vec![200, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 4]);
+
+        *value.lock().unwrap() = vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 104, 105];
+        let err = update_value(&context, "a", increment).await.unwrap_err();
+        assert!(err.to_string().starts_with("WRONGTYPE"));
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+const KEYSPACE: u8 = 1;
+const KEYEVENT: u8 = 1 << 1;
+const GENERIC: u8 = 1 << 2;
+const STRING: u8 = 1 << 3;
+const EXPIRED: u8 = 1 << 4;
+const EVICTED: u8 = 1 << 5;
+const ALL: u8 = GENERIC | STRING | EXPIRED | EVICTED;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyEvent {
+    Set,
+    Append,
+    SetRange,
+    SetBit,
+    Del,
+    Expire,
+    Expired,
+    Evicted,
+    MoveFrom,
+    MoveTo,
+    Persist,
+}
+
+impl KeyEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyEvent::Set => "set",
+            KeyEvent::Append => "append",
+            KeyEvent::SetRange => "setrange",
+            KeyEvent::SetBit => "setbit",
+            KeyEvent::Del => "del",
+            KeyEvent::Expire => "expire",
+            KeyEvent::Expired => "expired",
+            KeyEvent::Evicted => "evicted",
+            KeyEvent::MoveFrom => "move_from",
+            KeyEvent::MoveTo => "move_to",
+            KeyEvent::Persist => "persist",
+        }
+    }
+
+    fn class(&self) -> u8 {
+        match self {
+            KeyEvent::Set | KeyEvent::Append | KeyEvent::SetRange | KeyEvent::SetBit => STRING,
+            KeyEvent::Del
+            | KeyEvent::Expire
+            | KeyEvent::Persist
+            | KeyEvent::MoveFrom
+            | KeyEvent::MoveTo => GENERIC,
+            KeyEvent::Expired => EXPIRED,
+            KeyEvent::Evicted => EVICTED,
+        }
+    }
+}
+
+/// Which key events are published, configured like the `notify-keyspace-events` setting of redis:
+/// `K` keyspace channels, `E` keyevent channels, `g` generic commands (del, expire, persist, move), `$` string commands,
+/// `x` expired keys, `e` evicted keys and `A` as an alias for `g$xe`.
+/// Nothing is published unless at least one of `K` or `E` is set.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KeyspaceEvents(u8);
+
+impl KeyspaceEvents {
+    pub fn parse(flags: &str) -> Option<Self> {
+        let mut events = 0u8;
+        for flag in flags.chars() {
+            events |= match flag {
+                'K' => KEYSPACE,
+                'E' => KEYEVENT,
+                'g' => GENERIC,
+                '$' => STRING,
+                'x' => EXPIRED,
+                'e' => EVICTED,
+                'A' => ALL,
+                _ => return None,
+            };
+        }
+        Some(Self(events))
+    }
+
+    pub fn keyspace(&self) -> bool {
+        self.0 & KEYSPACE != 0
+    }
+
+    pub fn keyevent(&self) -> bool {
+        self.0 & KEYEVENT != 0
+    }
+
+    pub fn is_enabled(&self, event: KeyEvent) -> bool {
+        (self.keyspace() || self.keyevent()) && self.0 & event.class() != 0
+    }
+}
+
+impl fmt::Display for KeyspaceEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = String::new();
+        if self.0 & ALL == ALL {
+            flags.push('A');
+        } else {
+            for (class, flag) in [(GENERIC, 'g'), (STRING, '$'), (EXPIRED, 'x'), (EVICTED, 'e')] {
+                if self.0 & class != 0 {
+                    flags.push(flag);
+                }
+            }
+        }
+        if self.keyspace() {
+            flags.push('K');
+        }
+        if self.keyevent() {
+            flags.push('E');
+        }
+        write!(f, "{}", flags)
+    }
+}
+
+pub fn keyspace_channel(db: usize, key: &str) -> String {
+    format!("__keyspace@{}__:{}", db, key)
+}
+
+pub fn keyevent_channel(db: usize, event: KeyEvent) -> String {
+    format!("__keyevent@{}__:{}", db, event.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::notify::{keyevent_channel, keyspace_channel, KeyEvent, KeyspaceEvents};
+
+    #[test]
+    fn test_parse() {
+        let events = KeyspaceEvents::parse("Kg").unwrap();
+        assert!(events.keyspace());
+        assert!(!events.keyevent());
+        assert!(events.is_enabled(KeyEvent::Del));
+        assert!(events.is_enabled(KeyEvent::Expire));
+        assert!(!events.is_enabled(KeyEvent::Set));
+
+        let events = KeyspaceEvents::parse("EA").unwrap();
+        assert!(events.is_enabled(KeyEvent::Set));
+        assert!(events.is_enabled(KeyEvent::Expired));
+        assert!(events.is_enabled(KeyEvent::Evicted));
+
+        assert_eq!(KeyspaceEvents::parse("Kz"), None);
+    }
+
+    #[test]
+    fn test_disabled_without_channel_type() {
+        let events = KeyspaceEvents::parse("A").unwrap();
+        assert!(!events.is_enabled(KeyEvent::Set));
+        assert!(!KeyspaceEvents::default().is_enabled(KeyEvent::Set));
+    }
+
+    #[test]
+    fn test_to_string() {
+        assert_eq!(KeyspaceEvents::parse("EgK").unwrap().to_string(), "gKE");
+        assert_eq!(KeyspaceEvents::parse("g$xeK").unwrap().to_string(), "AK");
+        assert_eq!(KeyspaceEvents::default().to_string(), "");
+    }
+
+    #[test]
+    fn test_channels() {
+        assert_eq!(keyspace_channel(0, "foo"), "__keyspace@0__:foo");
+        assert_eq!(keyevent_channel(0, KeyEvent::Expired), "__keyevent@0__:expired");
+    }
+}
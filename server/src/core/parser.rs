@@ -1,20 +1,4 @@
-use regex::Regex;
-
-const PING_VALUE_REGEX: &str = "^(?i)ping(?-i) (.+)$";
-const GET_REGEX: &str = "^(?i)get(?-i) ([a-zA-Z0-9]+)$";
-const SET_REGEX: &str = "^(?i)set(?-i) ([a-zA-Z0-9]+) (.+)$";
-const SUBSCRIBE_REGEX: &str = "^(?i)subscribe(?-i) ([a-zA-Z0-9]+)$";
-
-#[derive(Debug, Eq, PartialEq)]
-pub enum NonSubscriptionCmdType {
-    Exit,
-    Ping,
-    PingValue(Vec<u8>),
-    Set(String, Vec<u8>),
-    Get(String),
-    Subscribe(String),
-    Other,
-}
+use crate::core::command::string::are_set_options;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum SubscriptionCmdType {
@@ -22,27 +6,58 @@ pub enum SubscriptionCmdType {
     Unsubscribe,
 }
 
-pub fn parse_non_subscription_command(command: Vec<u8>) -> NonSubscriptionCmdType {
-    let command_str = String::from_utf8_lossy(&command);
-    let command_str = command_str.trim();
-    if is_exit(command_str) {
-        NonSubscriptionCmdType::Exit
-    } else if is_ping(command_str) {
-        NonSubscriptionCmdType::Ping
-    } else if is_ping_value(command_str) {
-        let value = extract_ping_with_value(command_str).unwrap_or_default();
-        NonSubscriptionCmdType::PingValue(value)
-    } else if is_get(command_str) {
-        let key = extract_get(command_str);
-        NonSubscriptionCmdType::Get(key.to_owned())
-    } else if is_set(command_str) {
-        let (key, value) = extract_set(command_str);
-        NonSubscriptionCmdType::Set(key.to_owned(), value.to_owned())
-    } else if is_subscribe(command_str) {
-        let topic = extract_subscribe(command_str);
-        NonSubscriptionCmdType::Subscribe(topic.to_owned())
-    } else {
-        NonSubscriptionCmdType::Other
+/// Splits a command into its arguments separated by white spaces, the command name comes first.
+/// An argument in double quotes can contain white spaces, a quote or a backslash in it is escaped
+/// with a backslash. Returns None when a quote is not closed.
+pub fn parse_command(command: &[u8]) -> Option<Vec<String>> {
+    let command = String::from_utf8_lossy(command);
+    Some(split_command(&command)?.into_iter().map(|(_, arg)| arg).collect())
+}
+
+/// Parses a command sent by a client like `parse_command`, except that the value of `set <key> <value>` is the
+/// rest of the line as it is, white spaces included, as it was before `set` had options. Unless it is quoted or
+/// followed by options, see `are_set_options`.
+pub fn parse_request(request: &[u8]) -> Option<Vec<String>> {
+    let request = String::from_utf8_lossy(request);
+    let (starts, mut args): (Vec<usize>, Vec<String>) = split_command(&request)?.into_iter().unzip();
+    if args.len() > 3
+        && args[0].eq_ignore_ascii_case("set")
+        && !request[starts[2]..].starts_with('"')
+        && !are_set_options(&args[3..])
+    {
+        args.truncate(2);
+        args.push(request[starts[2]..].trim_end().to_owned());
+    }
+    Some(args)
+}
+
+fn split_command(command: &str) -> Option<Vec<(usize, String)>> {
+    let mut chars = command.char_indices().peekable();
+    let mut args = Vec::new();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, first)) = chars.peek() else { return Some(args); };
+
+        let mut arg = String::new();
+        if first == '"' {
+            chars.next();
+            loop {
+                match chars.next()?.1 {
+                    '"' => break,
+                    '\\' => arg.push(chars.next()?.1),
+                    c => arg.push(c),
+                }
+            }
+            // a closing quote ends the argument
+            if chars.peek().is_some_and(|(_, c)| !c.is_whitespace()) {
+                return None;
+            }
+        } else {
+            while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push((start, arg));
     }
 }
 
@@ -56,77 +71,6 @@ pub fn parse_subscription_command(command: Vec<u8>) -> SubscriptionCmdType {
     }
 }
 
-fn is_exit(command: &str) -> bool {
-    command == "exit"
-}
-
-fn is_ping(command: &str) -> bool {
-    command == "ping"
-}
-
-fn is_ping_value(command: &str) -> bool {
-    Regex::new(PING_VALUE_REGEX)
-        .unwrap()
-        .captures(command)
-        .is_some()
-}
-
-fn extract_ping_with_value(command: &str) -> Option<Vec<u8>> {
-    Regex::new(PING_VALUE_REGEX)
-        .unwrap()
-        .captures(command)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str().to_owned().into_bytes())
-}
-
-fn is_get(command: &str) -> bool {
-    Regex::new(GET_REGEX).unwrap().captures(command).is_some()
-}
-
-fn extract_get(command: &str) -> &str {
-    Regex::new(GET_REGEX)
-        .unwrap()
-        .captures(command)
-        .map(|c| {
-            let (_, [key]) = c.extract();
-            key
-        })
-        .unwrap()
-}
-
-fn is_set(command: &str) -> bool {
-    Regex::new(SET_REGEX).unwrap().captures(command).is_some()
-}
-
-fn extract_set(command: &str) -> (&str, Vec<u8>) {
-    Regex::new(SET_REGEX)
-        .unwrap()
-        .captures(command)
-        .map(|c| {
-            let (_, [key, value]) = c.extract();
-            (key, value.as_bytes().to_vec())
-        })
-        .unwrap()
-}
-
-fn is_subscribe(command: &str) -> bool {
-    Regex::new(SUBSCRIBE_REGEX)
-        .unwrap()
-        .captures(command)
-        .is_some()
-}
-
-fn extract_subscribe(command: &str) -> &str {
-    Regex::new(SUBSCRIBE_REGEX)
-        .unwrap()
-        .captures(command)
-        .map(|c| {
-            let (_, [topic]) = c.extract();
-            topic
-        })
-        .unwrap()
-}
-
 fn is_unsubscribe(command: &str) -> bool {
     command == "unsubscribe"
 }
@@ -135,62 +79,37 @@ fn is_unsubscribe(command: &str) -> bool {
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_parse_exit() {
-        let cmd = "exit".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
-        assert_eq!(cmd_type, NonSubscriptionCmdType::Exit);
-    }
-
-    #[tokio::test]
-    async fn test_parse_ping() {
-        let cmd = "ping".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
-        assert_eq!(cmd_type, NonSubscriptionCmdType::Ping);
-    }
-
-    #[tokio::test]
-    async fn test_parse_ping_value() {
-        let cmd = "ping xxx".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
-        assert_eq!(
-            cmd_type,
-            NonSubscriptionCmdType::PingValue("xxx".as_bytes().to_vec())
-        );
+    fn to_args(args: &[&str]) -> Option<Vec<String>> {
+        Some(args.iter().map(|arg| arg.to_string()).collect())
     }
 
     #[tokio::test]
-    async fn test_parse_get() {
-        let cmd = "get a".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
-        assert_eq!(cmd_type, NonSubscriptionCmdType::Get("a".to_owned()));
+    async fn test_parse_command() {
+        assert_eq!(parse_command(b"get a\n"), to_args(&["get", "a"]));
+        assert_eq!(parse_command(b"  set  a   b c "), to_args(&["set", "a", "b", "c"]));
+        assert_eq!(parse_command(b"\n"), to_args(&[]));
     }
 
     #[tokio::test]
-    async fn test_parse_set() {
-        let cmd = "set a a".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
-        assert_eq!(
-            cmd_type,
-            NonSubscriptionCmdType::Set("a".to_owned(), "a".as_bytes().to_vec())
-        );
+    async fn test_parse_request() {
+        assert_eq!(parse_request(b"set a b  c\t d \n"), to_args(&["set", "a", "b  c\t d"]));
+        assert_eq!(parse_request(b"SET  a  b  c\n"), to_args(&["SET", "a", "b  c"]));
+        assert_eq!(parse_request(b"set a b  nx ex 10\n"), to_args(&["set", "a", "b", "nx", "ex", "10"]));
+        assert_eq!(parse_request(br#"set a "b  c" d"#), to_args(&["set", "a", "b  c", "d"]));
+        assert_eq!(parse_request(b"mset a b  c d"), to_args(&["mset", "a", "b", "c", "d"]));
+        assert_eq!(parse_request(b"set a b"), to_args(&["set", "a", "b"]));
     }
 
     #[tokio::test]
-    async fn test_parse_subscribe() {
-        let cmd = "subscribe topic1".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
+    async fn test_parse_command_with_quotes() {
         assert_eq!(
-            cmd_type,
-            NonSubscriptionCmdType::Subscribe("topic1".to_owned())
+            parse_command(br#"eval "return call('set', \"a b\")" 0"#),
+            to_args(&["eval", r#"return call('set', "a b")"#, "0"])
         );
-    }
-
-    #[tokio::test]
-    async fn test_parse_other() {
-        let cmd = "xxx".as_bytes().to_vec();
-        let cmd_type = parse_non_subscription_command(cmd);
-        assert_eq!(cmd_type, NonSubscriptionCmdType::Other);
+        assert_eq!(parse_command(br#"set a "" b\c"#), to_args(&["set", "a", "", r#"b\c"#]));
+        assert_eq!(parse_command(br#"set a "\\""#), to_args(&["set", "a", r#"\"#]));
+        assert_eq!(parse_command(br#"set a "b"#), None);
+        assert_eq!(parse_command(br#"set a "b"c"#), None);
     }
 
     #[tokio::test]
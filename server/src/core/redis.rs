@@ -1,32 +1,185 @@
 use std::collections::HashMap;
 use std::io;
-use std::sync::{Arc, RwLock};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 use async_trait::async_trait;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
+use tokio::sync::OwnedMutexGuard;
 
+use crate::core::broker::BrokerService;
+use crate::core::cache::deadline::{self, DEADLINE_HEADER_SIZE};
 use crate::core::cache::reader::CacheReaderService;
 use crate::core::cache::writer::CacheWriterService;
+use crate::core::config::service::ConfigListener;
+use crate::core::config::Config;
+use crate::core::db::Db;
+use crate::core::eviction::{random, EvictionPolicy, MaxMemory};
+use crate::core::glob::glob_match;
+use crate::core::notify::{keyevent_channel, keyspace_channel, KeyEvent, KeyspaceEvents};
+use crate::core::tlv::split_tlv;
+
+pub const DEFAULT_DATABASES: usize = 16;
+
+/// The number of keys having a time to live drawn at once by the active expiry, which draws again while more than a
+/// quarter of them had expired, as redis does.
+const ACTIVE_EXPIRY_SAMPLES: usize = 20;
+const ACTIVE_EXPIRY_TIME_LIMIT: Duration = Duration::from_millis(25);
+
+pub type DbKey = (usize, String);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SetCondition {
+    #[default]
+    Always,
+    NoneExists,
+    AllExist,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SetExpiry {
+    #[default]
+    Clear,
+    Keep,
+    At(Instant),
+}
+
+pub type ValueCheck = fn(&[u8]) -> bool;
+
+pub type PreviousValues = Vec<Option<Vec<u8>>>;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Checked<T> {
+    Passed(T),
+    Failed,
+}
+
+pub type ValueInspection = Box<dyn FnOnce(Option<&[u8]>) -> Vec<u8> + Send>;
+
+/// Changes a value in place for `RedisService::update`, given an empty value if the key does not exist. It returns
+/// the reply and the ranges of the value it changed, written to the cache in that order.
+pub type ValueUpdate = Box<dyn FnOnce(&mut Vec<u8>) -> (Vec<u8>, Vec<Range<usize>>) + Send>;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ScanFilter {
+    pub pattern: Option<String>,
+    pub tlv_types: Option<Vec<u8>>,
+}
+
+impl ScanFilter {
+    fn matches(&self, key: &str, value: &[u8]) -> bool {
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| glob_match(pattern.as_bytes(), key.as_bytes()))
+            && self
+                .tlv_types
+                .as_ref()
+                .is_none_or(|tlv_types| value.first().is_some_and(|tlv_type| tlv_types.contains(tlv_type)))
+    }
+}
+
+#[derive(Debug)]
+struct WatchedKey {
+    version: u64,
+    watchers: usize,
+}
 
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait RedisService: Send + Sync {
-    async fn get(&self, key: &str) -> Option<Vec<u8>>;
+    fn databases(&self) -> usize;
+
+    async fn get(&self, db: usize, key: &str) -> Option<Vec<u8>>;
+
+    async fn set(&self, db: usize, key: String, value: Vec<u8>);
+
+    /// Sets several keys at once if the condition holds, returns their previous values or None if nothing is set.
+    /// They are written to the cache atomically, a failure leaves the keys as they were.
+    async fn set_all(
+        &self,
+        db: usize,
+        entries: Vec<(String, Vec<u8>)>,
+        condition: SetCondition,
+        expiry: SetExpiry,
+    ) -> io::Result<Option<Vec<Option<Vec<u8>>>>>;
+
+    /// Like `set_all`, except that nothing is set if the previous value of a key fails the check. The previous
+    /// values are returned along with whether the condition holds, so even when nothing is set.
+    async fn set_checked(
+        &self,
+        db: usize,
+        entries: Vec<(String, Vec<u8>)>,
+        condition: SetCondition,
+        expiry: SetExpiry,
+        check: ValueCheck,
+    ) -> io::Result<Checked<(bool, PreviousValues)>>;
+
+    async fn remove(&self, db: usize, key: &str);
+
+    async fn delete(&self, db: usize, key: &str) -> bool;
+
+    async fn inspect(&self, db: usize, key: &str, inspection: ValueInspection) -> Vec<u8>;
+
+    /// Changes a value in place, the key is created unless it is left empty. Only the changed ranges are written
+    /// to the cache, the whole value for a new key or one that got shorter. Returns what `update` returns.
+    async fn update(&self, db: usize, key: &str, event: KeyEvent, update: ValueUpdate) -> Vec<u8>;
+
+    async fn take(&self, db: usize, key: &str) -> Option<Vec<u8>>;
+
+    async fn take_checked(&self, db: usize, key: &str, check: ValueCheck) -> Checked<Option<Vec<u8>>>;
+
+    async fn expire(&self, db: usize, key: &str, ttl: Duration) -> bool;
 
-    async fn set(&self, key: String, value: Vec<u8>);
+    async fn persist(&self, db: usize, key: &str) -> bool;
 
-    async fn remove(&self, key: &str);
+    async fn remove_expired_keys(&self);
+
+    fn used_memory(&self) -> usize;
+
+    async fn evict(&self) -> bool;
+
+    /// Returns the keys from a cursor on and the cursor of the next call, 0 once every key has been returned.
+    /// The keys are visited in the order of a hash that doesn't depend on the other keys, so that a key
+    /// present during a whole iteration is returned exactly once however the database changes in between.
+    /// About `count` keys are visited per call, the filter then applies to them, so a call can return no key
+    /// without the iteration being over.
+    async fn scan(&self, db: usize, cursor: u64, count: usize, filter: ScanFilter) -> (u64, Vec<String>);
+
+    async fn move_key(&self, key: &str, from: usize, to: usize) -> io::Result<bool>;
+
+    async fn flush(&self, db: usize) -> io::Result<()>;
+
+    async fn swap(&self, db1: usize, db2: usize) -> io::Result<()>;
 
     async fn read_cache(&self) -> io::Result<()>;
 
-    async fn write_cache(&self, key: String, value: Vec<u8>) -> io::Result<()>;
+    async fn write_cache(&self, db: usize, key: String, value: Vec<u8>) -> io::Result<()>;
+
+    fn command_lock(&self) -> Arc<tokio::sync::RwLock<()>>;
+
+    async fn watch(&self, keys: Vec<DbKey>) -> Vec<u64>;
+
+    async fn unwatch(&self, keys: Vec<DbKey>);
+
+    async fn versions(&self, keys: Vec<DbKey>) -> Vec<u64>;
 }
 
 pub struct MyRedisService {
     cache_reader_service: Arc<dyn CacheReaderService>,
     cache_writer_service: Arc<dyn CacheWriterService>,
-    db: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+    broker_service: Option<Arc<dyn BrokerService>>,
+    dbs: RwLock<Vec<Db>>,
+    start: Instant,
+    maxmemory: RwLock<MaxMemory>,
+    keyspace_events: RwLock<KeyspaceEvents>,
+    command_lock: Arc<tokio::sync::RwLock<()>>,
+    watched_keys: RwLock<HashMap<DbKey, WatchedKey>>,
+    next_version: AtomicU64,
+    key_locks: Mutex<HashMap<DbKey, Arc<tokio::sync::Mutex<()>>>>,
+    next_expiry_db: AtomicUsize,
 }
 
 impl MyRedisService {
@@ -37,48 +190,660 @@ impl MyRedisService {
         Self {
             cache_reader_service,
             cache_writer_service,
-            db: Arc::new(RwLock::new(HashMap::new())),
+            broker_service: None,
+            dbs: RwLock::new((0..DEFAULT_DATABASES).map(|_| Db::default()).collect()),
+            start: Instant::now(),
+            maxmemory: RwLock::new(MaxMemory::default()),
+            keyspace_events: RwLock::new(KeyspaceEvents::default()),
+            command_lock: Arc::new(tokio::sync::RwLock::new(())),
+            watched_keys: RwLock::new(HashMap::new()),
+            next_version: AtomicU64::new(1),
+            key_locks: Mutex::new(HashMap::new()),
+            next_expiry_db: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_databases(mut self, databases: usize) -> Self {
+        self.dbs = RwLock::new((0..databases.max(1)).map(|_| Db::default()).collect());
+        self
+    }
+
+    pub fn with_maxmemory(mut self, maxmemory: MaxMemory) -> Self {
+        self.maxmemory = RwLock::new(maxmemory);
+        self
+    }
+
+    pub fn with_keyspace_notifications(
+        mut self,
+        broker_service: Arc<dyn BrokerService>,
+        keyspace_events: KeyspaceEvents,
+    ) -> Self {
+        self.broker_service = Some(broker_service);
+        self.keyspace_events = RwLock::new(keyspace_events);
+        self
+    }
+
+    async fn notify(&self, db: usize, event: KeyEvent, key: &str) {
+        let Some(broker_service) = &self.broker_service else { return; };
+        let keyspace_events = *self.keyspace_events.read().unwrap();
+        if !keyspace_events.is_enabled(event) {
+            return;
+        }
+        if keyspace_events.keyspace() {
+            broker_service
+                .publish_to(keyspace_channel(db, key), event.name().as_bytes().to_vec())
+                .await;
         }
+        if keyspace_events.keyevent() {
+            broker_service
+                .publish_to(keyevent_channel(db, event), key.as_bytes().to_vec())
+                .await;
+        }
+    }
+
+    fn touch(&self, db: usize, key: &str) {
+        if let Some(watched_key) = self.watched_keys.write().unwrap().get_mut(&(db, key.to_owned())) {
+            watched_key.version = self.next_version.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn touch_all(&self, dbs: &[usize]) {
+        for ((db, _), watched_key) in self.watched_keys.write().unwrap().iter_mut() {
+            if dbs.contains(db) {
+                watched_key.version = self.next_version.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn clock(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn is_expired(&self, db: usize, key: &str) -> bool {
+        self.dbs.read().unwrap()[db].is_expired(key, Instant::now())
+    }
+
+    /// Locks the given keys until the guards are dropped. A key is locked from its change until its cache file is
+    /// written, so that the cache files are written in the order of the changes. The keys are locked in order so
+    /// that two calls never wait for each other.
+    async fn lock_keys(&self, mut keys: Vec<DbKey>) -> Vec<OwnedMutexGuard<()>> {
+        keys.sort_unstable();
+        keys.dedup();
+        let locks: Vec<_> = {
+            let mut key_locks = self.key_locks.lock().unwrap();
+            // the locks no one holds or waits for
+            key_locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            keys.into_iter()
+                .map(|key| Arc::clone(key_locks.entry(key).or_default()))
+                .collect()
+        };
+        let mut guards = Vec::with_capacity(locks.len());
+        for lock in locks {
+            guards.push(lock.lock_owned().await);
+        }
+        guards
+    }
+
+    async fn remove_expired_key(&self, db: usize, key: &str) {
+        let _guards = self.lock_keys(vec![(db, key.to_owned())]).await;
+        self.remove_expired_key_locked(db, key).await;
+    }
+
+    async fn set_all_locked(
+        &self,
+        db: usize,
+        entries: Vec<(String, Vec<u8>)>,
+        condition: SetCondition,
+        expiry: SetExpiry,
+    ) -> io::Result<(bool, PreviousValues)> {
+        let keys: Vec<String> = entries.iter().map(|(key, _)| key.clone()).collect();
+        // the values and times to live to restore if the cache can't be written
+        let (previous, cache_entries): (Vec<_>, Vec<_>) = {
+            let mut dbs = self.dbs.write().unwrap();
+            let (keys, now, clock) = (&mut dbs[db], Instant::now(), self.clock());
+            let previous: Vec<_> = entries
+                .iter()
+                .map(|(key, _)| {
+                    if keys.is_expired(key, now) {
+                        (None, None)
+                    } else {
+                        (keys.get(key).cloned(), keys.deadline(key))
+                    }
+                })
+                .collect();
+            let holds = match condition {
+                SetCondition::Always => true,
+                SetCondition::NoneExists => previous.iter().all(|(value, _)| value.is_none()),
+                SetCondition::AllExist => previous.iter().all(|(value, _)| value.is_some()),
+            };
+            if !holds {
+                return Ok((false, previous.into_iter().map(|(value, _)| value).collect()));
+            }
+            for (key, value) in entries.iter() {
+                if expiry != SetExpiry::Keep || keys.is_expired(key, now) {
+                    keys.clear_deadline(key);
+                }
+                keys.insert(key, value.clone(), clock);
+                if let SetExpiry::At(deadline) = expiry {
+                    keys.set_deadline(key, deadline);
+                }
+            }
+            let cache_entries: Vec<_> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let contents = cache_contents(value, keys.deadline(&key));
+                    (key, contents)
+                })
+                .collect();
+            (previous, cache_entries)
+        };
+        keys.iter().for_each(|key| self.touch(db, key));
+        if let Err(err) = self.cache_writer_service.write_all(db, cache_entries).await {
+            let mut dbs = self.dbs.write().unwrap();
+            for (key, (value, deadline)) in keys.iter().zip(previous) {
+                let Some(value) = value else {
+                    dbs[db].remove(key);
+                    continue;
+                };
+                dbs[db].clear_deadline(key);
+                dbs[db].insert(key, value, self.clock());
+                if let Some(deadline) = deadline {
+                    dbs[db].set_deadline(key, deadline);
+                }
+            }
+            return Err(err);
+        }
+        for key in keys.iter() {
+            self.notify(db, KeyEvent::Set, key).await;
+            if let SetExpiry::At(_) = expiry {
+                self.notify(db, KeyEvent::Expire, key).await;
+            }
+        }
+        Ok((true, previous.into_iter().map(|(value, _)| value).collect()))
+    }
+
+    async fn take_locked(&self, db: usize, key: &str) -> Option<Vec<u8>> {
+        if self.is_expired(db, key) {
+            self.remove_expired_key_locked(db, key).await;
+            return None;
+        }
+        let value = self.dbs.write().unwrap()[db].remove(key)?;
+        self.touch(db, key);
+        self.remove_cache_file(db, key).await;
+        self.notify(db, KeyEvent::Del, key).await;
+        Some(value)
+    }
+
+    fn passes(&self, db: usize, key: &str, check: ValueCheck) -> bool {
+        let dbs = self.dbs.read().unwrap();
+        dbs[db].is_expired(key, Instant::now()) || dbs[db].get(key).is_none_or(|value| check(value))
+    }
+
+    async fn remove_expired_key_locked(&self, db: usize, key: &str) {
+        {
+            let mut dbs = self.dbs.write().unwrap();
+            // it may have been set again since the caller checked it
+            if !dbs[db].is_expired(key, Instant::now()) || dbs[db].remove(key).is_none() {
+                return;
+            }
+        }
+        self.touch(db, key);
+        self.remove_cache_file(db, key).await;
+        self.notify(db, KeyEvent::Expired, key).await;
+    }
+
+    async fn write_cache_file(&self, db: usize, key: &str, contents: Vec<u8>) {
+        if let Err(err) = self.cache_writer_service.write(db, key.to_owned(), contents).await {
+            log::error!("error during writing cache: {}", err);
+        }
+    }
+
+    async fn remove_cache_file(&self, db: usize, key: &str) {
+        if let Err(err) = self.cache_writer_service.remove(db, key.to_owned()).await {
+            log::error!("error during removing cache: {}", err);
+        }
+    }
+
+    async fn exists(&self, db: usize, key: &str) -> bool {
+        if self.is_expired(db, key) {
+            self.remove_expired_key(db, key).await;
+            return false;
+        }
+        self.dbs.read().unwrap()[db].contains_key(key)
+    }
+
+    fn eviction_candidate(&self, maxmemory: MaxMemory) -> Option<DbKey> {
+        if maxmemory.policy == EvictionPolicy::NoEviction {
+            return None;
+        }
+        let (clock, now) = (self.clock(), Instant::now());
+        let dbs = self.dbs.read().unwrap();
+        // the higher the score, the sooner the key is evicted
+        let mut candidate: Option<(u64, usize, &str)> = None;
+        for (index, db) in dbs.iter().enumerate() {
+            for key in db.sample(maxmemory.samples, maxmemory.policy.is_volatile()) {
+                let Some(entry) = db.entry(key) else { continue; };
+                let score = match maxmemory.policy {
+                    EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => entry.idle(clock),
+                    EvictionPolicy::AllKeysLfu => (u8::MAX - entry.frequency(clock)) as u64,
+                    EvictionPolicy::AllKeysRandom => random(),
+                    EvictionPolicy::VolatileTtl => {
+                        let ttl = db
+                            .deadline(key)
+                            .map_or(0, |deadline| deadline.saturating_duration_since(now).as_millis() as u64);
+                        u64::MAX - ttl
+                    }
+                    EvictionPolicy::NoEviction => 0,
+                };
+                if candidate.is_none_or(|(candidate_score, _, _)| score > candidate_score) {
+                    candidate = Some((score, index, key));
+                }
+            }
+        }
+        candidate.map(|(_, db, key)| (db, key.to_owned()))
+    }
+}
+
+impl ConfigListener for MyRedisService {
+    fn apply_config(&self, config: &Config) {
+        *self.maxmemory.write().unwrap() = config.maxmemory();
+        *self.keyspace_events.write().unwrap() = config.keyspace_events();
     }
 }
 
 #[async_trait]
 impl RedisService for MyRedisService {
-    async fn get(&self, key: &str) -> Option<Vec<u8>> {
-        self.db.read().unwrap().get(key).cloned()
+    fn databases(&self) -> usize {
+        self.dbs.read().unwrap().len()
+    }
+
+    async fn get(&self, db: usize, key: &str) -> Option<Vec<u8>> {
+        if self.is_expired(db, key) {
+            self.remove_expired_key(db, key).await;
+            return None;
+        }
+        let dbs = self.dbs.read().unwrap();
+        dbs[db].access(key, self.clock());
+        dbs[db].get(key).cloned()
+    }
+
+    async fn set(&self, db: usize, key: String, value: Vec<u8>) {
+        {
+            let mut dbs = self.dbs.write().unwrap();
+            dbs[db].clear_deadline(&key);
+            dbs[db].insert(&key, value, self.clock());
+        }
+        self.touch(db, &key);
+        self.notify(db, KeyEvent::Set, &key).await;
+    }
+
+    async fn set_all(
+        &self,
+        db: usize,
+        entries: Vec<(String, Vec<u8>)>,
+        condition: SetCondition,
+        expiry: SetExpiry,
+    ) -> io::Result<Option<Vec<Option<Vec<u8>>>>> {
+        let _guards = self
+            .lock_keys(entries.iter().map(|(key, _)| (db, key.clone())).collect())
+            .await;
+        let (is_set, previous) = self.set_all_locked(db, entries, condition, expiry).await?;
+        Ok(is_set.then_some(previous))
+    }
+
+    async fn set_checked(
+        &self,
+        db: usize,
+        entries: Vec<(String, Vec<u8>)>,
+        condition: SetCondition,
+        expiry: SetExpiry,
+        check: ValueCheck,
+    ) -> io::Result<Checked<(bool, PreviousValues)>> {
+        let _guards = self
+            .lock_keys(entries.iter().map(|(key, _)| (db, key.clone())).collect())
+            .await;
+        if !entries.iter().all(|(key, _)| self.passes(db, key, check)) {
+            return Ok(Checked::Failed);
+        }
+        Ok(Checked::Passed(self.set_all_locked(db, entries, condition, expiry).await?))
+    }
+
+    async fn remove(&self, db: usize, key: &str) {
+        self.dbs.write().unwrap()[db].remove(key);
+        self.touch(db, key);
+    }
+
+    async fn delete(&self, db: usize, key: &str) -> bool {
+        self.take(db, key).await.is_some()
+    }
+
+    async fn inspect(&self, db: usize, key: &str, inspection: ValueInspection) -> Vec<u8> {
+        if self.is_expired(db, key) {
+            self.remove_expired_key(db, key).await;
+        }
+        let dbs = self.dbs.read().unwrap();
+        dbs[db].access(key, self.clock());
+        inspection(dbs[db].get(key).map(Vec::as_slice))
+    }
+
+    async fn update(&self, db: usize, key: &str, event: KeyEvent, update: ValueUpdate) -> Vec<u8> {
+        let _guards = self.lock_keys(vec![(db, key.to_owned())]).await;
+        if self.is_expired(db, key) {
+            self.remove_expired_key_locked(db, key).await;
+        }
+        // the whole value of a new or shorter key, else the parts that changed
+        let (reply, value, parts) = {
+            let mut dbs = self.dbs.write().unwrap();
+            let previous_length = dbs[db].get(key).map(Vec::len);
+            let (reply, ranges) = dbs[db].update(key, self.clock(), update);
+            let deadline = dbs[db].deadline(key);
+            let Some(value) = dbs[db].get(key) else { return reply; };
+            // the cache file can't be truncated in place
+            if previous_length.is_none_or(|length| value.len() < length) {
+                (reply, Some(cache_contents(value.clone(), deadline)), Vec::new())
+            } else if ranges.is_empty() {
+                return reply;
+            } else {
+                // the value follows the deadline in the cache file
+                let header_size = if deadline.is_some() { DEADLINE_HEADER_SIZE } else { 0 };
+                let parts = ranges
+                    .into_iter()
+                    .map(|range| {
+                        let range = range.start.min(value.len())..range.end.min(value.len());
+                        ((header_size + range.start) as u64, value[range].to_vec())
+                    })
+                    .collect();
+                (reply, None, parts)
+            }
+        };
+        self.touch(db, key);
+        match value {
+            Some(contents) => self.write_cache_file(db, key, contents).await,
+            None => {
+                if let Err(err) = self.cache_writer_service.write_at(db, key.to_owned(), parts).await {
+                    log::error!("error during writing cache: {}", err);
+                }
+            }
+        }
+        self.notify(db, event, key).await;
+        reply
+    }
+
+    async fn take(&self, db: usize, key: &str) -> Option<Vec<u8>> {
+        let _guards = self.lock_keys(vec![(db, key.to_owned())]).await;
+        self.take_locked(db, key).await
+    }
+
+    async fn take_checked(&self, db: usize, key: &str, check: ValueCheck) -> Checked<Option<Vec<u8>>> {
+        let _guards = self.lock_keys(vec![(db, key.to_owned())]).await;
+        if !self.passes(db, key, check) {
+            return Checked::Failed;
+        }
+        Checked::Passed(self.take_locked(db, key).await)
+    }
+
+    async fn expire(&self, db: usize, key: &str, ttl: Duration) -> bool {
+        if !self.exists(db, key).await {
+            return false;
+        }
+        let _guards = self.lock_keys(vec![(db, key.to_owned())]).await;
+        let deadline = Instant::now() + ttl;
+        let value = {
+            let mut dbs = self.dbs.write().unwrap();
+            let Some(value) = dbs[db].get(key).cloned() else { return false; };
+            dbs[db].set_deadline(key, deadline);
+            value
+        };
+        self.touch(db, key);
+        self.write_cache_file(db, key, cache_contents(value, Some(deadline))).await;
+        self.notify(db, KeyEvent::Expire, key).await;
+        true
+    }
+
+    async fn persist(&self, db: usize, key: &str) -> bool {
+        if !self.exists(db, key).await {
+            return false;
+        }
+        let _guards = self.lock_keys(vec![(db, key.to_owned())]).await;
+        let value = {
+            let mut dbs = self.dbs.write().unwrap();
+            if dbs[db].deadline(key).is_none() {
+                return false;
+            }
+            let Some(value) = dbs[db].get(key).cloned() else { return false; };
+            dbs[db].clear_deadline(key);
+            value
+        };
+        self.touch(db, key);
+        self.write_cache_file(db, key, value).await;
+        self.notify(db, KeyEvent::Persist, key).await;
+        true
+    }
+
+    async fn remove_expired_keys(&self) {
+        let start = Instant::now();
+        let databases = self.databases();
+        let first = self.next_expiry_db.load(Ordering::Relaxed);
+        for db in (first..first + databases).map(|db| db % databases) {
+            if start.elapsed() >= ACTIVE_EXPIRY_TIME_LIMIT {
+                self.next_expiry_db.store(db, Ordering::Relaxed);
+                return;
+            }
+            loop {
+                let (sampled, expired_keys) = {
+                    let dbs = self.dbs.read().unwrap();
+                    let now = Instant::now();
+                    let mut keys = dbs[db].sample(ACTIVE_EXPIRY_SAMPLES, true);
+                    keys.sort_unstable();
+                    keys.dedup();
+                    let expired_keys: Vec<String> = keys
+                        .iter()
+                        .filter(|key| dbs[db].is_expired(key, now))
+                        .map(|key| key.to_string())
+                        .collect();
+                    (keys.len(), expired_keys)
+                };
+                for key in expired_keys.iter() {
+                    self.remove_expired_key(db, key).await;
+                }
+                if expired_keys.len() * 4 <= sampled || start.elapsed() >= ACTIVE_EXPIRY_TIME_LIMIT {
+                    break;
+                }
+            }
+        }
+        self.next_expiry_db.store(first, Ordering::Relaxed);
+    }
+
+    fn used_memory(&self) -> usize {
+        self.dbs.read().unwrap().iter().map(Db::used_memory).sum()
     }
 
-    async fn set(&self, key: String, value: Vec<u8>) {
-        self.db.write().unwrap().insert(key, value);
+    async fn evict(&self) -> bool {
+        let maxmemory = *self.maxmemory.read().unwrap();
+        while maxmemory.limit > 0 && self.used_memory() > maxmemory.limit {
+            let Some((db, key)) = self.eviction_candidate(maxmemory) else { return false; };
+            let _guards = self.lock_keys(vec![(db, key.clone())]).await;
+            if self.dbs.write().unwrap()[db].remove(&key).is_none() {
+                // removed while it was being locked
+                continue;
+            }
+            self.touch(db, &key);
+            log::debug!("evicted '{}' of the database {}", key, db);
+            self.remove_cache_file(db, &key).await;
+            self.notify(db, KeyEvent::Evicted, &key).await;
+        }
+        true
     }
 
-    async fn remove(&self, key: &str) {
-        self.db.write().unwrap().remove(key);
+    async fn scan(&self, db: usize, cursor: u64, count: usize, filter: ScanFilter) -> (u64, Vec<String>) {
+        let now = Instant::now();
+        let dbs = self.dbs.read().unwrap();
+        let db = &dbs[db];
+        let (next_cursor, visited) = db.scan(cursor, count);
+        let mut keys: Vec<String> = visited
+            .into_iter()
+            .filter(|key| !db.is_expired(key, now))
+            .filter(|key| db.get(key).is_some_and(|value| filter.matches(key, value)))
+            .map(str::to_owned)
+            .collect();
+        keys.sort_unstable();
+        (next_cursor, keys)
+    }
+
+    async fn move_key(&self, key: &str, from: usize, to: usize) -> io::Result<bool> {
+        if !self.exists(from, key).await || self.exists(to, key).await {
+            return Ok(false);
+        }
+        let _guards = self.lock_keys(vec![(from, key.to_owned()), (to, key.to_owned())]).await;
+        let value = {
+            let mut dbs = self.dbs.write().unwrap();
+            // checked again, the key may have been changed before it was locked
+            let now = Instant::now();
+            if !dbs[from].contains_key(key)
+                || dbs[from].is_expired(key, now)
+                || dbs[to].contains_key(key) && !dbs[to].is_expired(key, now)
+            {
+                return Ok(false);
+            }
+            dbs[to].remove(key);
+            let deadline = dbs[from].deadline(key);
+            let Some(value) = dbs[from].remove(key) else { return Ok(false); };
+            dbs[to].insert(key, value.clone(), self.clock());
+            if let Some(deadline) = deadline {
+                dbs[to].set_deadline(key, deadline);
+            }
+            cache_contents(value, deadline)
+        };
+        self.touch(from, key);
+        self.touch(to, key);
+        // written first so that the key is never lost, a crash in between keeps it in both databases
+        self.cache_writer_service.write(to, key.to_owned(), value).await?;
+        self.cache_writer_service.remove(from, key.to_owned()).await?;
+        self.notify(from, KeyEvent::MoveFrom, key).await;
+        self.notify(to, KeyEvent::MoveTo, key).await;
+        Ok(true)
+    }
+
+    async fn flush(&self, db: usize) -> io::Result<()> {
+        self.dbs.write().unwrap()[db] = Db::default();
+        self.touch_all(&[db]);
+        self.cache_writer_service.flush(db).await
+    }
+
+    async fn swap(&self, db1: usize, db2: usize) -> io::Result<()> {
+        if db1 == db2 {
+            return Ok(());
+        }
+        self.dbs.write().unwrap().swap(db1, db2);
+        self.touch_all(&[db1, db2]);
+        self.cache_writer_service.swap(db1, db2).await
     }
 
     async fn read_cache(&self) -> io::Result<()> {
-        let cache = self.cache_reader_service.read().await?;
-        for (key, value) in cache.into_iter() {
-            self.set(key, value).await;
+        for db in 0..self.databases() {
+            let cache = self.cache_reader_service.read(db).await?;
+            let (clock, now) = (self.clock(), SystemTime::now());
+            let mut expired_keys = Vec::new();
+            {
+                let mut dbs = self.dbs.write().unwrap();
+                for (key, contents) in cache.into_iter() {
+                    let decoded = deadline::decode(contents).filter(|(value, _)| split_tlv(value).is_some());
+                    let Some((value, deadline)) = decoded else {
+                        // e.g. a file truncated by a crash
+                        log::warn!("skipping the invalid cache file of '{}' in the database {}", key, db);
+                        continue;
+                    };
+                    if deadline.is_some_and(|deadline| deadline <= now) {
+                        expired_keys.push(key);
+                        continue;
+                    }
+                    dbs[db].insert(&key, value, clock);
+                    if let Some(deadline) = deadline {
+                        let ttl = deadline.duration_since(now).unwrap_or_default();
+                        dbs[db].set_deadline(&key, Instant::now() + ttl);
+                    }
+                }
+            }
+            // expired while the server was down
+            for key in expired_keys.iter() {
+                self.remove_cache_file(db, key).await;
+            }
         }
         Ok(())
     }
 
-    async fn write_cache(&self, key: String, value: Vec<u8>) -> io::Result<()> {
-        self.cache_writer_service.write(key, value).await
+    async fn write_cache(&self, db: usize, key: String, value: Vec<u8>) -> io::Result<()> {
+        self.cache_writer_service.write(db, key, value).await
+    }
+
+    fn command_lock(&self) -> Arc<tokio::sync::RwLock<()>> {
+        Arc::clone(&self.command_lock)
+    }
+
+    async fn watch(&self, keys: Vec<DbKey>) -> Vec<u64> {
+        let mut watched_keys = self.watched_keys.write().unwrap();
+        keys.into_iter()
+            .map(|key| {
+                let watched_key = watched_keys.entry(key).or_insert_with(|| WatchedKey {
+                    version: self.next_version.fetch_add(1, Ordering::Relaxed),
+                    watchers: 0,
+                });
+                watched_key.watchers += 1;
+                watched_key.version
+            })
+            .collect()
+    }
+
+    async fn unwatch(&self, keys: Vec<DbKey>) {
+        let mut watched_keys = self.watched_keys.write().unwrap();
+        for key in keys.iter() {
+            if let Some(watched_key) = watched_keys.get_mut(key) {
+                watched_key.watchers -= 1;
+                if watched_key.watchers == 0 {
+                    watched_keys.remove(key);
+                }
+            }
+        }
     }
+
+    async fn versions(&self, keys: Vec<DbKey>) -> Vec<u64> {
+        for (db, key) in keys.iter() {
+            if self.is_expired(*db, key) {
+                self.remove_expired_key(*db, key).await;
+            }
+        }
+        let watched_keys = self.watched_keys.read().unwrap();
+        keys.iter()
+            .map(|key| watched_keys.get(key).map_or(0, |watched_key| watched_key.version))
+            .collect()
+    }
+}
+
+/// The contents of the cache file of a key, see `deadline::encode`. The deadline is written as a wall clock time
+/// so that it still holds after a restart.
+fn cache_contents(value: Vec<u8>, deadline: Option<Instant>) -> Vec<u8> {
+    let deadline = deadline.map(|deadline| SystemTime::now() + deadline.saturating_duration_since(Instant::now()));
+    deadline::encode(value, deadline)
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use std::sync::Arc;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use mockall::predicate::{always, eq, function};
 
-    use mockall::predicate::eq;
+    use std::time::{Duration, Instant, SystemTime};
 
+    use crate::core::broker::MockBrokerService;
+    use crate::core::cache::deadline;
     use crate::core::cache::reader::MockCacheReaderService;
     use crate::core::cache::writer::MockCacheWriterService;
-    use crate::core::redis::{MyRedisService, RedisService};
+    use crate::core::notify::{KeyEvent, KeyspaceEvents};
+    use crate::core::eviction::{EvictionPolicy, MaxMemory};
+    use crate::core::redis::{Checked, MyRedisService, RedisService, ScanFilter, SetCondition, SetExpiry};
+    use crate::core::tlv::{to_tlv, TLVType};
 
     fn mock_deps() -> (MockCacheReaderService, MockCacheWriterService) {
         (MockCacheReaderService::new(), MockCacheWriterService::new())
@@ -99,13 +864,9 @@ mod tests {
             Arc::new(cache_writer_service),
         );
 
-        instance
-            .db
-            .write()
-            .unwrap()
-            .insert("hello".to_owned(), vec![111, 112, 113]);
+        instance.dbs.write().unwrap()[0].insert("hello", vec![111, 112, 113], 0);
 
-        let x = instance.get("hello");
+        let x = instance.get(0, "hello");
         let result = x.await;
         assert_eq!(result, Some(vec![111, 112, 113]));
     }
@@ -118,9 +879,9 @@ mod tests {
             Arc::new(cache_writer_service),
         );
 
-        instance.set("hi".to_owned(), vec![100, 102, 104]).await;
+        instance.set(0, "hi".to_owned(), vec![100, 102, 104]).await;
 
-        let result = instance.db.read().unwrap().get("hi").cloned();
+        let result = instance.dbs.read().unwrap()[0].get("hi").cloned();
         assert_eq!(result, Some(vec![100, 102, 104]));
     }
 
@@ -132,35 +893,60 @@ mod tests {
             Arc::new(cache_writer_service),
         );
 
-        instance
-            .db
-            .write()
-            .unwrap()
-            .insert("john".to_owned(), vec![123, 124, 125]);
+        instance.dbs.write().unwrap()[0].insert("john", vec![123, 124, 125], 0);
 
-        let result = instance.db.write().unwrap().remove("john");
+        let result = instance.dbs.write().unwrap()[0].remove("john");
         assert_eq!(result, Some(vec![123, 124, 125]));
-        assert!(instance.db.read().unwrap().is_empty());
+        assert!(instance.dbs.read().unwrap()[0].is_empty());
     }
 
     #[tokio::test]
     async fn read_cache_should_be_red() {
-        let (mut cache_reader_service, cache_writer_service) = mock_deps();
+        let (mut cache_reader_service, mut cache_writer_service) = mock_deps();
         cache_reader_service
             .expect_read()
+            .with(eq(0))
             .once()
-            .returning(|| Ok(HashMap::from([("Jack".to_owned(), vec![111u8, 112u8])])));
+            .returning(|_| {
+                let (later, earlier) = (SystemTime::now() + Duration::from_secs(60), SystemTime::now());
+                Ok(HashMap::from([
+                    ("Jack".to_owned(), to_tlv(vec![111u8, 112u8], TLVType::String)),
+                    ("Joe".to_owned(), Vec::new()),
+                    ("Jim".to_owned(), deadline::encode(to_tlv(vec![114u8], TLVType::String), Some(later))),
+                    ("Jill".to_owned(), deadline::encode(to_tlv(vec![115u8], TLVType::String), Some(earlier))),
+                ]))
+            });
+        // expired while the server was down
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("Jill".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        cache_reader_service
+            .expect_read()
+            .with(eq(1))
+            .once()
+            .returning(|_| Ok(HashMap::from([("Jane".to_owned(), to_tlv(vec![113u8], TLVType::String))])));
 
         let instance = new_instance(
             Arc::new(cache_reader_service),
             Arc::new(cache_writer_service),
-        );
+        )
+        .with_databases(2);
 
         let result = instance.read_cache().await;
 
         assert!(result.is_ok());
-        let jack = instance.db.read().unwrap().get("Jack").cloned();
-        assert_eq!(jack, Some(vec![111u8, 112u8]));
+        let jack = instance.dbs.read().unwrap()[0].get("Jack").cloned();
+        assert_eq!(jack, Some(to_tlv(vec![111u8, 112u8], TLVType::String)));
+        assert_eq!(instance.get(1, "Jane").await, Some(to_tlv(vec![113u8], TLVType::String)));
+        assert_eq!(instance.get(0, "Jane").await, None);
+        // an empty file is not a value
+        assert_eq!(instance.get(0, "Joe").await, None);
+        assert_eq!(instance.get(0, "Jim").await, Some(to_tlv(vec![114u8], TLVType::String)));
+        let ttl = instance.dbs.read().unwrap()[0].deadline("Jim").unwrap() - Instant::now();
+        assert!(ttl > Duration::from_secs(50));
+        assert_eq!(instance.get(0, "Jill").await, None);
     }
 
     #[tokio::test]
@@ -168,9 +954,9 @@ mod tests {
         let (cache_reader_service, mut cache_writer_service) = mock_deps();
         cache_writer_service
             .expect_write()
-            .with(eq("John".to_owned()), eq(vec![220u8, 221u8, 222u8]))
+            .with(eq(0), eq("John".to_owned()), eq(vec![220u8, 221u8, 222u8]))
             .once()
-            .returning(|_, _| Ok(()));
+            .returning(|_, _, _| Ok(()));
 
         let instance = new_instance(
             Arc::new(cache_reader_service),
@@ -178,9 +964,702 @@ mod tests {
         );
 
         let result = instance
-            .write_cache("John".to_owned(), vec![220u8, 221u8, 222u8])
+            .write_cache(0, "John".to_owned(), vec![220u8, 221u8, 222u8])
             .await;
 
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn delete_should_be_deleted() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+
+        assert!(instance.delete(0, "john").await);
+        assert!(!instance.delete(0, "john").await);
+        assert!(instance.dbs.read().unwrap()[0].is_empty());
+    }
+
+    #[tokio::test]
+    async fn expire_should_remove_key_lazily() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        cache_writer_service
+            .expect_write()
+            .with(
+                eq(0),
+                eq("john".to_owned()),
+                function(|contents: &Vec<u8>| contents.len() == 10 && contents[0] == 0),
+            )
+            .once()
+            .returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+
+        assert!(instance.expire(0, "john", Duration::ZERO).await);
+        assert_eq!(instance.get(0, "john").await, None);
+        assert!(instance.dbs.read().unwrap()[0].is_empty());
+        assert!(!instance.expire(0, "missing", Duration::ZERO).await);
+    }
+
+    #[tokio::test]
+    async fn remove_expired_keys_should_remove_expired_keys_only() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        cache_writer_service.expect_write().returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        instance.set(0, "jane".to_owned(), vec![2u8]).await;
+        instance.expire(0, "john", Duration::ZERO).await;
+        instance.expire(0, "jane", Duration::from_secs(60)).await;
+
+        instance.remove_expired_keys().await;
+
+        assert_eq!(instance.dbs.read().unwrap()[0].get("john"), None);
+        assert_eq!(instance.dbs.read().unwrap()[0].get("jane"), Some(&vec![2u8]));
+    }
+
+    #[tokio::test]
+    async fn remove_expired_keys_should_repeat_while_many_keys_expired() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .times(100)
+            .returning(|_, _| Ok(()));
+        cache_writer_service.expect_write().returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        for i in 0..100 {
+            instance.set(1, format!("key{i}"), vec![1u8]).await;
+            instance.expire(1, &format!("key{i}"), Duration::ZERO).await;
+        }
+        instance.set(1, "jane".to_owned(), vec![2u8]).await;
+        instance.expire(1, "jane", Duration::from_secs(60)).await;
+
+        instance.remove_expired_keys().await;
+
+        assert_eq!(instance.dbs.read().unwrap()[1].len(), 1);
+        assert_eq!(instance.dbs.read().unwrap()[1].get("jane"), Some(&vec![2u8]));
+    }
+
+    #[tokio::test]
+    async fn set_should_clear_time_to_live() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service.expect_write().returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        instance.expire(0, "john", Duration::ZERO).await;
+
+        instance.set(0, "john".to_owned(), vec![2u8]).await;
+
+        assert_eq!(instance.get(0, "john").await, Some(vec![2u8]));
+    }
+
+    #[tokio::test]
+    async fn set_all_should_check_condition() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_write_all()
+            .with(eq(0), eq(vec![("john".to_owned(), vec![1u8]), ("jane".to_owned(), vec![2u8])]))
+            .once()
+            .returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![0u8]).await;
+        let entries = vec![("john".to_owned(), vec![1u8]), ("jane".to_owned(), vec![2u8])];
+
+        let none_exists = SetCondition::NoneExists;
+        let result = instance.set_all(0, entries.clone(), none_exists, SetExpiry::Clear).await;
+        assert_eq!(result.unwrap(), None);
+        let all_exist = SetCondition::AllExist;
+        let result = instance.set_all(0, entries.clone(), all_exist, SetExpiry::Clear).await;
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(instance.get(0, "jane").await, None);
+
+        let result = instance.set_all(0, entries, SetCondition::Always, SetExpiry::Clear).await;
+        assert_eq!(result.unwrap(), Some(vec![Some(vec![0u8]), None]));
+        assert_eq!(instance.get(0, "john").await, Some(vec![1u8]));
+        assert_eq!(instance.get(0, "jane").await, Some(vec![2u8]));
+    }
+
+    #[tokio::test]
+    async fn set_checked_and_take_checked_should_keep_values_failing_check() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service.expect_write_all().once().returning(|_, _| Ok(()));
+        cache_writer_service.expect_remove().once().returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![2u8]).await;
+        let entries = vec![("john".to_owned(), vec![3u8])];
+        let always = SetCondition::Always;
+
+        let result = instance.set_checked(0, entries.clone(), always, SetExpiry::Clear, |value| value == [1u8]).await;
+        assert_eq!(result.unwrap(), Checked::Failed);
+        assert_eq!(instance.take_checked(0, "john", |value| value == [1u8]).await, Checked::Failed);
+        assert_eq!(instance.get(0, "john").await, Some(vec![2u8]));
+
+        let result = instance.set_checked(0, entries, always, SetExpiry::Clear, |value| value == [2u8]).await;
+        assert_eq!(result.unwrap(), Checked::Passed((true, vec![Some(vec![2u8])])));
+        let result = instance.take_checked(0, "john", |value| value == [3u8]).await;
+        assert_eq!(result, Checked::Passed(Some(vec![3u8])));
+        assert_eq!(instance.get(0, "john").await, None);
+    }
+
+    #[tokio::test]
+    async fn set_all_should_set_time_to_live() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service.expect_write_all().returning(|_, _| Ok(()));
+        // persisted, the time to live is removed from the cache file
+        cache_writer_service
+            .expect_write()
+            .with(eq(0), eq("john".to_owned()), eq(vec![1u8]))
+            .once()
+            .returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let entries = vec![("john".to_owned(), vec![1u8])];
+        let always = SetCondition::Always;
+
+        instance.set_all(0, entries.clone(), always, SetExpiry::At(deadline)).await.unwrap();
+        instance.set_all(0, entries.clone(), always, SetExpiry::Keep).await.unwrap();
+        assert_eq!(instance.dbs.read().unwrap()[0].deadline("john"), Some(deadline));
+
+        assert!(instance.persist(0, "john").await);
+        assert!(!instance.persist(0, "john").await);
+        assert!(!instance.persist(0, "missing").await);
+        instance.set_all(0, entries.clone(), always, SetExpiry::At(deadline)).await.unwrap();
+        instance.set_all(0, entries, always, SetExpiry::Clear).await.unwrap();
+        assert_eq!(instance.dbs.read().unwrap()[0].deadline("john"), None);
+    }
+
+    #[tokio::test]
+    async fn set_all_should_restore_keys_when_cache_err() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_write_all()
+            .once()
+            .returning(|_, _| Err(io::Error::other("disk full")));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![0u8]).await;
+        let deadline = Instant::now() + Duration::from_secs(60);
+        instance.dbs.write().unwrap()[0].set_deadline("john", deadline);
+        let entries = vec![("john".to_owned(), vec![1u8]), ("jane".to_owned(), vec![2u8])];
+
+        let result = instance.set_all(0, entries, SetCondition::Always, SetExpiry::Clear).await;
+
+        assert!(result.is_err());
+        assert_eq!(instance.get(0, "john").await, Some(vec![0u8]));
+        assert_eq!(instance.dbs.read().unwrap()[0].deadline("john"), Some(deadline));
+        assert_eq!(instance.get(0, "jane").await, None);
+    }
+
+    #[tokio::test]
+    async fn update_should_write_changed_parts_to_cache() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_write()
+            .with(eq(0), eq("john".to_owned()), eq(vec![1u8, 2u8]))
+            .once()
+            .returning(|_, _, _| Ok(()));
+        cache_writer_service
+            .expect_write_at()
+            .with(eq(0), eq("john".to_owned()), eq(vec![(2, vec![3u8]), (0, vec![9u8])]))
+            .once()
+            .returning(|_, _, _| Ok(()));
+        cache_writer_service
+            .expect_write()
+            .with(eq(0), eq("john".to_owned()), eq(vec![9u8]))
+            .once()
+            .returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+
+        let reply = instance
+            .update(0, "john", KeyEvent::Append, Box::new(|value| {
+                value.extend([1u8, 2u8]);
+                (b"created".to_vec(), vec![0..1, 1..2])
+            }))
+            .await;
+        assert_eq!(reply, b"created".to_vec());
+        instance
+            .update(0, "john", KeyEvent::Append, Box::new(|value| {
+                value[0] = 9u8;
+                value.push(3u8);
+                (Vec::new(), vec![2..3, 0..1])
+            }))
+            .await;
+        // unchanged, nothing is written
+        instance.update(0, "john", KeyEvent::Append, Box::new(|_| (Vec::new(), Vec::new()))).await;
+        instance.update(0, "jane", KeyEvent::Append, Box::new(|_| (Vec::new(), Vec::new()))).await;
+
+        let inspection = Box::new(|value: Option<&[u8]>| value.unwrap().to_vec());
+        assert_eq!(instance.inspect(0, "john", inspection).await, vec![9u8, 2u8, 3u8]);
+        let inspection = Box::new(|value: Option<&[u8]>| vec![value.is_none() as u8]);
+        assert_eq!(instance.inspect(0, "jane", inspection).await, vec![1u8]);
+        // shorter, the whole value is written
+        instance
+            .update(0, "john", KeyEvent::Append, Box::new(|value| {
+                value.truncate(1);
+                (Vec::new(), Vec::new())
+            }))
+            .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn updates_should_write_cache_in_order() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let created = Arc::clone(&writes);
+        cache_writer_service.expect_write().once().returning(move |_, _, _| {
+            // a slow disk, the next update is made meanwhile
+            std::thread::sleep(Duration::from_millis(100));
+            created.lock().unwrap().push("write");
+            Ok(())
+        });
+        let appended = Arc::clone(&writes);
+        cache_writer_service.expect_write_at().once().returning(move |_, _, _| {
+            appended.lock().unwrap().push("write_at");
+            Ok(())
+        });
+        let instance = Arc::new(new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        ));
+
+        let first = tokio::spawn({
+            let instance = Arc::clone(&instance);
+            async move {
+                instance
+                    .update(0, "john", KeyEvent::Append, Box::new(|value| {
+                        value.extend([1u8, 2u8]);
+                        (Vec::new(), vec![0..1, 1..2])
+                    }))
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        instance
+            .update(0, "john", KeyEvent::Append, Box::new(|value| {
+                value[0] = 9u8;
+                value.push(3u8);
+                (Vec::new(), vec![2..3, 0..1])
+            }))
+            .await;
+        first.await.unwrap();
+
+        assert_eq!(*writes.lock().unwrap(), vec!["write", "write_at"]);
+    }
+
+    #[tokio::test]
+    async fn take_should_return_deleted_value() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+
+        assert_eq!(instance.take(0, "john").await, Some(vec![1u8]));
+        assert_eq!(instance.take(0, "john").await, None);
+        assert!(instance.dbs.read().unwrap()[0].is_empty());
+    }
+
+    #[tokio::test]
+    async fn mutations_should_notify_enabled_events() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service.expect_remove().returning(|_, _| Ok(()));
+        let mut broker_service = MockBrokerService::new();
+        broker_service
+            .expect_publish_to()
+            .with(eq("__keyspace@0__:john".to_owned()), eq(b"set".to_vec()))
+            .once()
+            .returning(|_, _| ());
+        broker_service
+            .expect_publish_to()
+            .with(eq("__keyevent@0__:set".to_owned()), eq(b"john".to_vec()))
+            .once()
+            .returning(|_, _| ());
+        broker_service
+            .expect_publish_to()
+            .with(eq("__keyspace@0__:john".to_owned()), eq(b"del".to_vec()))
+            .never();
+
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        )
+        .with_keyspace_notifications(
+            Arc::new(broker_service),
+            KeyspaceEvents::parse("KE$").unwrap(),
+        );
+
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        instance.delete(0, "john").await;
+    }
+
+    #[tokio::test]
+    async fn versions_should_change_when_watched_key_is_modified() {
+        let (cache_reader_service, cache_writer_service) = mock_deps();
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        let keys = vec![(0, "john".to_owned()), (0, "jane".to_owned())];
+
+        let versions = instance.watch(keys.clone()).await;
+        assert_eq!(instance.versions(keys.clone()).await, versions);
+
+        instance.set(0, "jane".to_owned(), vec![1u8]).await;
+        let new_versions = instance.versions(keys.clone()).await;
+        assert_eq!(new_versions[0], versions[0]);
+        assert_ne!(new_versions[1], versions[1]);
+
+        instance.unwatch(keys.clone()).await;
+        assert!(instance.watched_keys.read().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn versions_should_change_when_watched_key_expires() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        instance.dbs.write().unwrap()[0].set_deadline("john", std::time::Instant::now());
+        let keys = vec![(0, "john".to_owned())];
+
+        let versions = instance.watch(keys.clone()).await;
+
+        assert_ne!(instance.versions(keys).await, versions);
+    }
+
+    #[tokio::test]
+    async fn unwatch_should_keep_keys_watched_by_others() {
+        let (cache_reader_service, cache_writer_service) = mock_deps();
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        let keys = vec![(0, "john".to_owned())];
+
+        let versions = instance.watch(keys.clone()).await;
+        assert_eq!(instance.watch(keys.clone()).await, versions);
+        instance.unwatch(keys.clone()).await;
+
+        assert_eq!(instance.versions(keys).await, versions);
+    }
+
+    #[tokio::test]
+    async fn databases_should_be_separate() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        )
+        .with_databases(2);
+
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        instance.set(1, "john".to_owned(), vec![2u8]).await;
+        instance.delete(0, "john").await;
+
+        assert_eq!(instance.databases(), 2);
+        assert_eq!(instance.get(0, "john").await, None);
+        assert_eq!(instance.get(1, "john").await, Some(vec![2u8]));
+    }
+
+    #[tokio::test]
+    async fn move_key_should_not_overwrite_key_set_meanwhile() {
+        let (cache_reader_service, cache_writer_service) = mock_deps();
+        let instance = Arc::new(new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        ));
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+
+        // set in the database 1 once MOVE has checked it but before it locks the key
+        let guards = instance.lock_keys(vec![(1, "john".to_owned())]).await;
+        let moved = tokio::spawn({
+            let instance = Arc::clone(&instance);
+            async move { instance.move_key("john", 0, 1).await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        instance.set(1, "john".to_owned(), vec![2u8]).await;
+        drop(guards);
+
+        assert!(!moved.await.unwrap());
+        assert_eq!(instance.get(0, "john").await, Some(vec![1u8]));
+        assert_eq!(instance.get(1, "john").await, Some(vec![2u8]));
+    }
+
+    #[tokio::test]
+    async fn move_key_should_keep_time_to_live() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_write()
+            .with(eq(0), eq("john".to_owned()), always())
+            .once()
+            .returning(|_, _, _| Ok(()));
+        cache_writer_service
+            .expect_write()
+            .with(
+                eq(1),
+                eq("john".to_owned()),
+                function(|contents: &Vec<u8>| contents.len() == 10 && contents[9] == 1),
+            )
+            .once()
+            .returning(|_, _, _| Ok(()));
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("john".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        instance.expire(0, "john", Duration::from_secs(60)).await;
+        instance.set(1, "jane".to_owned(), vec![2u8]).await;
+        instance.set(0, "jane".to_owned(), vec![3u8]).await;
+
+        assert!(instance.move_key("john", 0, 1).await.unwrap());
+        assert!(!instance.move_key("john", 0, 1).await.unwrap());
+        assert!(!instance.move_key("jane", 0, 1).await.unwrap());
+
+        assert_eq!(instance.get(0, "john").await, None);
+        assert_eq!(instance.get(1, "john").await, Some(vec![1u8]));
+        assert!(instance.dbs.read().unwrap()[1].deadline("john").is_some());
+    }
+
+    #[tokio::test]
+    async fn flush_and_swap_should_touch_watched_keys() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_swap()
+            .with(eq(0), eq(1))
+            .once()
+            .returning(|_, _| Ok(()));
+        cache_writer_service
+            .expect_flush()
+            .with(eq(1))
+            .once()
+            .returning(|_| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+        let keys = vec![(0, "john".to_owned()), (2, "jane".to_owned())];
+        let versions = instance.watch(keys.clone()).await;
+
+        instance.swap(0, 1).await.unwrap();
+        let new_versions = instance.versions(keys.clone()).await;
+        assert_ne!(new_versions[0], versions[0]);
+        assert_eq!(new_versions[1], versions[1]);
+        assert_eq!(instance.get(0, "john").await, None);
+        assert_eq!(instance.get(1, "john").await, Some(vec![1u8]));
+
+        instance.flush(1).await.unwrap();
+        assert_eq!(instance.get(1, "john").await, None);
+    }
+
+    #[tokio::test]
+    async fn scan_should_return_every_key_once_while_keys_are_added() {
+        let (cache_reader_service, cache_writer_service) = mock_deps();
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        for index in 0..100 {
+            instance.set(0, format!("key{}", index), vec![1u8]).await;
+        }
+
+        let mut scanned = Vec::new();
+        let mut cursor = 0;
+        for round in 0.. {
+            let (next_cursor, keys) = instance.scan(0, cursor, 10, ScanFilter::default()).await;
+            scanned.extend(keys);
+            instance.set(0, format!("new{}", round), vec![1u8]).await;
+            cursor = next_cursor;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        for index in 0..100 {
+            let key = format!("key{}", index);
+            assert_eq!(scanned.iter().filter(|scanned| **scanned == key).count(), 1, "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_should_filter_keys() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service.expect_write().returning(|_, _, _| Ok(()));
+        cache_writer_service.expect_remove().returning(|_, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "user1".to_owned(), vec![1u8]).await;
+        instance.set(0, "user2".to_owned(), vec![200u8]).await;
+        instance.set(0, "order1".to_owned(), vec![1u8]).await;
+        instance.set(0, "user3".to_owned(), vec![1u8]).await;
+        instance.expire(0, "user3", Duration::ZERO).await;
+
+        let filter = ScanFilter {
+            pattern: Some("user*".to_owned()),
+            tlv_types: None,
+        };
+        let result = instance.scan(0, 0, usize::MAX, filter.clone()).await;
+        assert_eq!(result, (0, vec!["user1".to_owned(), "user2".to_owned()]));
+
+        let filter = ScanFilter {
+            tlv_types: Some(vec![1u8]),
+            ..filter
+        };
+        let result = instance.scan(0, 0, usize::MAX, filter).await;
+        assert_eq!(result, (0, vec!["user1".to_owned()]));
+    }
+
+    fn maxmemory(limit: usize, policy: EvictionPolicy) -> MaxMemory {
+        MaxMemory {
+            limit,
+            policy,
+            samples: 50,
+        }
+    }
+
+    #[tokio::test]
+    async fn evict_should_remove_least_recently_used_key() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("old".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        let mut instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.start = Instant::now() - Duration::from_secs(10);
+        instance.dbs.write().unwrap()[0].insert("old", vec![1u8; 100], 0);
+        instance.dbs.write().unwrap()[0].insert("new", vec![1u8; 100], 0);
+        instance.set(1, "other".to_owned(), vec![1u8; 100]).await;
+        instance.get(0, "new").await;
+
+        assert!(instance.evict().await);
+        let limit = instance.used_memory() - 1;
+        instance = instance.with_maxmemory(maxmemory(limit, EvictionPolicy::AllKeysLru));
+        assert!(instance.evict().await);
+
+        assert!(instance.used_memory() <= limit);
+        assert_eq!(instance.get(0, "old").await, None);
+        assert!(instance.get(0, "new").await.is_some());
+        assert!(instance.get(1, "other").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn evict_should_remove_shortest_time_to_live_only() {
+        let (cache_reader_service, mut cache_writer_service) = mock_deps();
+        cache_writer_service
+            .expect_remove()
+            .with(eq(0), eq("soon".to_owned()))
+            .once()
+            .returning(|_, _| Ok(()));
+        cache_writer_service.expect_write().returning(|_, _, _| Ok(()));
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        );
+        instance.set(0, "soon".to_owned(), vec![1u8; 100]).await;
+        instance.set(0, "later".to_owned(), vec![1u8; 100]).await;
+        instance.set(0, "never".to_owned(), vec![1u8; 100]).await;
+        instance.expire(0, "soon", Duration::from_secs(10)).await;
+        instance.expire(0, "later", Duration::from_secs(1000)).await;
+        let limit = instance.used_memory() - 1;
+        let instance = instance.with_maxmemory(maxmemory(limit, EvictionPolicy::VolatileTtl));
+
+        assert!(instance.evict().await);
+        assert_eq!(instance.get(0, "soon").await, None);
+        assert!(instance.get(0, "later").await.is_some());
+
+        // the keys without time to live can't be evicted
+        let instance = instance.with_maxmemory(maxmemory(1, EvictionPolicy::VolatileTtl));
+        instance.remove(0, "later").await;
+        assert!(!instance.evict().await);
+        assert!(instance.get(0, "never").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn evict_should_fail_without_eviction() {
+        let (cache_reader_service, cache_writer_service) = mock_deps();
+        let instance = new_instance(
+            Arc::new(cache_reader_service),
+            Arc::new(cache_writer_service),
+        )
+        .with_maxmemory(maxmemory(1, EvictionPolicy::NoEviction));
+
+        assert!(instance.evict().await);
+        instance.set(0, "john".to_owned(), vec![1u8]).await;
+
+        assert!(!instance.evict().await);
+        assert!(instance.get(0, "john").await.is_some());
+    }
 }
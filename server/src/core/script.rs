@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use mlua::{Error, HookTriggers, Lua, LuaOptions, StdLib, Value, Variadic};
+#[cfg(test)]
+use mockall::{automock, predicate::*};
+
+use crate::core::config::service::ConfigListener;
+use crate::core::config::Config;
+
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(5);
+const MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+const HOOK_INSTRUCTIONS: u32 = 1000;
+const UNSAFE_GLOBALS: [&str; 4] = ["dofile", "loadfile", "load", "print"];
+
+pub type CallBridge = Arc<dyn Fn(Vec<String>) -> Result<String, String> + Send + Sync>;
+
+/// Runs the Lua scripts of EVAL and EVALSHA and caches them by their SHA1 digest.
+///
+/// Scripts have access to the `KEYS` and `ARGV` tables and to `call(cmd, args...)` running a command
+/// through the command dispatch of the server. Only the table, string, math and utf8 libraries are loaded,
+/// and scripts are aborted once they exceed the time limit or when killed with SCRIPT KILL.
+/// The commands already called by an aborted script are not rolled back.
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait ScriptService: Send + Sync {
+    fn load(&self, script: String) -> String;
+
+    fn script(&self, sha: String) -> Option<String>;
+
+    async fn eval(
+        &self,
+        script: String,
+        keys: Vec<String>,
+        args: Vec<String>,
+        call: CallBridge,
+    ) -> Result<Vec<u8>, String>;
+
+    fn kill(&self) -> bool;
+}
+
+pub struct MyScriptService {
+    scripts: RwLock<HashMap<String, String>>,
+    running: Mutex<Option<Arc<AtomicBool>>>,
+    time_limit: RwLock<Duration>,
+}
+
+impl MyScriptService {
+    pub fn new() -> Self {
+        Self {
+            scripts: RwLock::new(HashMap::new()),
+            running: Mutex::new(None),
+            time_limit: RwLock::new(DEFAULT_TIME_LIMIT),
+        }
+    }
+
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = RwLock::new(time_limit);
+        self
+    }
+
+    fn new_lua(
+        call: CallBridge,
+        keys: Vec<String>,
+        args: Vec<String>,
+        killed: Arc<AtomicBool>,
+        time_limit: Duration,
+    ) -> mlua::Result<Lua> {
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8;
+        let lua = Lua::new_with(libs, LuaOptions::default())?;
+        lua.set_memory_limit(MEMORY_LIMIT)?;
+
+        let globals = lua.globals();
+        for name in UNSAFE_GLOBALS {
+            globals.set(name, Value::Nil)?;
+        }
+        globals.set("KEYS", keys)?;
+        globals.set("ARGV", args)?;
+        let call = lua.create_function(move |_, args: Variadic<Value>| {
+            let args = args.iter().map(|arg| arg.to_string()).collect::<mlua::Result<_>>()?;
+            call(args).map_err(Error::RuntimeError)
+        })?;
+        globals.set("call", call)?;
+        drop(globals);
+
+        let started_at = Instant::now();
+        let triggers = HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS);
+        lua.set_hook(triggers, move |_, _| {
+            if killed.load(Ordering::Relaxed) {
+                Err(Error::RuntimeError("script killed by user".to_owned()))
+            } else if started_at.elapsed() > time_limit {
+                Err(Error::RuntimeError("script timed out".to_owned()))
+            } else {
+                Ok(())
+            }
+        });
+        Ok(lua)
+    }
+}
+
+impl ConfigListener for MyScriptService {
+    fn apply_config(&self, config: &Config) {
+        *self.time_limit.write().unwrap() = config.lua_time_limit;
+    }
+}
+
+impl Default for MyScriptService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ScriptService for MyScriptService {
+    fn load(&self, script: String) -> String {
+        let sha = sha1_smol::Sha1::from(&script).digest().to_string();
+        self.scripts.write().unwrap().insert(sha.clone(), script);
+        sha
+    }
+
+    fn script(&self, sha: String) -> Option<String> {
+        self.scripts.read().unwrap().get(&sha).cloned()
+    }
+
+    async fn eval(
+        &self,
+        script: String,
+        keys: Vec<String>,
+        args: Vec<String>,
+        call: CallBridge,
+    ) -> Result<Vec<u8>, String> {
+        let killed = Arc::new(AtomicBool::new(false));
+        *self.running.lock().unwrap() = Some(killed.clone());
+        let time_limit = *self.time_limit.read().unwrap();
+
+        // the script blocks its thread, including while waiting for the commands it calls
+        let result = tokio::task::spawn_blocking(move || {
+            let lua = Self::new_lua(call, keys, args, killed, time_limit)?;
+            let value = lua.load(script).set_name("script").eval::<Value>()?;
+            to_reply(value)
+        })
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|result| result.map_err(to_error_message));
+
+        *self.running.lock().unwrap() = None;
+        result
+    }
+
+    fn kill(&self) -> bool {
+        let running = self.running.lock().unwrap();
+        let Some(killed) = running.as_ref() else { return false; };
+        killed.store(true, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Converts the value returned by a script into a reply: nil and false are nil, true is 1
+/// and the items of a table are written one per line.
+fn to_reply(value: Value) -> mlua::Result<Vec<u8>> {
+    let mut reply = Vec::new();
+    match value {
+        Value::Table(table) => {
+            for item in table.sequence_values::<Value>() {
+                reply.extend(to_reply(item?)?);
+            }
+            if reply.is_empty() {
+                reply.extend(b"empty\n");
+            }
+            return Ok(reply);
+        }
+        Value::Nil | Value::Boolean(false) => reply.extend(b"nil"),
+        Value::Boolean(true) => reply.extend(b"1"),
+        Value::String(value) => reply.extend(value.as_bytes()),
+        value => reply.extend(value.to_string()?.into_bytes()),
+    }
+    reply.push(b'\n');
+    Ok(reply)
+}
+
+fn to_error_message(err: Error) -> String {
+    match err {
+        Error::RuntimeError(message) => message,
+        Error::CallbackError { cause, .. } => to_error_message(cause.as_ref().clone()),
+        err => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::core::script::{CallBridge, MyScriptService, ScriptService};
+
+    fn no_call() -> CallBridge {
+        Arc::new(|_| Err("not allowed".to_owned()))
+    }
+
+    #[test]
+    fn test_load() {
+        let instance = MyScriptService::new();
+        let sha = instance.load("1 + 1".to_owned());
+
+        assert_eq!(sha, "26c19af0b33481d87494e14efba3c6190ac05097");
+        assert_eq!(instance.script(sha), Some("1 + 1".to_owned()));
+        assert_eq!(instance.script("unknown".to_owned()), None);
+    }
+
+    #[tokio::test]
+    async fn test_eval_replies() {
+        let instance = MyScriptService::new();
+        let eval = |script: &str| {
+            instance.eval(
+                script.to_owned(),
+                vec!["a".to_owned()],
+                vec!["b".to_owned()],
+                no_call(),
+            )
+        };
+
+        assert_eq!(eval("return 40 + 2").await, Ok(b"42\n".to_vec()));
+        assert_eq!(eval("return KEYS[1] .. ARGV[1]").await, Ok(b"ab\n".to_vec()));
+        assert_eq!(eval("return true").await, Ok(b"1\n".to_vec()));
+        assert_eq!(eval("return nil").await, Ok(b"nil\n".to_vec()));
+        assert_eq!(eval("return {1, 'x'}").await, Ok(b"1\nx\n".to_vec()));
+        assert_eq!(eval("return {}").await, Ok(b"empty\n".to_vec()));
+        assert!(eval("return 1 +").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_eval_call() {
+        let instance = MyScriptService::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls_cloned = calls.clone();
+        let call: CallBridge = Arc::new(move |args| {
+            calls_cloned.lock().unwrap().push(args.clone());
+            if args[0] == "get" {
+                Ok("hello".to_owned())
+            } else {
+                Err("err unknown".to_owned())
+            }
+        });
+
+        let result = instance
+            .eval("return call('get', KEYS[1]) .. 1".to_owned(), vec!["a".to_owned()], vec![], call.clone())
+            .await;
+        assert_eq!(result, Ok(b"hello1\n".to_vec()));
+
+        let result = instance
+            .eval("call('xxx') return 1".to_owned(), vec![], vec![], call)
+            .await;
+        assert_eq!(result, Err("err unknown".to_owned()));
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![vec!["get".to_owned(), "a".to_owned()], vec!["xxx".to_owned()]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eval_sandbox() {
+        let instance = MyScriptService::new();
+
+        let result = instance
+            .eval("return dofile('/etc/passwd')".to_owned(), vec![], vec![], no_call())
+            .await;
+        assert!(result.is_err());
+        let result = instance
+            .eval("return io.open('/etc/passwd')".to_owned(), vec![], vec![], no_call())
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_eval_time_limit() {
+        let instance = MyScriptService::new().with_time_limit(Duration::from_millis(50));
+
+        let result = instance
+            .eval("while true do end".to_owned(), vec![], vec![], no_call())
+            .await;
+        assert_eq!(result, Err("script timed out".to_owned()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_kill() {
+        let instance = Arc::new(MyScriptService::new());
+        assert!(!instance.kill());
+
+        let instance_cloned = instance.clone();
+        let running = tokio::spawn(async move {
+            instance_cloned
+                .eval("while true do end".to_owned(), vec![], vec![], no_call())
+                .await
+        });
+        while !instance.kill() {
+            tokio::task::yield_now().await;
+        }
+
+        let result = running.await.unwrap();
+        assert_eq!(result, Err("script killed by user".to_owned()));
+        assert!(!instance.kill());
+    }
+}
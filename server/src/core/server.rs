@@ -1,26 +1,32 @@
 ﻿use std::net::SocketAddr;
-use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 use tokio::io;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::sync::{Mutex, oneshot};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tokio_rustls::rustls::ServerConfig;
 use tokio_rustls::TlsAcceptor;
 
+use crate::core::buffer::output_buffer;
+use crate::core::command::context::CommandContext;
+use crate::core::config::service::ConfigListener;
+use crate::core::config::Config;
 use crate::core::handler::{HandlerService, MyHandlerService};
-use crate::core::parser::{
-    NonSubscriptionCmdType, parse_non_subscription_command, parse_subscription_command,
-    SubscriptionCmdType,
-};
+use crate::core::parser::{parse_request, parse_subscription_command, SubscriptionCmdType};
+use crate::core::tls::{ClientIdentity, MyTlsService, TlsService};
 
 const DEFAULT_BUFFER_SIZE: usize = 1024;
+const ACTIVE_EXPIRY_INTERVAL: Duration = Duration::from_millis(100);
+const DEFAULT_MAX_CLIENTS: usize = 10000;
+const TLS_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+type Reader = Arc<Mutex<dyn AsyncRead + Send + Unpin>>;
+type Writer = Arc<Mutex<dyn AsyncWrite + Send + Unpin>>;
 
 #[cfg_attr(test, automock)]
 #[async_trait]
@@ -32,61 +38,30 @@ pub trait ServerService: Send + Sync {
 pub struct MyServerService {
     binding_host: String,
     binding_port: String,
-    cert_file_path: String,
-    key_file_path: String,
+    tls_service: Arc<MyTlsService>,
     handler_service: Arc<dyn HandlerService>,
+    client_limits: ClientLimits,
 }
 
 impl MyServerService {
     pub fn new(
         binding_host: &str,
         binding_port: &str,
-        cert_file_path: &str,
-        key_file_path: &str,
+        tls_service: Arc<MyTlsService>,
         handler_service: Arc<dyn HandlerService>,
     ) -> Self {
         Self {
             binding_host: binding_host.to_owned(),
             binding_port: binding_port.to_owned(),
-            cert_file_path: cert_file_path.to_owned(),
-            key_file_path: key_file_path.to_owned(),
+            tls_service,
             handler_service,
+            client_limits: ClientLimits::default(),
         }
     }
 
-    fn load_tls_config(cert_file_path: &str, key_file_path: &str) -> io::Result<ServerConfig> {
-        let certs = utils::cert::load_cert(Path::new(cert_file_path))?;
-        let mut keys = utils::cert::load_key(Path::new(key_file_path))?;
-        let config = ServerConfig::builder()
-            .with_safe_defaults()
-            .with_no_client_auth()
-            .with_single_cert(certs, keys.remove(0))
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
-        Ok(config)
-    }
-
-    async fn accept_new_connection(
-        listener: &TcpListener,
-        tls_acceptor: TlsAcceptor,
-    ) -> Option<(TcpStream, SocketAddr)> {
-        let (tls_socket, address) = match Self::accept_as_tls(listener, tls_acceptor).await {
-            Ok((tls_socket, address)) => (tls_socket, address),
-            Err(err) => {
-                println!("failed to accept a new connection: {:?}", err);
-                return None;
-            }
-        };
-        Some((tls_socket, address))
-    }
-
-    async fn accept_as_tls(
-        listener: &TcpListener,
-        acceptor: TlsAcceptor,
-    ) -> io::Result<(TcpStream, SocketAddr)> {
-        let (socket, address) = listener.accept().await?;
-        let stream = acceptor.accept(socket).await?;
-        let (tls_socket, _server_connection) = stream.into_inner();
-        Ok((tls_socket, address))
+    pub fn with_client_limits(mut self, client_limits: ClientLimits) -> Self {
+        self.client_limits = client_limits;
+        self
     }
 }
 
@@ -94,6 +69,7 @@ pub struct MyNonSecureServerService {
     binding_host: String,
     binding_port: String,
     handler_service: Arc<dyn HandlerService>,
+    client_limits: ClientLimits,
 }
 
 impl MyNonSecureServerService {
@@ -106,49 +82,53 @@ impl MyNonSecureServerService {
             binding_host: binding_host.to_owned(),
             binding_port: binding_port.to_owned(),
             handler_service,
+            client_limits: ClientLimits::default(),
         }
     }
 
-    async fn accept_new_connection(listener: &TcpListener) -> Option<(TcpStream, SocketAddr)> {
-        let (socket, address) = match listener.accept().await {
-            Ok((tls_socket, address)) => (tls_socket, address),
-            Err(err) => {
-                println!("failed to accept a new connection: {:?}", err);
-                return None;
-            }
-        };
-        Some((socket, address))
+    pub fn with_client_limits(mut self, client_limits: ClientLimits) -> Self {
+        self.client_limits = client_limits;
+        self
     }
 }
 
 #[async_trait]
 impl ServerService for MyServerService {
     async fn start(&self, started_signal_tx: oneshot::Sender<u16>) -> io::Result<()> {
-        let config = Self::load_tls_config(&self.cert_file_path, &self.key_file_path)?;
-        let tls_acceptor = TlsAcceptor::from(Arc::new(config));
-
         let address = format!("{}:{}", self.binding_host, self.binding_port);
         let listener = TcpListener::bind(address.clone()).await?;
-        println!("===============================================================================================");
+        log::info!("===============================================================================================");
         self.handler_service.handle_cache_recovering().await?;
-        println!("===============================================================================================");
-        println!("server started...",);
+        log::info!("===============================================================================================");
+        log::info!("server started...");
 
         let port = listener.local_addr().unwrap().port();
         started_signal_tx.send(port).unwrap();
+        tokio::spawn(active_expiry(Arc::clone(&self.handler_service)));
+        tokio::spawn(Arc::clone(&self.tls_service).watch(TLS_WATCH_INTERVAL));
+        #[cfg(unix)]
+        tokio::spawn(reload_tls_on_hangup(Arc::clone(&self.tls_service)));
 
         loop {
-            let tls_acceptor = tls_acceptor.clone();
-            let Some((tls_socket, address)) = Self::accept_new_connection(&listener, tls_acceptor).await else { continue} ;
-            println!("[{}] has connected", address);
-
+            let Some((socket, address)) = accept_new_connection(&listener).await else { continue} ;
+            // a reloaded configuration only applies to the new connections
+            let tls_acceptor = TlsAcceptor::from(self.tls_service.config());
             let handler_service = Arc::clone(&self.handler_service);
-            let (reader, writer) = tls_socket.into_split();
-            let reader = Arc::new(Mutex::new(reader));
-            let writer = Arc::new(Mutex::new(writer));
-
+            let client_limits = self.client_limits.clone();
+            // the handshake doesn't hold up the next connections
             tokio::spawn(async move {
-                handle_connection(handler_service, reader, writer, address).await
+                match tls_acceptor.accept(socket).await {
+                    Ok(tls_stream) => {
+                        let (_, connection) = tls_stream.get_ref();
+                        let identity = connection
+                            .peer_certificates()
+                            .and_then(|certs| certs.first())
+                            .and_then(ClientIdentity::from_certificate);
+                        serve_connection(handler_service, client_limits, tls_stream, address, identity)
+                            .await
+                    }
+                    Err(err) => log::warn!("[{}] TLS handshake failed: {}", address, err),
+                }
             });
         }
     }
@@ -159,51 +139,198 @@ impl ServerService for MyNonSecureServerService {
     async fn start(&self, started_signal_tx: oneshot::Sender<u16>) -> io::Result<()> {
         let address = format!("{}:{}", self.binding_host, self.binding_port);
         let listener = TcpListener::bind(address.clone()).await?;
-        println!("===============================================================================================");
+        log::info!("===============================================================================================");
         self.handler_service.handle_cache_recovering().await?;
-        println!("===============================================================================================");
-        println!("server started...",);
+        log::info!("===============================================================================================");
+        log::info!("server started...");
 
         let port = listener.local_addr().unwrap().port();
         started_signal_tx.send(port).unwrap();
+        tokio::spawn(active_expiry(Arc::clone(&self.handler_service)));
 
         loop {
-            let Some((socket, address)) = Self::accept_new_connection(&listener).await else { continue} ;
-            println!("[{}] has connected", address);
-
+            let Some((socket, address)) = accept_new_connection(&listener).await else { continue} ;
             let handler_service = Arc::clone(&self.handler_service);
-            let (reader, writer) = socket.into_split();
-            let reader = Arc::new(Mutex::new(reader));
-            let writer = Arc::new(Mutex::new(writer));
+            let client_limits = self.client_limits.clone();
+            tokio::spawn(serve_connection(handler_service, client_limits, socket, address, None));
+        }
+    }
+}
 
-            tokio::spawn(async move {
-                handle_connection(handler_service, reader, writer, address).await
-            });
+#[derive(Clone, Default)]
+pub struct ClientLimits {
+    state: Arc<ClientLimitsState>,
+}
+
+struct ClientLimitsState {
+    max_clients: AtomicUsize,
+    timeout: AtomicU64,
+    connected_clients: AtomicUsize,
+}
+
+impl Default for ClientLimitsState {
+    fn default() -> Self {
+        Self {
+            max_clients: AtomicUsize::new(DEFAULT_MAX_CLIENTS),
+            timeout: AtomicU64::new(0),
+            connected_clients: AtomicUsize::new(0),
         }
     }
 }
 
+impl ClientLimits {
+    pub fn set_max_clients(&self, max_clients: usize) {
+        self.state.max_clients.store(max_clients, Ordering::Release);
+    }
+
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.state.timeout.store(timeout.as_secs(), Ordering::Release);
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        let timeout = self.state.timeout.load(Ordering::Acquire);
+        (timeout > 0).then(|| Duration::from_secs(timeout))
+    }
+
+    fn acquire(&self) -> bool {
+        let connected_clients = self.state.connected_clients.fetch_add(1, Ordering::AcqRel);
+        if connected_clients >= self.state.max_clients.load(Ordering::Acquire) {
+            self.release();
+            return false;
+        }
+        true
+    }
+
+    fn release(&self) {
+        self.state.connected_clients.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl ConfigListener for ClientLimits {
+    fn apply_config(&self, config: &Config) {
+        self.set_max_clients(config.maxclients);
+        self.set_timeout(config.timeout);
+    }
+}
+
+async fn accept_new_connection(listener: &TcpListener) -> Option<(TcpStream, SocketAddr)> {
+    match listener.accept().await {
+        Ok((socket, address)) => Some((socket, address)),
+        Err(err) => {
+            log::warn!("failed to accept a new connection: {:?}", err);
+            None
+        }
+    }
+}
+
+async fn serve_connection<S>(
+    handler_service: Arc<dyn HandlerService>,
+    client_limits: ClientLimits,
+    stream: S,
+    address: SocketAddr,
+    identity: Option<ClientIdentity>,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (reader, writer) = io::split(stream);
+    let reader: Reader = Arc::new(Mutex::new(reader));
+    let writer: Writer = Arc::new(Mutex::new(writer));
+    if !client_limits.acquire() {
+        log::warn!("[{}] refused, max number of clients reached", address);
+        write(&writer, b"err max number of clients reached\n").await;
+        let _ = writer.lock().await.shutdown().await;
+        return;
+    }
+    log::debug!("[{}] has connected", address);
+
+    handle_connection(handler_service, &client_limits, reader, writer, address, identity).await;
+    client_limits.release();
+}
+
+async fn write(writer: &Writer, data: &[u8]) {
+    let _ = write_flushed(&mut *writer.lock().await, data).await;
+}
+
+async fn write_flushed(writer: &mut (dyn AsyncWrite + Send + Unpin), data: &[u8]) -> io::Result<()> {
+    writer.write_all(data).await?;
+    writer.flush().await
+}
+
+#[cfg(unix)]
+async fn reload_tls_on_hangup(tls_service: Arc<MyTlsService>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            log::warn!("SIGHUP doesn't reload the TLS files: {}", err);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading the TLS files");
+        // logged by reload
+        let _ = tls_service.reload();
+    }
+}
+
+async fn active_expiry(handler_service: Arc<dyn HandlerService>) {
+    let mut interval = tokio::time::interval(ACTIVE_EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        let command_lock = handler_service.command_lock();
+        let _guard = command_lock.read().await;
+        handler_service.handle_active_expiry().await;
+    }
+}
+
 async fn handle_connection(
     handler_service: Arc<dyn HandlerService>,
-    reader: Arc<Mutex<OwnedReadHalf>>,
-    writer: Arc<Mutex<OwnedWriteHalf>>,
+    client_limits: &ClientLimits,
+    reader: Reader,
+    writer: Writer,
     address: SocketAddr,
+    identity: Option<ClientIdentity>,
 ) {
-    let (tx, mut rx) = unbounded_channel::<Vec<u8>>();
+    let (tx, mut rx) = output_buffer();
     let subscription_writer = Arc::clone(&writer);
     tokio::spawn(async move {
         while let Some(message) = rx.recv().await {
-            let _ = subscription_writer.lock().await.write_all(&message).await;
+            let mut subscription_writer = subscription_writer.lock().await;
+            tokio::select! {
+                _ = write_flushed(&mut *subscription_writer, &message) => {}
+                // a slow subscriber must not hold the writer once it is disconnected
+                _ = rx.closed() => break,
+            }
         }
         // channel closed
     });
 
+    let mut context = handler_service.new_command_context(tx.clone(), address, identity);
     loop {
         let writer_cloned = Arc::clone(&writer);
         let reader_cloned = Arc::clone(&reader);
-        let tx_cloned = tx.clone();
 
-        let Some(read_data) = read(reader_cloned, address.to_string()).await else {
+        // subscribers are idle while waiting for messages
+        let timeout = match client_limits.timeout() {
+            Some(timeout) if !handler_service.is_subscription_connection(address).await => Some(timeout),
+            _ => None,
+        };
+        let read_data = tokio::select! {
+            read_data = read(reader_cloned, address.to_string()) => read_data,
+            _ = idle(timeout) => {
+                log::debug!("[{}] disconnected after being idle", address);
+                None
+            }
+            _ = tx.closed() => {
+                log::debug!("[{}] disconnected by the server", address);
+                None
+            }
+        };
+        let Some(read_data) = read_data else {
+            handler_service
+                .unwatch_keys(context.session.take_watched_keys())
+                .await;
             let _ = handler_service.handle_exit_cmd(writer).await;
             handler_service.handle_unsubscribe_cmd(address, writer_cloned).await;
             break;
@@ -216,24 +343,32 @@ async fn handle_connection(
             handle_subscription_connection(handler_service, address, writer_cloned, read_data)
                 .await;
         } else {
-            handle_non_subscription_connection(
-                handler_service,
-                tx_cloned,
-                writer_cloned,
-                read_data,
-            )
-            .await;
+            handle_non_subscription_connection(handler_service, &mut context, writer_cloned, read_data)
+                .await;
         }
         // print!("\t[{}]: {}", address, String::from_utf8(data).unwrap())
     }
 }
 
-async fn read(reader: Arc<Mutex<OwnedReadHalf>>, address: String) -> Option<Vec<u8>> {
+async fn idle(timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep(timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn read(reader: Reader, address: String) -> Option<Vec<u8>> {
     let mut buffer = [0u8; DEFAULT_BUFFER_SIZE];
-    let size = reader.lock().await.read(&mut buffer).await.unwrap();
+    let size = match reader.lock().await.read(&mut buffer).await {
+        Ok(size) => size,
+        Err(err) => {
+            log::debug!("[{}] disconnected: {}", address, err);
+            return None;
+        }
+    };
     if size == 0 {
         // client disconnected
-        println!("[{}] disconnected", address);
+        log::debug!("[{}] disconnected", address);
         return None;
     }
     let read_data: Vec<u8> = buffer.into_iter().filter(|&byte| byte != 0).collect();
@@ -243,7 +378,7 @@ async fn read(reader: Arc<Mutex<OwnedReadHalf>>, address: String) -> Option<Vec<
 async fn handle_subscription_connection(
     handler_service: Arc<dyn HandlerService>,
     address: SocketAddr,
-    writer: Arc<Mutex<OwnedWriteHalf>>,
+    writer: Writer,
     data: Vec<u8>,
 ) {
     let cmd_type = parse_subscription_command(data);
@@ -261,35 +396,21 @@ async fn handle_subscription_connection(
 
 async fn handle_non_subscription_connection(
     handler_service: Arc<dyn HandlerService>,
-    sender: UnboundedSender<Vec<u8>>,
-    writer: Arc<Mutex<OwnedWriteHalf>>,
+    context: &mut CommandContext,
+    writer: Writer,
     data: Vec<u8>,
 ) {
-    let cmd_type = parse_non_subscription_command(data);
-    match cmd_type {
-        NonSubscriptionCmdType::Exit => {
-            let _ = handler_service.handle_exit_cmd(writer).await;
-        }
-        NonSubscriptionCmdType::Ping => {
-            handler_service.handle_ping_cmd(writer).await;
-        }
-        NonSubscriptionCmdType::PingValue(value) => {
-            handler_service.handle_ping_value_cmd(writer, value).await;
-        }
-        NonSubscriptionCmdType::Get(key) => {
-            handler_service.handle_get_cmd(writer, key.as_str()).await;
-        }
-        NonSubscriptionCmdType::Set(key, value) => {
-            handler_service.handle_set_cmd(writer, key, value).await;
-        }
-        NonSubscriptionCmdType::Subscribe(topic) => {
-            let peer_addr = writer.lock().await.peer_addr().unwrap();
-            handler_service
-                .handle_subscribe_cmd(writer, sender, peer_addr, topic)
-                .await;
-        }
-        NonSubscriptionCmdType::Other => {
-            handler_service.handle_other_cmd(writer).await;
-        }
+    let Some(args) = parse_request(&data) else {
+        write(&writer, b"err unbalanced quotes\n").await;
+        return;
+    };
+    if args == ["exit"] {
+        let _ = handler_service.handle_exit_cmd(writer).await;
+        return;
     }
+
+    // the response is buffered so that the command lock is never held while writing to the client
+    handler_service.handle_cmd(context, args).await;
+    let response = context.take_response();
+    write(&writer, &response).await;
 }
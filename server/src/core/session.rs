@@ -0,0 +1,162 @@
+use crate::core::redis::DbKey;
+use crate::core::tls::ClientIdentity;
+
+pub const DEFAULT_USER: &str = "default";
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Transaction {
+    pub commands: Vec<Vec<String>>,
+    pub aborted: bool,
+}
+
+#[derive(Debug)]
+pub struct Session {
+    transaction: Option<Transaction>,
+    db: usize,
+    watched_keys: Vec<(DbKey, u64)>,
+    identity: Option<ClientIdentity>,
+    user: String,
+    authenticated: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            transaction: None,
+            db: 0,
+            watched_keys: Vec::new(),
+            identity: None,
+            user: DEFAULT_USER.to_owned(),
+            authenticated: false,
+        }
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn identity(&self) -> Option<&ClientIdentity> {
+        self.identity.as_ref()
+    }
+
+    pub fn set_identity(&mut self, identity: Option<ClientIdentity>) {
+        self.identity = identity;
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn set_user(&mut self, user: String) {
+        self.user = user;
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    pub fn authenticate(&mut self, user: String) {
+        self.user = user;
+        self.authenticated = true;
+    }
+
+    pub fn db(&self) -> usize {
+        self.db
+    }
+
+    pub fn select(&mut self, db: usize) {
+        self.db = db;
+    }
+
+    pub fn is_in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    pub fn begin_transaction(&mut self) {
+        self.transaction = Some(Transaction::default());
+    }
+
+    pub fn queue(&mut self, args: Vec<String>) {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.commands.push(args);
+        }
+    }
+
+    pub fn abort_transaction(&mut self) {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.aborted = true;
+        }
+    }
+
+    pub fn take_transaction(&mut self) -> Option<Transaction> {
+        self.transaction.take()
+    }
+
+    pub fn watch(&mut self, keys: Vec<DbKey>, versions: Vec<u64>) {
+        self.watched_keys.extend(keys.into_iter().zip(versions));
+    }
+
+    pub fn watched_keys(&self) -> &[(DbKey, u64)] {
+        &self.watched_keys
+    }
+
+    pub fn take_watched_keys(&mut self) -> Vec<DbKey> {
+        self.watched_keys.drain(..).map(|(key, _)| key).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::session::{Session, Transaction};
+
+    #[test]
+    fn test_transaction() {
+        let mut session = Session::new();
+        assert!(!session.is_in_transaction());
+        // nothing is queued outside of a transaction
+        session.queue(vec!["ping".to_owned()]);
+
+        session.begin_transaction();
+        session.queue(vec!["get".to_owned(), "a".to_owned()]);
+        assert!(session.is_in_transaction());
+
+        let transaction = session.take_transaction();
+        assert_eq!(
+            transaction,
+            Some(Transaction {
+                commands: vec![vec!["get".to_owned(), "a".to_owned()]],
+                aborted: false,
+            })
+        );
+        assert!(!session.is_in_transaction());
+        assert_eq!(session.take_transaction(), None);
+    }
+
+    #[test]
+    fn test_abort_transaction() {
+        let mut session = Session::new();
+        session.begin_transaction();
+        session.abort_transaction();
+
+        assert!(session.take_transaction().unwrap().aborted);
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut session = Session::new();
+        session.watch(vec![(0, "a".to_owned()), (0, "b".to_owned())], vec![1, 2]);
+        session.watch(vec![(1, "a".to_owned())], vec![3]);
+
+        assert_eq!(
+            session.watched_keys(),
+            &[((0, "a".to_owned()), 1), ((0, "b".to_owned()), 2), ((1, "a".to_owned()), 3)]
+        );
+        assert_eq!(
+            session.take_watched_keys(),
+            vec![(0, "a".to_owned()), (0, "b".to_owned()), (1, "a".to_owned())]
+        );
+        assert!(session.watched_keys().is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+#[cfg(test)]
+use mockall::automock;
+use tokio_rustls::rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient};
+use tokio_rustls::rustls::{Certificate, RootCertStore, ServerConfig};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ClientAuth {
+    #[default]
+    No,
+    Optional,
+    Required,
+}
+
+impl FromStr for ClientAuth {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_lowercase().as_str() {
+            "no" => Ok(ClientAuth::No),
+            "optional" => Ok(ClientAuth::Optional),
+            "yes" => Ok(ClientAuth::Required),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for ClientAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientAuth::No => write!(f, "no"),
+            ClientAuth::Optional => write!(f, "optional"),
+            ClientAuth::Required => write!(f, "yes"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CertUser {
+    #[default]
+    Off,
+    CommonName,
+}
+
+impl CertUser {
+    pub fn user(&self, identity: &ClientIdentity) -> Option<String> {
+        match self {
+            CertUser::Off => None,
+            CertUser::CommonName => identity.common_name.clone(),
+        }
+    }
+}
+
+impl FromStr for CertUser {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(CertUser::Off),
+            "cn" => Ok(CertUser::CommonName),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for CertUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertUser::Off => write!(f, "off"),
+            CertUser::CommonName => write!(f, "CN"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientIdentity {
+    pub subject: String,
+    pub common_name: Option<String>,
+}
+
+impl ClientIdentity {
+    pub fn from_certificate(certificate: &Certificate) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(&certificate.0).ok()?;
+        let subject = certificate.subject();
+        let common_name = subject
+            .iter_common_name()
+            .next()
+            .and_then(|common_name| common_name.as_str().ok())
+            .map(str::to_owned);
+        Some(Self {
+            subject: subject.to_string(),
+            common_name,
+        })
+    }
+}
+
+#[cfg_attr(test, automock)]
+pub trait TlsService: Send + Sync {
+    fn config(&self) -> Arc<ServerConfig>;
+
+    /// Loads the certificate, key and CA files again, the current configuration is kept if one of them
+    /// fails to load.
+    fn reload(&self) -> io::Result<()>;
+}
+
+pub struct MyTlsService {
+    cert_file_path: PathBuf,
+    key_file_path: PathBuf,
+    ca_cert_file_path: Option<PathBuf>,
+    client_auth: ClientAuth,
+    config: RwLock<Arc<ServerConfig>>,
+}
+
+impl MyTlsService {
+    pub fn new(cert_file_path: &str, key_file_path: &str) -> io::Result<Self> {
+        let cert_file_path = PathBuf::from(cert_file_path);
+        let key_file_path = PathBuf::from(key_file_path);
+        let config = load_config(&cert_file_path, &key_file_path, None, ClientAuth::No)?;
+        Ok(Self {
+            cert_file_path,
+            key_file_path,
+            ca_cert_file_path: None,
+            client_auth: ClientAuth::No,
+            config: RwLock::new(Arc::new(config)),
+        })
+    }
+
+    pub fn with_client_auth(mut self, ca_cert_file_path: &str, client_auth: ClientAuth) -> io::Result<Self> {
+        self.ca_cert_file_path = Some(PathBuf::from(ca_cert_file_path));
+        self.client_auth = client_auth;
+        self.reload()?;
+        Ok(self)
+    }
+
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut modified = self.modified();
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let last_modified = self.modified();
+            if last_modified == modified {
+                continue;
+            }
+            modified = last_modified;
+            log::info!("the TLS files have changed, reloading them");
+            // logged by reload
+            let _ = self.reload();
+        }
+    }
+
+    fn files(&self) -> Vec<&Path> {
+        let mut files = vec![self.cert_file_path.as_path(), self.key_file_path.as_path()];
+        files.extend(self.ca_cert_file_path.as_deref());
+        files
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.files()
+            .into_iter()
+            .map(|file| file.metadata().and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+}
+
+impl TlsService for MyTlsService {
+    fn config(&self) -> Arc<ServerConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    fn reload(&self) -> io::Result<()> {
+        let config = load_config(
+            &self.cert_file_path,
+            &self.key_file_path,
+            self.ca_cert_file_path.as_deref(),
+            self.client_auth,
+        )
+        .inspect_err(|err| log::error!("failed to reload the TLS files, keeping the current ones: {}", err))?;
+        *self.config.write().unwrap() = Arc::new(config);
+        log::info!("TLS files reloaded");
+        Ok(())
+    }
+}
+
+fn load_config(
+    cert_file_path: &Path,
+    key_file_path: &Path,
+    ca_cert_file_path: Option<&Path>,
+    client_auth: ClientAuth,
+) -> io::Result<ServerConfig> {
+    let invalid = |err| io::Error::new(io::ErrorKind::InvalidInput, err);
+    let certs = utils::cert::load_cert(cert_file_path)?;
+    let key = utils::cert::load_key(key_file_path)?;
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match (ca_cert_file_path, client_auth) {
+        (Some(ca_cert_file_path), ClientAuth::Optional | ClientAuth::Required) => {
+            let mut roots = RootCertStore::empty();
+            for cert in utils::cert::load_cert(ca_cert_file_path)? {
+                roots.add(&cert).map_err(|err| invalid(err.to_string()))?;
+            }
+            let verifier = match client_auth {
+                ClientAuth::Required => AllowAnyAuthenticatedClient::new(roots).boxed(),
+                _ => AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(certs, key)
+        .map_err(|err| invalid(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use crate::core::tls::{CertUser, ClientAuth, ClientIdentity, MyTlsService, TlsService};
+
+    fn generate_certs(temp_dir: &TempDir) -> (String, String) {
+        utils::dev_certs::generate(temp_dir.path(), &["localhost".to_owned()], &["alice".to_owned()]).unwrap();
+        let cert_file = temp_dir.path().join("server.crt");
+        let key_file = temp_dir.path().join("server.key");
+        (cert_file.display().to_string(), key_file.display().to_string())
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let temp_dir = TempDir::new("tls-tests").unwrap();
+        generate_certs(&temp_dir);
+        let certificates = utils::cert::load_cert(&temp_dir.path().join("alice.crt")).unwrap();
+
+        let identity = ClientIdentity::from_certificate(&certificates[0]).unwrap();
+
+        assert_eq!(identity.common_name, Some("alice".to_owned()));
+        assert_eq!(identity.subject, "CN=alice");
+        assert_eq!(CertUser::CommonName.user(&identity), Some("alice".to_owned()));
+        assert_eq!(CertUser::Off.user(&identity), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("YES".parse(), Ok(ClientAuth::Required));
+        assert_eq!("optional".parse(), Ok(ClientAuth::Optional));
+        assert_eq!("cn".parse(), Ok(CertUser::CommonName));
+        assert_eq!("xxx".parse::<CertUser>(), Err(()));
+        assert_eq!(CertUser::CommonName.to_string(), "CN");
+    }
+
+    #[test]
+    fn reload_should_keep_config_on_error() {
+        let temp_dir = TempDir::new("tls-tests").unwrap();
+        let (cert_file, key_file) = generate_certs(&temp_dir);
+        let instance = MyTlsService::new(&cert_file, &key_file).unwrap();
+        let config = instance.config();
+
+        instance.reload().unwrap();
+        assert!(!Arc::ptr_eq(&config, &instance.config()));
+
+        let config = instance.config();
+        std::fs::write(&key_file, "").unwrap();
+        let err = instance.reload().unwrap_err();
+        assert!(err.to_string().contains("server.key' contains no private key"));
+        assert!(Arc::ptr_eq(&config, &instance.config()));
+
+        let err = MyTlsService::new(&cert_file, &key_file).err().unwrap();
+        assert!(err.to_string().contains("server.key' contains no private key"));
+    }
+
+    #[tokio::test]
+    async fn watch_should_reload_modified_files() {
+        let temp_dir = TempDir::new("tls-tests").unwrap();
+        let (cert_file, key_file) = generate_certs(&temp_dir);
+        let instance = Arc::new(MyTlsService::new(&cert_file, &key_file).unwrap());
+        let config = instance.config();
+        tokio::spawn(instance.clone().watch(Duration::from_millis(10)));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(Arc::ptr_eq(&config, &instance.config()));
+
+        let file = std::fs::File::options().append(true).open(&cert_file).unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(1)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!Arc::ptr_eq(&config, &instance.config()));
+    }
+}
@@ -1,4 +1,7 @@
 const TLV_LENGTH_SIZE: usize = 8;
+pub const TLV_HEADER_SIZE: usize = 1 + TLV_LENGTH_SIZE;
+/// The tlv types from this one on are left to the value types of the modules.
+pub const MODULE_TLV_TYPE_MIN: u8 = 128;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum TLVType {
@@ -30,6 +33,11 @@ pub fn to_tlv(value: Vec<u8>, tlv_type: TLVType) -> Vec<u8> {
     }
 }
 
+/// Given a value of a module type as byte array, converts it to the tlv
+pub fn to_module_tlv(value: Vec<u8>, tlv_type: u8) -> Vec<u8> {
+    form_tlv([tlv_type], value)
+}
+
 fn form_tlv(tlv_type: [u8; 1], tlv_value: Vec<u8>) -> Vec<u8> {
     let mut tlv = Vec::new();
     tlv.extend(tlv_type);
@@ -38,21 +46,31 @@ fn form_tlv(tlv_type: [u8; 1], tlv_value: Vec<u8>) -> Vec<u8> {
     tlv
 }
 
-/// Given a tlv byte array, converts it to the value
+/// Given a tlv byte array, converts it to the value, empty if it is not a String or an Int or is truncated
 pub fn from_tlv(value: Vec<u8>) -> Vec<u8> {
-    let Some(tlv_type) = TLVType::from_u8(value[0]) else { return Vec::new(); };
-    match tlv_type {
-        TLVType::String | TLVType::Int => {
-            let tlv_length = &value[1..=TLV_LENGTH_SIZE];
-            let value_length = usize::from_be_bytes(tlv_length.try_into().unwrap());
-            value[TLV_LENGTH_SIZE + 1..=TLV_LENGTH_SIZE + value_length].to_vec()
-        }
+    match split_tlv(&value) {
+        Some((tlv_type, value)) if TLVType::from_u8(tlv_type).is_some() => value.to_vec(),
+        _ => Vec::new(),
     }
 }
 
+pub fn split_tlv(tlv: &[u8]) -> Option<(u8, &[u8])> {
+    let (&tlv_type, rest) = tlv.split_first()?;
+    let tlv_length = rest.get(..TLV_LENGTH_SIZE)?;
+    let value_length = usize::from_be_bytes(tlv_length.try_into().unwrap());
+    let value = rest[TLV_LENGTH_SIZE..].get(..value_length)?;
+    Some((tlv_type, value))
+}
+
+pub fn resize_tlv(tlv: &mut Vec<u8>, length: usize) {
+    tlv.resize(TLV_HEADER_SIZE + length, 0);
+    tlv[0] = TLVType::String as u8;
+    tlv[1..TLV_HEADER_SIZE].copy_from_slice(&length.to_be_bytes());
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::core::tlv::{form_tlv, from_tlv, TLVType, to_tlv};
+    use crate::core::tlv::{form_tlv, from_tlv, resize_tlv, split_tlv, TLVType, to_module_tlv, to_tlv};
 
     #[test]
     fn test_tlv_type_from_u8() {
@@ -97,5 +115,27 @@ mod tests {
         ];
         let data = from_tlv(tlv);
         assert_eq!(vec![116, 101, 101, 32, 97, 108, 32, 118, 101, 101], data);
+        assert!(from_tlv(Vec::new()).is_empty());
+        assert!(from_tlv(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 116]).is_empty());
+    }
+
+    #[test]
+    fn test_module_tlv() {
+        let tlv = to_module_tlv(vec![7, 8], 200);
+        assert_eq!(vec![200, 0, 0, 0, 0, 0, 0, 0, 2, 7, 8], tlv);
+        assert_eq!(Some((200, &[7u8, 8u8][..])), split_tlv(&tlv));
+        assert_eq!(None, split_tlv(&tlv[..10]));
+        assert_eq!(None, split_tlv(&[]));
+    }
+
+    #[test]
+    fn test_resize_tlv() {
+        let mut tlv = Vec::new();
+        resize_tlv(&mut tlv, 2);
+        assert_eq!(vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0], tlv);
+
+        let mut tlv = to_tlv(b"123".to_vec(), TLVType::Int);
+        resize_tlv(&mut tlv, 1);
+        assert_eq!(vec![1, 0, 0, 0, 0, 0, 0, 0, 1, 49], tlv);
     }
 }
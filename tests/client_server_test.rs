@@ -102,4 +102,538 @@ mod client_server {
         let file_exists = metadata(temp_file).await.unwrap().is_file();
         assert!(file_exists);
     }
+
+    #[tokio::test]
+    async fn del_should_remove_data_and_cache_file() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "set a aa").await;
+        let _ = client_utils::read_message(&mut reader).await;
+
+        server_utils::write_message(&mut writer, "del a").await;
+        let del_response = client_utils::read_message(&mut reader).await;
+        assert_eq!(del_response, b"1\n".to_vec());
+
+        server_utils::write_message(&mut writer, "get a").await;
+        let get_response = client_utils::read_message(&mut reader).await;
+        assert_eq!(get_response, b"not found\n".to_vec());
+
+        let temp_file = temp_dir.path().join("a");
+        assert!(metadata(temp_file).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn multi_exec_should_execute_queued_commands() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let mut other_socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        let (mut other_reader, mut other_writer) = other_socket.split();
+
+        server_utils::write_message(&mut writer, "multi").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"multi ok\n".to_vec());
+
+        server_utils::write_message(&mut writer, "set a hello").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"queued\n".to_vec());
+        server_utils::write_message(&mut writer, "get a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"queued\n".to_vec());
+
+        // nothing is visible before exec
+        server_utils::write_message(&mut other_writer, "get a").await;
+        let response = client_utils::read_message(&mut other_reader).await;
+        assert_eq!(response, b"not found\n".to_vec());
+
+        server_utils::write_message(&mut writer, "exec").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"set ok\nhello\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn exec_should_abort_when_watched_key_is_modified() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let mut other_socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        let (mut other_reader, mut other_writer) = other_socket.split();
+
+        server_utils::write_message(&mut writer, "watch a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"watch ok\n".to_vec());
+        server_utils::write_message(&mut other_writer, "set a other").await;
+        let _ = client_utils::read_message(&mut other_reader).await;
+
+        server_utils::write_message(&mut writer, "multi").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "set a hello").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "exec").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"nil\n".to_vec());
+
+        server_utils::write_message(&mut writer, "get a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"other\n".to_vec());
+
+        // keys are unwatched after exec
+        server_utils::write_message(&mut writer, "multi").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "set a hello").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "exec").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"set ok\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn eval_should_call_commands() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        let script = r#"eval "call('set', KEYS[1], ARGV[1]) return call('get', KEYS[1])" 1 a hello"#;
+        server_utils::write_message(&mut writer, script).await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"hello\n".to_vec());
+
+        server_utils::write_message(&mut writer, r#"eval "return call('subscribe', 'x')" 0"#).await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"err 'subscribe' is not allowed from scripts\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn evalsha_should_run_loaded_script() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, r#"script load "return {KEYS[1], ARGV[1]}""#).await;
+        let sha = client_utils::read_message(&mut reader).await;
+        let sha = String::from_utf8(sha).unwrap();
+
+        server_utils::write_message(&mut writer, &format!("evalsha {} 1 a b", sha.trim())).await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"a\nb\n".to_vec());
+
+        let unknown_sha = "0".repeat(40);
+        server_utils::write_message(&mut writer, &format!("evalsha {} 0", unknown_sha)).await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"err NOSCRIPT no matching script\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn script_kill_should_abort_running_script() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let mut other_socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        let (mut other_reader, mut other_writer) = other_socket.split();
+
+        server_utils::write_message(&mut other_writer, "script kill").await;
+        let response = client_utils::read_message(&mut other_reader).await;
+        assert_eq!(response, b"err NOTBUSY no scripts in execution right now\n".to_vec());
+
+        server_utils::write_message(&mut writer, r#"eval "while true do end" 0"#).await;
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        server_utils::write_message(&mut other_writer, "script kill").await;
+        let response = client_utils::read_message(&mut other_reader).await;
+        assert_eq!(response, b"kill ok\n".to_vec());
+
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"err script killed by user\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn multi_exec_should_abort_on_syntax_error() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "multi").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "set a hello").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "xxx").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"unknown\n".to_vec());
+
+        server_utils::write_message(&mut writer, "exec").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(
+            response,
+            b"err EXECABORT transaction discarded because of previous errors\n".to_vec()
+        );
+
+        server_utils::write_message(&mut writer, "get a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"not found\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn discard_should_drop_queued_commands() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "multi").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "set a hello").await;
+        let _ = client_utils::read_message(&mut reader).await;
+
+        server_utils::write_message(&mut writer, "discard").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"discard ok\n".to_vec());
+
+        server_utils::write_message(&mut writer, "exec").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"err EXEC without MULTI\n".to_vec());
+
+        server_utils::write_message(&mut writer, "get a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"not found\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn command_info_should_describe_command() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "command info get xxx").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"get 2 readonly 1 1 1\nnil\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn config_set_should_apply_to_running_server() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "config set maxclients 1").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"config ok\n".to_vec());
+        server_utils::write_message(&mut writer, "config get maxc*").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"maxclients 1\n".to_vec());
+
+        let mut other_socket = utils::start_client(port).await;
+        let (mut other_reader, _) = other_socket.split();
+        let response = client_utils::read_message(&mut other_reader).await;
+        assert_eq!(response, b"err max number of clients reached\n".to_vec());
+
+        server_utils::write_message(&mut writer, "config set port 1").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(
+            response,
+            b"err CONFIG SET failed: 'port' can't be changed while the server runs\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn requirepass_should_require_auth_from_new_clients() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "config set requirepass s3cret").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"config ok\n".to_vec());
+        // the clients already connected stay authenticated
+        server_utils::write_message(&mut writer, "set a 1").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"set ok\n".to_vec());
+
+        let mut other_socket = utils::start_client(port).await;
+        let (mut other_reader, mut other_writer) = other_socket.split();
+        for (message, expected) in [
+            ("get a", "err NOAUTH Authentication required.\n"),
+            ("ping", "pong\n"),
+            ("auth wrong", "err WRONGPASS invalid username-password pair\n"),
+            ("auth s3cret", "auth ok\n"),
+            ("get a", "1\n"),
+        ] {
+            server_utils::write_message(&mut other_writer, message).await;
+            let response = client_utils::read_message(&mut other_reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+    }
+
+    #[tokio::test]
+    async fn acl_user_should_be_limited_to_its_permissions() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        for (message, expected) in [
+            ("acl setuser alice on >wonderland ~cache* -@all +get +acl|whoami", "setuser ok\n"),
+            ("set cache1 1", "set ok\n"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+
+        let mut other_socket = utils::start_client(port).await;
+        let (mut other_reader, mut other_writer) = other_socket.split();
+        for (message, expected) in [
+            ("auth alice wonderland", "auth ok\n"),
+            ("acl whoami", "alice\n"),
+            ("get cache1", "1\n"),
+            ("get other", "err NOPERM no permissions to access a key\n"),
+            ("set cache1 2", "err NOPERM user 'alice' has no permissions to run the 'set' command\n"),
+        ] {
+            server_utils::write_message(&mut other_writer, message).await;
+            let response = client_utils::read_message(&mut other_reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+
+        server_utils::write_message(&mut writer, "acl log 1").await;
+        let response = String::from_utf8(client_utils::read_message(&mut reader).await).unwrap();
+        assert!(
+            response.starts_with("count 1 reason command object set username alice "),
+            "{}",
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn numbered_databases_should_keep_keys_separate() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        for (message, expected) in [
+            ("select 1", "select ok
+"),
+            ("set a one", "set ok
+"),
+            ("select 0", "select ok
+"),
+            ("get a", "not found
+"),
+            ("set b two", "set ok
+"),
+            ("move b 1", "1
+"),
+            ("get b", "not found
+"),
+            ("select 16", "err DB index is out of range
+"),
+            ("select 1", "select ok
+"),
+            ("get b", "two
+"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        assert!(temp_dir.path().join("db-1").join("b").is_file());
+        assert!(!temp_dir.path().join("b").exists());
+
+        for (message, expected) in [
+            ("swapdb 0 1", "swapdb ok
+"),
+            ("get a", "not found
+"),
+            ("select 0", "select ok
+"),
+            ("get a", "one
+"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        assert!(temp_dir.path().join("a").is_file());
+        assert!(!temp_dir.path().join("db-1").join("a").exists());
+
+        for (message, expected) in [
+            ("flushdb", "flushdb ok
+"),
+            ("get b", "not found
+"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        assert!(!temp_dir.path().join("a").exists());
+    }
+
+    #[tokio::test]
+    async fn keys_and_scan_should_list_keys() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        for index in 0..25 {
+            server_utils::write_message(&mut writer, &format!("set user{} {}", index, index)).await;
+            let _ = client_utils::read_message(&mut reader).await;
+        }
+        server_utils::write_message(&mut writer, "set order1 1").await;
+        let _ = client_utils::read_message(&mut reader).await;
+
+        server_utils::write_message(&mut writer, "keys user1?").await;
+        let response = client_utils::read_message(&mut reader).await;
+        let expected: Vec<String> = (10..20).map(|index| format!("user{}\n", index)).collect();
+        assert_eq!(String::from_utf8(response).unwrap(), expected.concat());
+
+        let mut scanned = Vec::new();
+        let mut cursor = "0".to_owned();
+        loop {
+            server_utils::write_message(&mut writer, &format!("scan {} match user* count 5", cursor)).await;
+            let response = String::from_utf8(client_utils::read_message(&mut reader).await).unwrap();
+            let mut lines = response.lines();
+            cursor = lines.next().unwrap().to_owned();
+            scanned.extend(lines.map(str::to_owned));
+            if cursor == "0" {
+                break;
+            }
+        }
+        scanned.sort();
+        let mut expected: Vec<String> = (0..25).map(|index| format!("user{}", index)).collect();
+        expected.sort();
+        assert_eq!(scanned, expected);
+    }
+
+    #[tokio::test]
+    async fn maxmemory_should_evict_keys_or_refuse_writes() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        // the memory is checked before a command runs, so the first value goes over the limit
+        let large_value = "x".repeat(600);
+        for (message, expected) in [
+            ("config set maxmemory 500 maxmemory-policy noeviction".to_owned(), "config ok\n"),
+            (format!("set a {}", large_value), "set ok\n"),
+            ("set b 2".to_owned(), "err OOM command not allowed when used memory > 'maxmemory'.\n"),
+            ("get b".to_owned(), "not found\n"),
+            ("config set maxmemory-policy allkeys-lru".to_owned(), "config ok\n"),
+        ] {
+            server_utils::write_message(&mut writer, &message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+
+        let value = "x".repeat(200);
+        for key in ["b", "c", "d", "e"] {
+            server_utils::write_message(&mut writer, &format!("set {} {}", key, value)).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(response, b"set ok\n".to_vec(), "{}", key);
+        }
+        server_utils::write_message(&mut writer, "keys *").await;
+        let response = String::from_utf8(client_utils::read_message(&mut reader).await).unwrap();
+        assert!(response.lines().count() < 5, "{}", response);
+        assert!(!temp_dir.path().join("a").exists());
+    }
+
+    #[tokio::test]
+    async fn set_options_and_multi_key_commands_should_be_handled() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        for (message, expected) in [
+            ("set lock owner1 nx ex 10", "set ok\n"),
+            ("set lock owner2 nx ex 10", "nil\n"),
+            ("set lock owner2 xx get keepttl", "owner1\n"),
+            ("mset a 1 b 2 c 3", "mset ok\n"),
+            ("msetnx c 4 d 4", "0\n"),
+            ("mget a b c d lock", "1\n2\n3\nnil\nowner2\n"),
+            ("getset a 5", "1\n"),
+            ("getdel b", "2\n"),
+            ("getex c persist", "3\n"),
+            ("get b", "not found\n"),
+            ("set spaced hello  big\tworld", "set ok\n"),
+            ("get spaced", "hello  big\tworld\n"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        assert!(temp_dir.path().join("a").is_file());
+        assert!(temp_dir.path().join("c").is_file());
+        assert!(!temp_dir.path().join("b").exists());
+        assert!(!temp_dir.path().join("d").exists());
+        assert!(!temp_dir.path().join("write-journal").exists());
+    }
+
+    #[tokio::test]
+    async fn string_manipulation_commands_should_update_cache() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        for (message, expected) in [
+            ("append log hello", "5\n"),
+            ("append log -world", "11\n"),
+            ("setrange log 6 W", "11\n"),
+            ("setrange record 2 ab", "4\n"),
+            ("strlen log", "11\n"),
+            ("getrange log -5 -1", "World\n"),
+            ("set other yellow", "set ok\n"),
+            ("lcs log other", "ello\n"),
+            ("set session hi ex 100", "set ok\n"),
+            ("append session !", "3\n"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        let log = tokio::fs::read(temp_dir.path().join("log")).await.unwrap();
+        assert_eq!(log, [&[1, 0, 0, 0, 0, 0, 0, 0, 11][..], b"hello-World"].concat());
+        let record = tokio::fs::read(temp_dir.path().join("record")).await.unwrap();
+        assert_eq!(record, vec![1, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 97, 98]);
+        // the deadline precedes the value
+        let session = tokio::fs::read(temp_dir.path().join("session")).await.unwrap();
+        assert_eq!(session[0], 0);
+        assert_eq!(session[9..], [&[1, 0, 0, 0, 0, 0, 0, 0, 3][..], b"hi!"].concat());
+    }
+
+    #[tokio::test]
+    async fn bitmap_commands_should_update_cache() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+        for (message, expected) in [
+            ("setbit day1 8000000 1", "0\n"),
+            ("setbit day1 7 1", "0\n"),
+            ("setbit day2 7 1", "0\n"),
+            ("getbit day1 8000000", "1\n"),
+            ("bitcount day1", "2\n"),
+            ("bitpos day1 1 1", "8000000\n"),
+            ("bitop and both day1 day2", "1000001\n"),
+            ("bitcount both", "1\n"),
+            ("bitfield counters incrby u8 #1 200 overflow sat incrby u8 #1 100", "200\n255\n"),
+            ("bitfield_ro counters get u16 0", "255\n"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        let day1 = tokio::fs::read(temp_dir.path().join("day1")).await.unwrap();
+        assert_eq!(day1.len(), 9 + 1000001);
+        assert_eq!((day1[9], day1[9 + 1000000]), (1, 0x80));
+        let counters = tokio::fs::read(temp_dir.path().join("counters")).await.unwrap();
+        assert_eq!(counters, vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 255]);
+    }
 }
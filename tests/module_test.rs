@@ -0,0 +1,87 @@
+pub mod utils;
+
+mod module {
+    use server::core::module::example::CounterModule;
+
+    use crate::utils::{TEST_CONNECTION_HOST, TEST_CONNECTION_PORT};
+
+    use super::utils;
+    use super::utils::client as client_utils;
+    use super::utils::file as file_utils;
+    use super::utils::server as server_utils;
+
+    #[tokio::test]
+    async fn module_commands_should_be_handled() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server_with_modules(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            &[&CounterModule],
+        )
+        .await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "module list").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"counter\n".to_vec());
+
+        server_utils::write_message(&mut writer, "counter.incrby a 5").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"5\n".to_vec());
+
+        server_utils::write_message(&mut writer, "COUNTER.INCRBY a -2").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"3\n".to_vec());
+
+        server_utils::write_message(&mut writer, "get a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(
+            response,
+            b"err WRONGTYPE Operation against a key holding the wrong kind of value\n".to_vec()
+        );
+
+        server_utils::write_message(&mut writer, "set b hello").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        server_utils::write_message(&mut writer, "counter.get b").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(
+            response,
+            b"err WRONGTYPE Operation against a key holding the wrong kind of value\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn module_values_should_be_recovered_from_cache() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server_with_modules(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            &[&CounterModule],
+        )
+        .await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "counter.incrby a 7").await;
+        let _ = client_utils::read_message(&mut reader).await;
+        let cache_file = std::fs::read(temp_dir.path().join("a")).unwrap();
+        assert_eq!(cache_file, vec![200, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 7]);
+
+        let port = utils::start_server_with_modules(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            &[&CounterModule],
+        )
+        .await;
+        let mut socket = utils::start_client(port).await;
+        let (mut reader, mut writer) = socket.split();
+
+        server_utils::write_message(&mut writer, "counter.get a").await;
+        let response = client_utils::read_message(&mut reader).await;
+        assert_eq!(response, b"7\n".to_vec());
+    }
+}
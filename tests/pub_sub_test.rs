@@ -1,6 +1,8 @@
 pub mod utils;
 
 mod pub_sub {
+    use tokio::fs::metadata;
+
     use crate::utils::{TEST_CONNECTION_HOST, TEST_CONNECTION_PORT};
 
     use super::utils;
@@ -70,4 +72,137 @@ mod pub_sub {
             vec![117, 98, 110, 115, 117, 98, 115, 99, 114, 105, 98, 101, 100, 32, 111, 107, 10]
         );
     }
+
+    #[tokio::test]
+    async fn pubsub_introspection() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut client1 = utils::start_client(port).await;
+        let mut client2 = utils::start_client(port).await;
+        let (mut reader1, mut writer1) = client1.split();
+        let (mut reader2, mut writer2) = client2.split();
+
+        server_utils::write_message(&mut writer1, "subscribe topicA").await;
+        let _ = client_utils::read_message(&mut reader1).await;
+
+        server_utils::write_message(&mut writer2, "pubsub channels").await;
+        let response = client_utils::read_message(&mut reader2).await;
+        assert_eq!(response, b"topicA\n".to_vec());
+
+        server_utils::write_message(&mut writer2, "pubsub numsub topicA topicB").await;
+        let response = client_utils::read_message(&mut reader2).await;
+        assert_eq!(response, b"topicA 1\ntopicB 0\n".to_vec());
+
+        server_utils::write_message(&mut writer2, "pubsub numpat").await;
+        let response = client_utils::read_message(&mut reader2).await;
+        assert_eq!(response, b"0\n".to_vec());
+
+        // the channel is gone once its last subscriber leaves
+        server_utils::write_message(&mut writer1, "unsubscribe").await;
+        let _ = client_utils::read_message(&mut reader1).await;
+
+        server_utils::write_message(&mut writer2, "pubsub channels").await;
+        let response = client_utils::read_message(&mut reader2).await;
+        assert_eq!(response, b"empty\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn subscribe_from_replays_durable_topic() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut client1 = utils::start_client(port).await;
+        let mut client2 = utils::start_client(port).await;
+        let mut client3 = utils::start_client(port).await;
+        let (mut reader1, mut writer1) = client1.split();
+        let (mut reader2, mut writer2) = client2.split();
+        let (mut reader3, mut writer3) = client3.split();
+
+        server_utils::write_message(&mut writer1, "durable topicA maxlen 2").await;
+        let response = client_utils::read_message(&mut reader1).await;
+        assert_eq!(response, b"durable ok\n".to_vec());
+
+        server_utils::write_message(&mut writer1, "subscribe topicA").await;
+        let _ = client_utils::read_message(&mut reader1).await;
+        server_utils::write_message(&mut writer2, "subscribe topicA").await;
+        let _ = client_utils::read_message(&mut reader2).await;
+        for message in ["one", "two", "three"] {
+            server_utils::write_message(&mut writer1, message).await;
+            let response = client_utils::read_message(&mut reader2).await;
+            assert_eq!(response, message.as_bytes().to_vec());
+        }
+
+        // the first message has been dropped by the retention policy, the messages come with their offsets
+        server_utils::write_message(&mut writer3, "subscribe topicA from 0").await;
+        let expected = b"subscribed ok\n1 two2 three".to_vec();
+        let mut response = Vec::new();
+        while response.len() < expected.len() {
+            response.extend(client_utils::read_message(&mut reader3).await);
+        }
+        assert_eq!(response, expected);
+        server_utils::write_message(&mut writer1, "four").await;
+        let response = client_utils::read_message(&mut reader3).await;
+        assert_eq!(response, b"3 four".to_vec());
+        let response = client_utils::read_message(&mut reader2).await;
+        assert_eq!(response, b"four".to_vec());
+
+        let history_file = temp_dir.path().join("topic-history").join("topicA");
+        let file_exists = metadata(history_file).await.unwrap().is_file();
+        assert!(file_exists);
+    }
+
+    #[tokio::test]
+    async fn durable_topic_history_should_not_clash_with_keys() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut client = utils::start_client(port).await;
+        let (mut reader, mut writer) = client.split();
+
+        for (message, expected) in [
+            ("durable topicA maxlen 2", "durable ok\n"),
+            ("set topics hello", "set ok\n"),
+            ("durable topicB maxlen 2", "durable ok\n"),
+            ("get topics", "hello\n"),
+        ] {
+            server_utils::write_message(&mut writer, message).await;
+            let response = client_utils::read_message(&mut reader).await;
+            assert_eq!(String::from_utf8(response).unwrap(), expected, "{}", message);
+        }
+        assert!(metadata(temp_dir.path().join("topics")).await.unwrap().is_file());
+    }
+
+    #[tokio::test]
+    async fn keyspace_notifications() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut client1 = utils::start_client(port).await;
+        let mut client2 = utils::start_client(port).await;
+        let mut client3 = utils::start_client(port).await;
+        let (mut reader1, mut writer1) = client1.split();
+        let (mut reader2, mut writer2) = client2.split();
+        let (mut reader3, mut writer3) = client3.split();
+
+        server_utils::write_message(&mut writer1, "subscribe __keyspace@0__:a").await;
+        let _ = client_utils::read_message(&mut reader1).await;
+        server_utils::write_message(&mut writer2, "subscribe __keyevent@0__:expired").await;
+        let _ = client_utils::read_message(&mut reader2).await;
+
+        server_utils::write_message(&mut writer3, "set a aa").await;
+        let _ = client_utils::read_message(&mut reader3).await;
+        let message = client_utils::read_message(&mut reader1).await;
+        assert_eq!(message, b"set".to_vec());
+
+        server_utils::write_message(&mut writer3, "expire a 0").await;
+        let response = client_utils::read_message(&mut reader3).await;
+        assert_eq!(response, b"1\n".to_vec());
+
+        // removed by the active expiry
+        let message = client_utils::read_message(&mut reader2).await;
+        assert_eq!(message, b"a".to_vec());
+        let expected = b"expireexpired".to_vec();
+        let mut messages = Vec::new();
+        while messages.len() < expected.len() {
+            messages.extend(client_utils::read_message(&mut reader1).await);
+        }
+        assert_eq!(messages, expected);
+    }
 }
@@ -0,0 +1,257 @@
+pub mod utils;
+
+mod tls {
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::Mutex;
+
+    use client::core::client::ClientService;
+    use server::core::tls::ClientAuth;
+
+    use crate::utils::TEST_CONNECTION_HOST;
+    use crate::utils::TEST_CONNECTION_PORT;
+
+    use super::utils;
+    use super::utils::client as client_utils;
+    use super::utils::file as file_utils;
+
+    type Recording = Arc<Mutex<Vec<u8>>>;
+
+    async fn start_recording_proxy(server_port: u16) -> (u16, Recording) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let recording: Recording = Arc::new(Mutex::new(Vec::new()));
+        let proxy_recording = recording.clone();
+        tokio::spawn(async move {
+            let (client, _) = listener.accept().await.unwrap();
+            let server = TcpStream::connect(format!("localhost:{}", server_port)).await.unwrap();
+            let (client_reader, client_writer) = client.into_split();
+            let (server_reader, server_writer) = server.into_split();
+            tokio::spawn(forward(client_reader, server_writer, proxy_recording.clone()));
+            forward(server_reader, client_writer, proxy_recording).await;
+        });
+        (port, recording)
+    }
+
+    async fn forward(
+        mut reader: impl AsyncRead + Unpin,
+        mut writer: impl AsyncWrite + Unpin,
+        recording: Recording,
+    ) {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let size = reader.read(&mut buffer).await.unwrap_or(0);
+            if size == 0 {
+                break;
+            }
+            recording.lock().await.extend_from_slice(&buffer[..size]);
+            if writer.write_all(&buffer[..size]).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn send<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, message: &str) -> Vec<u8> {
+        stream.write_all(message.as_bytes()).await.unwrap();
+        stream.flush().await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let size = stream.read(&mut buffer).await.unwrap();
+        buffer[..size].to_vec()
+    }
+
+    #[tokio::test]
+    async fn tls_connection_should_be_encrypted() {
+        let temp_dir = file_utils::create_temp_folder();
+        let server_port =
+            utils::start_tls_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let (proxy_port, recording) = start_recording_proxy(server_port).await;
+        let client = client_utils::new_tls_client("localhost", &proxy_port.to_string());
+        let mut stream = client.connect().await.unwrap();
+
+        let set_response = send(&mut stream, "set password hunter2-hunter2").await;
+        let get_response = send(&mut stream, "get password").await;
+
+        assert_eq!(set_response, b"set ok\n".to_vec());
+        assert_eq!(get_response, b"hunter2-hunter2\n".to_vec());
+        let recording = recording.lock().await;
+        // a TLS handshake record
+        assert_eq!(recording.first(), Some(&0x16));
+        for plaintext in ["password", "hunter2-hunter2", "set ok"] {
+            assert!(
+                !recording
+                    .windows(plaintext.len())
+                    .any(|window| window == plaintext.as_bytes()),
+                "'{}' went through the network in plaintext",
+                plaintext
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn tls_server_should_survive_failed_handshake() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port =
+            utils::start_tls_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let mut plain_socket = TcpStream::connect(format!("localhost:{}", port)).await.unwrap();
+        plain_socket.write_all(b"ping").await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let _ = plain_socket.read(&mut buffer).await;
+
+        let client = client_utils::new_tls_client("localhost", &port.to_string());
+        let mut stream = client.connect().await.unwrap();
+
+        assert_eq!(send(&mut stream, "ping").await, b"pong\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn mtls_client_should_be_authenticated_by_its_certificate() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_mtls_server(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            ClientAuth::Required,
+        )
+        .await;
+        let client = client_utils::new_tls_client("localhost", &port.to_string());
+        let mut stream = client.connect().await.unwrap();
+
+        let response = String::from_utf8(send(&mut stream, "client info").await).unwrap();
+
+        assert!(response.contains("\nuser client\n"), "{}", response);
+        assert!(response.contains("\ncert-subject CN=client\n"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn mtls_server_should_refuse_client_without_certificate() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_mtls_server(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            ClientAuth::Required,
+        )
+        .await;
+
+        // with TLS 1.3 the client learns that it is refused once it reads
+        let refused = match client_utils::connect_without_cert(port).await {
+            Ok(mut stream) => {
+                let _ = stream.write_all(b"ping").await;
+                let _ = stream.flush().await;
+                let mut buffer = [0u8; 1024];
+                !matches!(stream.read(&mut buffer).await, Ok(size) if size > 0)
+            }
+            Err(_) => true,
+        };
+        assert!(refused);
+    }
+
+    #[tokio::test]
+    async fn mtls_optional_should_accept_client_without_certificate() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port = utils::start_mtls_server(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            ClientAuth::Optional,
+        )
+        .await;
+        let mut stream = client_utils::connect_without_cert(port).await.unwrap();
+
+        let response = String::from_utf8(send(&mut stream, "client info").await).unwrap();
+
+        assert!(response.contains("\nuser default\n"), "{}", response);
+        assert!(!response.contains("cert-subject"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn tls_client_should_verify_server_certificate() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port =
+            utils::start_tls_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let port = port.to_string();
+
+        let untrusting_client = client_utils::new_untrusting_tls_client("localhost", &port);
+        assert!(untrusting_client.connect().await.is_err());
+        let wrong_name_client =
+            client_utils::new_tls_client("localhost", &port).with_server_name("example.com");
+        assert!(wrong_name_client.connect().await.is_err());
+
+        let client = client_utils::new_tls_client("127.0.0.1", &port).with_server_name("localhost");
+        let mut stream = client.connect().await.unwrap();
+        assert_eq!(send(&mut stream, "ping").await, b"pong\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn tls_client_should_accept_pinned_certificate_only() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port =
+            utils::start_tls_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let port = port.to_string();
+
+        let other_pin_client = client_utils::new_untrusting_tls_client("localhost", &port)
+            .with_pinned_cert(&utils::cert_path("client.crt"));
+        assert!(other_pin_client.connect().await.is_err());
+
+        // the pinned certificate is accepted whatever its names and issuer
+        let client = client_utils::new_untrusting_tls_client("127.0.0.1", &port)
+            .with_server_name("example.com")
+            .with_pinned_cert(&utils::cert_path("server.crt"));
+        let mut stream = client.connect().await.unwrap();
+        assert_eq!(send(&mut stream, "ping").await, b"pong\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn insecure_tls_client_should_accept_any_certificate() {
+        let temp_dir = file_utils::create_temp_folder();
+        let port =
+            utils::start_tls_server(TEST_CONNECTION_HOST, TEST_CONNECTION_PORT, &temp_dir).await;
+        let client = client_utils::new_untrusting_tls_client("localhost", &port.to_string()).insecure();
+
+        let mut stream = client.connect().await.unwrap();
+
+        assert_eq!(send(&mut stream, "ping").await, b"pong\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn tls_reload_should_apply_to_new_connections_only() {
+        let temp_dir = file_utils::create_temp_folder();
+        let cert_file = temp_dir.path().join("tls.crt").display().to_string();
+        let key_file = temp_dir.path().join("tls.key").display().to_string();
+        std::fs::copy(utils::cert_path("client.crt"), &cert_file).unwrap();
+        std::fs::copy(utils::cert_path("client.key"), &key_file).unwrap();
+        let port = utils::start_tls_server_with_cert(
+            TEST_CONNECTION_HOST,
+            TEST_CONNECTION_PORT,
+            &temp_dir,
+            &cert_file,
+            &key_file,
+            ClientAuth::No,
+        )
+        .await;
+        let port = port.to_string();
+        let pinned_client = |pinned_cert: &str| {
+            client_utils::new_untrusting_tls_client("localhost", &port)
+                .with_pinned_cert(&utils::cert_path(pinned_cert))
+        };
+        let mut stream = pinned_client("client.crt").connect().await.unwrap();
+
+        // the current files are kept when the new ones are invalid
+        std::fs::write(&key_file, "").unwrap();
+        let response = send(&mut stream, "tls reload").await;
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.contains("tls.key' contains no private key"), "{}", response);
+        assert!(pinned_client("client.crt").connect().await.is_ok());
+
+        std::fs::copy(utils::cert_path("server.crt"), &cert_file).unwrap();
+        std::fs::copy(utils::cert_path("server.key"), &key_file).unwrap();
+        assert_eq!(send(&mut stream, "tls reload").await, b"reload ok\n".to_vec());
+
+        assert_eq!(send(&mut stream, "ping").await, b"pong\n".to_vec());
+        let mut new_stream = pinned_client("server.crt").connect().await.unwrap();
+        assert_eq!(send(&mut new_stream, "ping").await, b"pong\n".to_vec());
+        assert!(pinned_client("client.crt").connect().await.is_err());
+    }
+}
@@ -1,12 +1,43 @@
+use std::path::Path;
+use std::sync::Arc;
+
 use tokio::io::AsyncReadExt;
 use tokio::net::tcp::ReadHalf;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+
+use client::core::client::{MyClientService, MyNonSecureClientService};
 
-use client::core::client::MyNonSecureClientService;
+use crate::utils::cert_path;
 
 pub fn new_client(address: &str, port: &str) -> MyNonSecureClientService {
     MyNonSecureClientService::new(address, port)
 }
 
+pub fn new_tls_client(address: &str, port: &str) -> MyClientService {
+    new_untrusting_tls_client(address, port).with_ca_file_path(&cert_path("ca.crt"))
+}
+
+pub fn new_untrusting_tls_client(address: &str, port: &str) -> MyClientService {
+    MyClientService::new(address, port, &cert_path("client.crt"), &cert_path("client.key"))
+}
+
+pub async fn connect_without_cert(port: u16) -> std::io::Result<TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    for cert in utils::cert::load_cert(Path::new(&cert_path("ca.crt")))? {
+        roots.add(&cert).unwrap();
+    }
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let socket = TcpStream::connect(format!("localhost:{}", port)).await?;
+    let domain = ServerName::try_from("localhost").unwrap();
+    TlsConnector::from(Arc::new(config)).connect(domain, socket).await
+}
+
 pub async fn read_message(reader: &mut ReadHalf<'_>) -> Vec<u8> {
     let mut buffer = [0u8; 1024];
     let _ = reader
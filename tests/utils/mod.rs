@@ -1,7 +1,11 @@
+use std::sync::OnceLock;
+
 use tempdir::TempDir;
 use tokio::net::TcpStream;
 
 use ::client::core::client::ClientService;
+use ::server::core::module::Module;
+use ::server::core::tls::ClientAuth;
 
 use crate::utils::client::new_client;
 
@@ -13,14 +17,59 @@ pub const TEST_CONNECTION_HOST: &str = "localhost";
 pub const TEST_CONNECTION_PORT: &str = "0";
 
 pub async fn start_server(host: &str, port: &str, temp_dir: &TempDir) -> u16 {
+    start_server_with_modules(host, port, temp_dir, &[]).await
+}
+
+pub async fn start_server_with_modules(
+    host: &str,
+    port: &str,
+    temp_dir: &TempDir,
+    modules: &[&dyn Module],
+) -> u16 {
     let temp_dir = temp_dir.path().display().to_string();
-    let rx = server::start_server(host, port, &temp_dir);
+    let rx = server::start_server(host, port, &temp_dir, modules);
     rx.await.unwrap()
 }
 
+pub async fn start_tls_server(host: &str, port: &str, temp_dir: &TempDir) -> u16 {
+    start_mtls_server(host, port, temp_dir, ClientAuth::No).await
+}
+
+pub async fn start_mtls_server(host: &str, port: &str, temp_dir: &TempDir, client_auth: ClientAuth) -> u16 {
+    let cert_file_path = cert_path("server.crt");
+    let key_file_path = cert_path("server.key");
+    start_tls_server_with_cert(host, port, temp_dir, &cert_file_path, &key_file_path, client_auth).await
+}
+
+pub async fn start_tls_server_with_cert(
+    host: &str,
+    port: &str,
+    temp_dir: &TempDir,
+    cert_file_path: &str,
+    key_file_path: &str,
+    client_auth: ClientAuth,
+) -> u16 {
+    let temp_dir = temp_dir.path().display().to_string();
+    let rx = server::start_tls_server(host, port, &temp_dir, cert_file_path, key_file_path, client_auth);
+    rx.await.unwrap()
+}
+
+/// The certificates generated once for the tests: `ca`, `server` valid for localhost and 127.0.0.1
+/// and `client`, e.g. `cert_path("client.key")`.
+pub fn cert_path(file_name: &str) -> String {
+    static CERTS: OnceLock<TempDir> = OnceLock::new();
+    let certs = CERTS.get_or_init(|| {
+        let temp_dir = TempDir::new("test-certs").unwrap();
+        let server_names = ["localhost".to_owned(), "127.0.0.1".to_owned()];
+        utils::dev_certs::generate(temp_dir.path(), &server_names, &["client".to_owned()]).unwrap();
+        temp_dir
+    });
+    certs.path().join(file_name).display().to_string()
+}
+
 pub async fn start_client(port: u16) -> TcpStream {
     let client = new_client("localhost", &port.to_string());
-    client.connect().await
+    client.connect().await.unwrap()
 }
 
 // fn mut_mock<T>(mock: &mut Arc<T>) -> &mut T {
@@ -5,33 +5,91 @@ use tokio::net::tcp::{ReadHalf, WriteHalf};
 use tokio::sync::oneshot;
 use tokio::sync::oneshot::Receiver;
 
+use server::core::auth::{AuthService, MyAuthService};
 use server::core::broker::MyBrokerService;
+use server::core::cache::history::MyHistoryStore;
 use server::core::cache::reader::MyCacheReader;
 use server::core::cache::writer::MyCacheWriter;
+use server::core::config::service::MyConfigService;
+use server::core::config::Config;
 use server::core::handler::MyHandlerService;
+use server::core::module::Module;
+use server::core::notify::KeyspaceEvents;
 use server::core::redis::MyRedisService;
-use server::core::server::{MyNonSecureServerService, ServerService};
+use server::core::server::{ClientLimits, MyNonSecureServerService, MyServerService, ServerService};
+use server::core::tls::{CertUser, ClientAuth, MyTlsService};
 
-pub fn start_server(host: &str, port: &str, cache_folder: &str) -> Receiver<u16> {
+use crate::utils::cert_path;
+
+pub fn start_server(host: &str, port: &str, cache_folder: &str, modules: &[&dyn Module]) -> Receiver<u16> {
+    let (handler_service, client_limits) = new_handler_service(cache_folder, modules);
+    let server_service =
+        MyNonSecureServerService::new(host, port, Arc::new(handler_service)).with_client_limits(client_limits);
+    spawn_server(server_service)
+}
+
+pub fn start_tls_server(
+    host: &str,
+    port: &str,
+    cache_folder: &str,
+    cert_file_path: &str,
+    key_file_path: &str,
+    client_auth: ClientAuth,
+) -> Receiver<u16> {
+    let (handler_service, client_limits) = new_handler_service(cache_folder, &[]);
+    let auth_service = Arc::new(MyAuthService::new());
+    auth_service
+        .set_user("client", &["on".to_owned(), "allkeys".to_owned(), "allcommands".to_owned()])
+        .unwrap();
+    let tls_service = MyTlsService::new(cert_file_path, key_file_path)
+        .unwrap()
+        .with_client_auth(&cert_path("ca.crt"), client_auth)
+        .unwrap();
+    let tls_service = Arc::new(tls_service);
+    let handler_service = handler_service
+        .with_auth_service(auth_service)
+        .with_cert_user(CertUser::CommonName)
+        .with_tls_service(tls_service.clone());
+    let server_service = MyServerService::new(host, port, tls_service, Arc::new(handler_service))
+        .with_client_limits(client_limits);
+    spawn_server(server_service)
+}
+
+fn spawn_server(server_service: impl ServerService + 'static) -> Receiver<u16> {
     let (started_signal_tx, started_signal_rx) = oneshot::channel::<u16>();
-    let server_service = new_server(host, port, cache_folder);
     tokio::spawn(async move {
         server_service.start(started_signal_tx).await.unwrap();
     });
     started_signal_rx
 }
 
-fn new_server(host: &str, port: &str, cache_folder: &str) -> MyNonSecureServerService {
+fn new_handler_service(
+    cache_folder: &str,
+    modules: &[&dyn Module],
+) -> (MyHandlerService, ClientLimits) {
     let cache_reader_service = Arc::new(MyCacheReader::new(cache_folder));
     let cache_writer_service = Arc::new(MyCacheWriter::new(cache_folder));
-    let redis_service = Arc::new(MyRedisService::new(
-        cache_reader_service,
-        cache_writer_service,
-    ));
-    let broker_service = Arc::new(MyBrokerService::new());
-    let handler_service = Arc::new(MyHandlerService::new(redis_service, broker_service));
-
-    MyNonSecureServerService::new(host, port, handler_service)
+    let history_store = Arc::new(MyHistoryStore::new(cache_folder));
+    let broker_service = Arc::new(MyBrokerService::new().with_history_store(history_store));
+    let keyspace_events = KeyspaceEvents::parse("KEA").unwrap();
+    let redis_service = Arc::new(
+        MyRedisService::new(cache_reader_service, cache_writer_service)
+            .with_keyspace_notifications(broker_service.clone(), keyspace_events),
+    );
+    let auth_service = Arc::new(MyAuthService::new());
+    let client_limits = ClientLimits::default();
+    let config_service = MyConfigService::new(Config::default())
+        .with_listener(redis_service.clone())
+        .with_listener(broker_service.clone())
+        .with_listener(auth_service.clone())
+        .with_listener(Arc::new(client_limits.clone()));
+    let mut handler_service = MyHandlerService::new(redis_service, broker_service)
+        .with_config_service(Arc::new(config_service))
+        .with_auth_service(auth_service);
+    for module in modules.iter() {
+        handler_service = handler_service.with_module(*module).unwrap();
+    }
+    (handler_service, client_limits)
 }
 
 pub async fn write_message(writer: &mut WriteHalf<'_>, message: &str) {